@@ -0,0 +1,54 @@
+use crate::utils::{self, error, format_size, guard, is_package_used, list_cached_packages, normalize_package_name, read_registry, AnyError};
+
+/// Removes every cached version of the given packages.
+///
+/// # Arguments
+/// - `names`: The packages to remove, matched as PyPI does, ex. `typing_extensions` is `Typing-Extensions`.
+/// - `force`: Whether to remove versions that registered environments still link to.
+///
+/// # Termination
+/// - This function returns an error if a package isn't in the cache, or if a version is still used and `force` isn't set.
+///   Nothing is removed in that case.
+pub fn cache_clean(names: Vec<&String>, force: bool) -> Result<(), AnyError> {
+	let packages = guard!(list_cached_packages(), "Couldn't list the cached packages.");
+	let registry = guard!(read_registry(), "Couldn't read the environments registered in pen.");
+
+	let mut removed_packages = Vec::new();
+	for name in names {
+		let normalized_name = normalize_package_name(name);
+		let matching_packages = packages
+			.iter()
+			.filter(|package| normalize_package_name(&package.name) == normalized_name)
+			.collect::<Vec<_>>();
+
+		if matching_packages.is_empty() {
+			return error!("{} is not in the cache.", name);
+		}
+		removed_packages.extend(matching_packages);
+	}
+
+	let used_packages = removed_packages
+		.iter()
+		.filter(|package| is_package_used(&registry, &package.path))
+		.collect::<Vec<_>>();
+	if !used_packages.is_empty() {
+		println!("These packages are used by registered environments:");
+		for package in &used_packages {
+			println!("  - {} {}", package.name, package.version);
+		}
+		if !force {
+			return error!("Packages are still in use. Use --force to remove them anyway.");
+		}
+		println!("The environments linking to them will be broken until `pen sync` downloads them again.");
+	}
+
+	let mut freed_size = 0;
+	for package in removed_packages {
+		guard!(utils::try_deleting_dir(&package.path), "Failed to delete {}", package.path.display());
+		println!("Removed {} {}", package.name, package.version);
+		freed_size += package.size;
+	}
+
+	println!("Freed {}", format_size(freed_size));
+	return Ok(());
+}
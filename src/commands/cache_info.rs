@@ -0,0 +1,33 @@
+use crate::constants::PYTHON_PACKAGES_DIR;
+use crate::utils::{format_size, guard, list_cached_packages, AnyError};
+
+pub fn cache_info() -> Result<(), AnyError> {
+	let packages = guard!(list_cached_packages(), "Couldn't list the cached packages.");
+
+	// Group the cached versions by package, the list is already sorted by name
+	let mut package_totals: Vec<(&str, usize, u64)> = Vec::new();
+	for package in &packages {
+		match package_totals.last_mut() {
+			Some((name, count, size)) if *name == package.name => {
+				*count += 1;
+				*size += package.size;
+			}
+			_ => package_totals.push((&package.name, 1, package.size)),
+		}
+	}
+
+	let total_size: u64 = packages.iter().map(|package| package.size).sum();
+	println!("Package cache: {}", PYTHON_PACKAGES_DIR.display());
+	println!(
+		"Total: {} in {} entries of {} packages",
+		format_size(total_size),
+		packages.len(),
+		package_totals.len()
+	);
+
+	for (name, count, size) in package_totals {
+		let entries = if count == 1 { "entry" } else { "entries" };
+		println!("  - {} ({} {}): {}", name, count, entries, format_size(size));
+	}
+	return Ok(());
+}
@@ -0,0 +1,51 @@
+use crate::utils::{self, format_size, get_package_path, guard, is_package_used, list_cached_packages, read_lockfile, read_registry, AnyError};
+use std::collections::HashSet;
+
+pub fn cache_prune() -> Result<(), AnyError> {
+	let registry = guard!(read_registry(), "Couldn't read the environments registered in pen.");
+	let packages = guard!(list_cached_packages(), "Couldn't list the cached packages.");
+
+	// The locked packages of a project are kept even if its environment wasn't synced with them yet, ex. after `pen add --no-sync`
+	let mut locked_paths = HashSet::new();
+	for env_path in registry.environments.keys() {
+		let project_path = match env_path.parent() {
+			Some(project_path) => project_path.to_path_buf(),
+			None => continue,
+		};
+		if let Some(lockfile) = read_lockfile(&project_path)? {
+			locked_paths.extend(lockfile.packages.iter().map(|package| get_package_path(&package.to_package())));
+		}
+	}
+
+	let unused_packages = packages
+		.into_iter()
+		.filter(|package| !is_package_used(&registry, &package.path) && !locked_paths.contains(&package.path))
+		.collect::<Vec<_>>();
+
+	if unused_packages.is_empty() {
+		println!("Every cached package is used by an environment.");
+		return Ok(());
+	}
+
+	let unused_size: u64 = unused_packages.iter().map(|package| package.size).sum();
+	println!("Cached packages not used by any environment or lockfile:");
+	for package in &unused_packages {
+		println!("  - {} {} ({})", package.name, package.version, format_size(package.size));
+	}
+
+	let user_said_yes = guard!(
+		utils::confirm_action(&format!("Remove them from the cache, freeing {}? (y/N)", format_size(unused_size))),
+		"todo"
+	);
+	if !user_said_yes {
+		println!("Pruning canceled");
+		return Ok(());
+	}
+
+	for package in unused_packages {
+		guard!(utils::try_deleting_dir(&package.path), "Failed to delete {}", package.path.display());
+	}
+
+	println!("Freed {}", format_size(unused_size));
+	return Ok(());
+}
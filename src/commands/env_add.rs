@@ -1,19 +1,28 @@
-use crate::utils::{download_package, find_matching_package_version, get_package_path, get_project_root, guard, read_config, write_config, AnyError};
+use crate::utils::{
+	download_dep_if_missing, error, find_matching_package_version, get_project_root, print_lockfile_diff, read_config, read_lockfile,
+	resolve_lockfile, write_config, write_lockfile, AnyError, MAIN_GROUP,
+};
 use semver::VersionReq;
-use std::fs;
 
-pub fn env_add(name: &str, version: &VersionReq) -> Result<(), AnyError> {
+pub fn env_add(name: &str, version: &VersionReq, group: Option<&String>) -> Result<(), AnyError> {
 	let projet_path = get_project_root()?;
 	let mut config = read_config(&projet_path)?;
 
 	let package = find_matching_package_version(name, version)?;
-	let package_path = get_package_path(&package);
-	let package_path_exists = guard!(fs::exists(&package_path), "Couldn't see if package is installed");
-	if !package_path_exists {
-		download_package(&package, &config.python);
-	}
+	download_dep_if_missing(&package, &config.python)?;
 
-	config.packages.insert(package.name, toml::Value::String(version.to_string()));
-	write_config(projet_path, config);
+	let packages = match group {
+		Some(group) if group == MAIN_GROUP => return error!("{} is the name of the [packages] table, it can't be used as a group", MAIN_GROUP),
+		Some(group) => config.dependency_groups.entry(group.clone()).or_default(),
+		None => &mut config.packages,
+	};
+	packages.insert(package.name, toml::Value::String(version.to_string()));
+
+	// The lockfile is written after the config, so that a failure never leaves it locking packages the config doesn't have
+	let previous = read_lockfile(&projet_path)?;
+	let lockfile = resolve_lockfile(&config, previous.as_ref())?;
+	print_lockfile_diff(previous.as_ref(), &lockfile);
+	write_config(projet_path.clone(), config)?;
+	write_lockfile(&projet_path, &lockfile)?;
 	return Ok(());
 }
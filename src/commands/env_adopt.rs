@@ -0,0 +1,272 @@
+use crate::constants::{CONFIG_FILE_NAME, ENV_DIR_NAME, LOCKFILE_NAME, PYPROJECT_FILE_NAME};
+use crate::utils::{
+	adopt_virtual_env, error, get_config_path, get_group_packages, get_installed_dependencies, guard, init_pyproject_config, normalize_package_name,
+	read_config, read_global_config, read_installed_distributions, read_pyvenv_python, resolve_lockfile_from, select_groups, write_config,
+	write_lockfile, AnyError, Config, Dependency, InstalledDistribution, LinkMode, LockedPackage, Lockfile, Package, PackageSource, PypiSource,
+	PythonSpec, MAIN_GROUP,
+};
+use semver::Version;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// The tools virtual environments come with, only adopted when a package depends on them.
+static PACKAGING_TOOLS: [&str; 3] = ["pip", "setuptools", "wheel"];
+
+/// Turns the .venv of the current directory, made by `python -m venv` or virtualenv and filled by pip, into a pen project.
+///
+/// # Arguments
+/// - `link_mode`: How packages are put into the .venv, defaults to the `link_mode` of the global config.
+///
+/// # Termination
+/// - This function returns an error if there is no .venv, if it was already made by pen, if the project already has a config,
+///   or if the packages can't be downloaded. The .venv, the config and the lockfile are then left as they were.
+///
+/// # Guarantees
+/// - The installed packages no other installed package requires are written to `pen.toml`, the others are only locked.
+///   Every package is locked at its installed version, with the dependencies of its installed metadata, without asking PyPI.
+/// - The `[project]` of a `pyproject.toml` isn't changed, only the installed packages it needs are adopted.
+/// - pip, setuptools and wheel are only adopted when another package depends on them.
+/// - Distributions pen can't install (non semver versions, editable or URL installs, files modified since they were installed)
+///   and every file no distribution owns are kept in the .venv as they are, and listed.
+pub fn env_adopt(link_mode: Option<LinkMode>) -> Result<(), AnyError> {
+	let project_path = guard!(env::current_dir(), "Failed to get current directory");
+	let env_path = project_path.join(ENV_DIR_NAME);
+	let pyvenv_path = env_path.join("pyvenv.cfg");
+
+	if project_path.join(CONFIG_FILE_NAME).exists() {
+		return error!("This project already has a {}, run `pen sync` instead.", CONFIG_FILE_NAME);
+	}
+	let pyvenv = match fs::read_to_string(&pyvenv_path) {
+		Ok(pyvenv) => pyvenv,
+		Err(_) => return error!("No virtual environment at {}.", env_path.display()),
+	};
+	if pyvenv.starts_with("# Created using pen") {
+		return error!("{} was already made by pen.", env_path.display());
+	}
+
+	let link_mode = match link_mode {
+		Some(link_mode) => link_mode,
+		None => read_global_config()?.link_mode.unwrap_or(LinkMode::Symlink),
+	};
+
+	let python = read_pyvenv_python(&env_path)?;
+	let site_packages_relative_path = PathBuf::from("lib").join(python.lib_dir_name()).join("site-packages");
+	let site_packages_path = env_path.join(&site_packages_relative_path);
+
+	let mut adoptable = Vec::new();
+	let mut kept = Vec::new();
+	for distribution in read_installed_distributions(&site_packages_path)? {
+		let package = match distribution.to_package() {
+			Some(package) => package,
+			None => {
+				kept.push(format!("{} {}: the version isn't semver", distribution.name, distribution.version));
+				continue;
+			}
+		};
+		if let Some(direct_url) = &distribution.direct_url {
+			kept.push(format!("{} {}: installed from {}", distribution.name, distribution.version, direct_url));
+			continue;
+		}
+		let modified = distribution.get_modified_files(&site_packages_path)?;
+		if let Some(path) = modified.first() {
+			kept.push(format!(
+				"{} {}: {} file(s) changed since it was installed, ex. {}",
+				distribution.name,
+				distribution.version,
+				modified.len(),
+				path.display()
+			));
+			continue;
+		}
+		adoptable.push((distribution, package));
+	}
+
+	// Only the distributions nothing else requires go to the config, their dependencies are only locked
+	let source = InstalledSource::new(&adoptable, &python)?;
+	let required: HashSet<&String> = source
+		.packages
+		.values()
+		.flat_map(|(_, dependencies)| dependencies.iter().map(|d| &d.name))
+		.collect();
+	let mut packages = toml::Table::new();
+	let mut locked = Vec::new();
+	for (_, package) in &adoptable {
+		let name = normalize_package_name(&package.name);
+		if !required.contains(&name) && !PACKAGING_TOOLS.contains(&name.as_str()) {
+			packages.insert(package.name.clone(), toml::Value::String(format!("^{}", package.version)));
+		}
+		locked.push(LockedPackage {
+			name: package.name.clone(),
+			version: package.version.clone(),
+			groups: vec![String::from(MAIN_GROUP)],
+			dependencies: Vec::new(),
+		});
+	}
+
+	// Written before the .venv is converted, so that it is newer than them and `pen run` doesn't sync it again
+	let pyproject_path = project_path.join(PYPROJECT_FILE_NAME);
+	let lockfile_path = project_path.join(LOCKFILE_NAME);
+	let previous_files: Vec<(PathBuf, Option<String>)> = [&pyproject_path, &lockfile_path, &project_path.join(CONFIG_FILE_NAME)]
+		.into_iter()
+		.map(|path| (path.clone(), fs::read_to_string(path).ok()))
+		.collect();
+	let lockfile = match write_adopted_config(&project_path, python, packages, locked, &source) {
+		Ok(lockfile) => lockfile,
+		Err(e) => {
+			restore_files(&previous_files);
+			return Err(e);
+		}
+	};
+
+	let config_name = get_config_path(&project_path)
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_default();
+
+	// pen links the files of the locked packages from its cache, so they aren't carried over
+	let mut replaced_paths = HashSet::new();
+	let mut read_records = || -> Result<(), AnyError> {
+		for (distribution, package) in &adoptable {
+			let is_locked = lockfile
+				.packages
+				.iter()
+				.any(|locked| normalize_package_name(&locked.name) == normalize_package_name(&package.name) && locked.version == package.version);
+			if !is_locked {
+				let reason = match PACKAGING_TOOLS.contains(&normalize_package_name(&package.name).as_str()) {
+					true => String::from("a packaging tool no package depends on"),
+					false => format!("not a dependency in {}", config_name),
+				};
+				kept.push(format!("{} {}: {}", distribution.name, distribution.version, reason));
+				continue;
+			}
+			for entry in distribution.read_record()? {
+				replaced_paths.insert(site_packages_relative_path.join(entry.path));
+			}
+			if let Some(dist_info_name) = distribution.dist_info_path.file_name() {
+				replaced_paths.insert(site_packages_relative_path.join(dist_info_name));
+			}
+		}
+		return Ok(());
+	};
+	let adopted = read_records().and_then(|_| read_config(&project_path)).and_then(|config| {
+		let selected_groups = select_groups(&config, Vec::new(), false)?;
+		let packages = get_group_packages(&lockfile, &selected_groups);
+		adopt_virtual_env(config, packages, &env_path, link_mode, &replaced_paths)
+	});
+	if let Err(e) = adopted {
+		restore_files(&previous_files);
+		return Err(e);
+	}
+
+	for package in &lockfile.packages {
+		println!("+ {} {}", package.name, package.version);
+	}
+	if !kept.is_empty() {
+		println!(
+			"\nKept in {} as they were, `pen sync` will remove them unless they are added to {}:",
+			ENV_DIR_NAME, config_name
+		);
+		for kept in &kept {
+			println!("- {}", kept);
+		}
+	}
+	println!(
+		"\nAdopted {} package(s) into {} and {}.",
+		lockfile.packages.len(),
+		config_name,
+		LOCKFILE_NAME
+	);
+	return Ok(());
+}
+
+/// Writes the config and the lockfile of an adopted environment.
+///
+/// # Arguments
+/// - `packages`: The installed packages no other installed package requires.
+/// - `locked`: Every installed package pen can adopt, with its installed version.
+///
+/// # Output
+/// - The lockfile, with the installed versions.
+///
+/// # Guarantees
+/// - In a `pyproject.toml`, `[project]` is left as it was, the installed packages it doesn't list aren't adopted.
+fn write_adopted_config(
+	project_path: &Path,
+	python: PythonSpec,
+	packages: toml::Table,
+	locked: Vec<LockedPackage>,
+	source: &InstalledSource,
+) -> Result<Lockfile, AnyError> {
+	let pyproject_path = project_path.join(PYPROJECT_FILE_NAME);
+	let config = match pyproject_path.exists() {
+		true => {
+			init_pyproject_config(&pyproject_path, &python)?;
+			read_config(project_path)?
+		}
+		false => Config {
+			python,
+			env_files: Vec::new(),
+			packages,
+			dependency_groups: BTreeMap::new(),
+			scripts: BTreeMap::new(),
+		},
+	};
+
+	// Only the dependencies of pyproject.toml that aren't installed need PyPI
+	let lockfile = resolve_lockfile_from(&config, Some(&Lockfile::new(locked)), source)?;
+	write_config(project_path.to_path_buf(), config)?;
+	write_lockfile(project_path, &lockfile)?;
+	return Ok(lockfile);
+}
+
+/// The distributions of the adopted .venv, with the dependencies declared in their installed metadata, and PyPI for the other packages.
+struct InstalledSource {
+	/// The installed packages and their dependencies, keyed by normalized name.
+	packages: BTreeMap<String, (Package, Vec<Dependency>)>,
+	pypi: PypiSource,
+}
+
+impl InstalledSource {
+	fn new(distributions: &[(InstalledDistribution, Package)], python: &PythonSpec) -> Result<InstalledSource, AnyError> {
+		let mut packages = BTreeMap::new();
+		for (distribution, package) in distributions {
+			let dependencies = get_installed_dependencies(package, &distribution.dist_info_path, python)?;
+			packages.insert(normalize_package_name(&package.name), (package.clone(), dependencies));
+		}
+		return Ok(InstalledSource {
+			packages,
+			pypi: PypiSource::default(),
+		});
+	}
+}
+
+impl PackageSource for InstalledSource {
+	fn get_versions(&self, name: &str) -> Result<(String, Vec<Version>), AnyError> {
+		return match self.packages.get(&normalize_package_name(name)) {
+			Some((package, _)) => Ok((package.name.clone(), vec![package.version.clone()])),
+			None => self.pypi.get_versions(name),
+		};
+	}
+
+	fn get_dependencies(&self, package: &Package, python: &PythonSpec) -> Result<Vec<Dependency>, AnyError> {
+		return match self.packages.get(&normalize_package_name(&package.name)) {
+			Some((installed, dependencies)) if installed.version == package.version => Ok(dependencies.clone()),
+			_ => self.pypi.get_dependencies(package, python),
+		};
+	}
+}
+
+/// Puts files back as they were, removing the ones that didn't exist.
+fn restore_files(files: &Vec<(PathBuf, Option<String>)>) {
+	for (path, contents) in files {
+		let result = match contents {
+			Some(contents) => fs::write(path, contents),
+			None if path.exists() => fs::remove_file(path),
+			None => Ok(()),
+		};
+		if let Err(e) = result {
+			eprintln!("Couldn't restore {}: {}", path.display(), e);
+		}
+	}
+}
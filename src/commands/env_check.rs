@@ -0,0 +1,152 @@
+use crate::constants::{ENV_DIR_NAME, LOCKFILE_NAME, PYTHON_PACKAGES_DIR};
+use crate::utils::{
+	error, get_config_path, get_project_root, guard, is_lockfile_up_to_date, normalize_package_name, parse_release_version, read_config,
+	read_installed_distributions, read_lockfile, read_pyvenv_python, read_registry, select_groups, AnyError,
+};
+use std::fs;
+use std::path::PathBuf;
+
+/// Checks that the .venv of the project still matches its config and lockfile, ex. after a `pip install` inside it.
+///
+/// # Arguments
+/// - `groups`: Dependency groups expected in the .venv in addition to `[packages]` and the `dev` group, like `pen sync`.
+/// - `no_dev`: Whether the `dev` group is expected to be left out.
+///
+/// # Output
+/// - Every difference found, by kind: packages that aren't locked, versions that differ from the lockfile, locked packages
+///   that are missing, entry point scripts written by `pen sync` that are missing, broken links into the package cache and a different interpreter.
+///
+/// # Termination
+/// - This function returns an error if there is no .venv, or if any difference was found, so that pen exits with a non-zero code.
+pub fn env_check(groups: Vec<String>, no_dev: bool) -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let config = read_config(&project_path)?;
+	let selected_groups = select_groups(&config, groups, no_dev)?;
+	let env_path = project_path.join(ENV_DIR_NAME);
+	let config_name = get_config_path(&project_path)
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_default();
+
+	if !env_path.join("pyvenv.cfg").exists() {
+		return error!("No virtual environment at {}, run `pen sync` first.", env_path.display());
+	}
+
+	let mut sections: Vec<(&str, Vec<String>)> = Vec::new();
+
+	// Interpreter
+	let mut interpreter = Vec::new();
+	let python = read_pyvenv_python(&env_path)?;
+	if python != config.python {
+		interpreter.push(format!(
+			"{} uses Python {}, {} asks for {}",
+			ENV_DIR_NAME, python, config_name, config.python
+		));
+	}
+	if !env_path.join("bin/python").exists() {
+		interpreter.push(format!("{} doesn't exist anymore", env_path.join("bin/python").display()));
+	}
+	sections.push(("Interpreter", interpreter));
+
+	// Lockfile
+	let lockfile = read_lockfile(&project_path)?;
+	let mut lock = Vec::new();
+	match &lockfile {
+		Some(lockfile) if !is_lockfile_up_to_date(&config, lockfile)? => lock.push(format!("{} is out of date with {}", LOCKFILE_NAME, config_name)),
+		Some(_) => {}
+		None => lock.push(format!("{} has no {}", config_name, LOCKFILE_NAME)),
+	}
+	sections.push(("Lockfile", lock));
+
+	// Packages
+	let site_packages_path = env_path.join("lib").join(python.lib_dir_name()).join("site-packages");
+	let distributions = match site_packages_path.exists() {
+		true => read_installed_distributions(&site_packages_path)?,
+		false => Vec::new(),
+	};
+	let locked_packages = lockfile.as_ref().map(|lockfile| lockfile.packages.as_slice()).unwrap_or_default();
+
+	let mut not_locked = Vec::new();
+	let mut different_versions = Vec::new();
+	for distribution in &distributions {
+		let name = normalize_package_name(&distribution.name);
+		match locked_packages.iter().find(|p| normalize_package_name(&p.name) == name) {
+			None => not_locked.push(format!("{} {}", distribution.name, distribution.version)),
+			Some(locked) if parse_release_version(&distribution.version).as_ref() != Some(&locked.version) => different_versions.push(format!(
+				"{} {} installed, {} locked",
+				distribution.name, distribution.version, locked.version
+			)),
+			Some(_) => {}
+		}
+	}
+	let missing = locked_packages
+		.iter()
+		.filter(|p| p.groups.iter().any(|group| selected_groups.contains(group)))
+		.filter(|p| {
+			!distributions
+				.iter()
+				.any(|d| normalize_package_name(&d.name) == normalize_package_name(&p.name))
+		})
+		.map(|p| format!("{} {} ({})", p.name, p.version, p.groups.join(", ")))
+		.collect();
+	sections.push(("Not in the config or the lockfile", not_locked));
+	sections.push(("Versions differing from the lockfile", different_versions));
+	sections.push(("Locked but not installed", missing));
+
+	// Only the scripts pen wrote are expected, the ones of distributions installed by pip are pip's business
+	let missing_entry_points = match read_registry()?.environments.get(&env_path) {
+		Some(environment) => environment
+			.entry_points
+			.iter()
+			.filter(|script_name| !env_path.join("bin").join(script_name).exists())
+			.cloned()
+			.collect(),
+		None => Vec::new(),
+	};
+	sections.push(("Missing entry points", missing_entry_points));
+
+	// Links
+	let mut broken_links = Vec::new();
+	if site_packages_path.exists() {
+		find_broken_links(&site_packages_path, &env_path, &mut broken_links)?;
+	}
+	sections.push(("Broken links into the package cache", broken_links));
+
+	let problem_count: usize = sections.iter().map(|(_, problems)| problems.len()).sum();
+	if problem_count == 0 {
+		println!("{} matches {} and {}.", ENV_DIR_NAME, config_name, LOCKFILE_NAME);
+		return Ok(());
+	}
+	for (title, problems) in sections.iter().filter(|(_, problems)| !problems.is_empty()) {
+		println!("{}:", title);
+		for problem in problems {
+			println!("- {}", problem);
+		}
+	}
+	return error!(
+		"{} doesn't match {} ({} problem(s)), run `pen sync` to rebuild it.",
+		ENV_DIR_NAME, config_name, problem_count
+	);
+}
+
+/// Lists the links of `dir_path` and its subdirectories pointing into `PYTHON_PACKAGES_DIR` at something that doesn't exist.
+fn find_broken_links(dir_path: &PathBuf, env_path: &PathBuf, broken_links: &mut Vec<String>) -> Result<(), AnyError> {
+	let entries = guard!(fs::read_dir(dir_path), "Failed to read {}", dir_path.display());
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		let file_type = guard!(entry.file_type(), "Couldn't read the type of {}", entry.path().display());
+
+		if file_type.is_symlink() {
+			let target = guard!(fs::read_link(entry.path()), "Couldn't read link {}", entry.path().display());
+			if target.starts_with(&*PYTHON_PACKAGES_DIR) && !entry.path().exists() {
+				let path = entry.path();
+				let relative_path = path.strip_prefix(env_path).unwrap_or(&path);
+				broken_links.push(format!("{} -> {}", relative_path.display(), target.display()));
+			}
+		} else if file_type.is_dir() {
+			// Links are never followed, so the cache itself isn't walked
+			find_broken_links(&entry.path(), env_path, broken_links)?;
+		}
+	}
+	return Ok(());
+}
@@ -0,0 +1,182 @@
+use crate::constants::{LOCKFILE_NAME, PYPROJECT_FILE_NAME};
+use crate::utils::{
+	error, get_config_path, get_package_files, get_project_root, guard, is_lockfile_up_to_date, normalize_package_name, read_config,
+	read_dependency_markers, read_lockfile, select_groups, AnyError, LockedPackage, PackageFile, MAIN_GROUP,
+};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, InlineTable, Table};
+
+/// The index the locked packages come from.
+static PYPI_SIMPLE_URL: &str = "https://pypi.org/simple";
+
+/// Exports the locked packages of some groups as a `requirements.txt` or as a PEP 751 `pylock.toml`.
+///
+/// # Arguments
+/// - `groups`: Dependency groups to export in addition to `[packages]` and the `dev` group.
+/// - `no_dev`: Whether to leave out the `dev` group.
+/// - `pylock`: Whether to write a `pylock.toml` instead of a `requirements.txt`.
+/// - `output`: The file to write, the export is printed when `None`.
+/// - `no_hashes`: Whether to leave out the `--hash` lines of the `requirements.txt`, which avoids requesting PyPI.
+///
+/// # Termination
+/// - This function returns an error if a group doesn't exist, if `no_hashes` is given with `pylock`, if the lockfile is missing or out of date, or if PyPI can't be reached for the hashes.
+///
+/// # Guarantees
+/// - Every package is pinned to its locked version, and the dependencies of the exported packages are exported too,
+///   so pip accepts the file in `--require-hashes` mode.
+/// - The environment markers of a `pyproject.toml` project are kept, except on packages another exported package depends on,
+///   which pip must always find in the file.
+/// - In a `pylock.toml`, the packages only needed by a dependency group are marked with it, ex. `'dev' in dependency_groups`,
+///   and the names are normalized.
+pub fn env_export(groups: Vec<String>, no_dev: bool, pylock: bool, output: Option<&PathBuf>, no_hashes: bool) -> Result<(), AnyError> {
+	if pylock && no_hashes {
+		return error!("A pylock.toml needs the hashes of the files, --no-hashes only works for a requirements.txt.");
+	}
+	let project_path = get_project_root()?;
+	let config = read_config(&project_path)?;
+	let selected_groups = select_groups(&config, groups, no_dev)?;
+
+	// The export must be what `pen sync` installs, so a stale lockfile isn't silently re-resolved
+	let lockfile = match read_lockfile(&project_path)? {
+		Some(lockfile) => lockfile,
+		None => return error!("No {} in the project, run `pen sync` first.", LOCKFILE_NAME),
+	};
+	if !is_lockfile_up_to_date(&config, &lockfile)? {
+		return error!("{} is out of date with the config, run `pen sync` first.", LOCKFILE_NAME);
+	}
+	let packages: Vec<&LockedPackage> = lockfile
+		.packages
+		.iter()
+		.filter(|p| p.groups.iter().any(|group| selected_groups.contains(group)))
+		.collect();
+
+	let config_path = get_config_path(&project_path);
+	let markers = match config_path.file_name().is_some_and(|name| name == PYPROJECT_FILE_NAME) {
+		true => read_dependency_markers(&config_path, &config.python, &selected_groups)?,
+		false => BTreeMap::new(),
+	};
+	let required: Vec<String> = packages.iter().flat_map(|package| package.dependencies.clone()).collect();
+	let get_marker = |package: &LockedPackage| {
+		let name = normalize_package_name(&package.name);
+		match required.contains(&name) {
+			true => None,
+			false => markers.get(&name).cloned(),
+		}
+	};
+
+	let mut files = BTreeMap::new();
+	if pylock || !no_hashes {
+		for package in &packages {
+			let package_files = get_package_files(&package.to_package())?;
+			if package_files.is_empty() {
+				return error!("PyPI has no files for {} {}.", package.name, package.version);
+			}
+			files.insert(package.name.clone(), package_files);
+		}
+	}
+
+	let contents = match pylock {
+		true => {
+			let mut document = DocumentMut::new();
+			document.insert("lock-version", value("1.0"));
+			document.insert("requires-python", value(format!("=={}.*", config.python.short_version())));
+			document.insert("extras", value(Array::new()));
+			let dependency_groups: Array = selected_groups.iter().filter(|group| *group != MAIN_GROUP).collect();
+			document.insert("dependency-groups", value(dependency_groups.clone()));
+			document.insert("default-groups", value(dependency_groups));
+			document.insert("created-by", value("pen"));
+
+			let mut tables = ArrayOfTables::new();
+			for package in &packages {
+				let groups: Vec<String> = package
+					.groups
+					.iter()
+					.filter(|group| selected_groups.contains(group))
+					.map(|group| format!("'{}' in dependency_groups", group))
+					.collect();
+				let group_marker = match package.groups.iter().any(|group| group == MAIN_GROUP) {
+					true => None,
+					false => Some(groups.join(" or ")),
+				};
+				let marker = match (get_marker(package), group_marker) {
+					(Some(marker), Some(group_marker)) => Some(format!("({}) and ({})", marker, group_marker)),
+					(marker, group_marker) => marker.or(group_marker),
+				};
+
+				let mut table = Table::new();
+				table.insert("name", value(normalize_package_name(&package.name)));
+				table.insert("version", value(package.version.to_string()));
+				if let Some(marker) = marker {
+					table.insert("marker", value(marker));
+				}
+				table.insert("index", value(PYPI_SIMPLE_URL));
+
+				let package_files = &files[&package.name];
+				if let Some(sdist) = package_files.iter().find(|file| !file.is_wheel) {
+					table.insert("sdist", value(to_pylock_file(sdist)));
+				}
+				let mut wheels = Array::new();
+				for wheel in package_files.iter().filter(|file| file.is_wheel) {
+					wheels.push(to_pylock_file(wheel));
+				}
+				if !wheels.is_empty() {
+					// One wheel per line, they are long
+					for wheel in wheels.iter_mut() {
+						wheel.decor_mut().set_prefix("\n\t");
+					}
+					wheels.set_trailing("\n");
+					wheels.set_trailing_comma(true);
+					table.insert("wheels", value(wheels));
+				}
+				tables.push(table);
+			}
+			document.insert("packages", toml_edit::Item::ArrayOfTables(tables));
+			document.to_string()
+		}
+		false => {
+			let mut contents = format!(
+				"# Exported by `pen export` from {} for Python {}, groups: {}\n",
+				LOCKFILE_NAME,
+				config.python,
+				selected_groups.join(", ")
+			);
+			for package in &packages {
+				let mut line = format!("{}=={}", package.name, package.version);
+				if let Some(marker) = get_marker(package) {
+					line = format!("{} ; {}", line, marker);
+				}
+				for file in files.get(&package.name).into_iter().flatten() {
+					line = format!("{} \\\n    --hash=sha256:{}", line, file.sha256);
+				}
+				contents.push_str(&line);
+				contents.push('\n');
+			}
+			contents
+		}
+	};
+
+	match output {
+		Some(output) => {
+			guard!(fs::write(output, contents), "Couldn't write to {}.", output.display());
+			println!("Exported {} package(s) to {}", packages.len(), output.display());
+		}
+		None => print!("{}", contents),
+	}
+	return Ok(());
+}
+
+/// Describes a file of a package for the `sdist` or `wheels` of a `pylock.toml` package.
+fn to_pylock_file(file: &PackageFile) -> InlineTable {
+	let mut table = InlineTable::new();
+	table.insert("name", file.filename.as_str().into());
+	table.insert("url", file.url.as_str().into());
+	if let Some(size) = file.size {
+		table.insert("size", (size as i64).into());
+	}
+	let mut hashes = InlineTable::new();
+	hashes.insert("sha256", file.sha256.as_str().into());
+	table.insert("hashes", hashes.into());
+	return table;
+}
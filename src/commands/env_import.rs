@@ -0,0 +1,70 @@
+use crate::utils::{
+	error, get_config_path, get_project_root, import_requirements, normalize_package_name, read_config, write_config, AnyError, MAIN_GROUP,
+};
+use std::path::PathBuf;
+
+/// Adds the dependencies of a `requirements.txt`, a `Pipfile`, a `Pipfile.lock` or a Poetry `pyproject.toml` to the project.
+///
+/// # Arguments
+/// - `path`: The file to import.
+/// - `group`: The dependency group of its main dependencies, `[packages]` when `None`.
+///
+/// # Termination
+/// - This function returns an error if the file can't be read or parsed.
+///
+/// # Guarantees
+/// - Packages already in the config keep their requirement.
+/// - Every entry that couldn't be translated is listed with the reason. The .venv isn't touched, `pen sync` installs the packages.
+pub fn env_import(path: &PathBuf, group: Option<&String>) -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let mut config = read_config(&project_path)?;
+	let config_name = get_config_path(&project_path)
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_default();
+
+	let group = match group {
+		Some(group) if group == MAIN_GROUP => return error!("{} is the name of the [packages] table, it can't be used as a group", MAIN_GROUP),
+		Some(group) => group.as_str(),
+		None => MAIN_GROUP,
+	};
+	let import = import_requirements(path, group, &config.python)?;
+
+	let mut imported = 0;
+	for requirement in &import.requirements {
+		let packages = match requirement.group == MAIN_GROUP {
+			true => &mut config.packages,
+			false => config.dependency_groups.entry(requirement.group.clone()).or_default(),
+		};
+		let normalized_name = normalize_package_name(&requirement.name);
+		if let Some((name, version)) = packages.iter().find(|(name, _)| normalize_package_name(name) == normalized_name) {
+			println!("= {} {} (already in {}, kept)", name, version.as_str().unwrap_or_default(), config_name);
+			continue;
+		}
+
+		packages.insert(requirement.name.clone(), toml::Value::String(requirement.version.clone()));
+		match requirement.group == MAIN_GROUP {
+			true => println!("+ {} {}", requirement.name, requirement.version),
+			false => println!("+ {} {} [{}]", requirement.name, requirement.version, requirement.group),
+		}
+		imported += 1;
+	}
+	if imported > 0 {
+		write_config(project_path, config)?;
+	}
+
+	if !import.notes.is_empty() {
+		println!("\nNotes:");
+		for note in &import.notes {
+			println!("- {}", note);
+		}
+	}
+	if !import.skipped.is_empty() {
+		println!("\nNot imported:");
+		for skipped in &import.skipped {
+			println!("- {}", skipped);
+		}
+	}
+	println!("\nImported {} package(s) into {}. Run `pen sync` to install them.", imported, config_name);
+	return Ok(());
+}
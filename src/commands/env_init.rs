@@ -1,23 +1,34 @@
-use crate::utils::{error, guard, write_config, AnyError, Config};
-use semver::Version;
-use std::{env, fs};
+use crate::constants::{CONFIG_FILE_NAME, PYPROJECT_FILE_NAME};
+use crate::utils::{error, guard, init_pyproject_config, write_config, AnyError, Config, PythonSpec};
+use std::{collections::BTreeMap, env, fs};
 
-pub fn env_init(version: Version) -> Result<(), AnyError> {
+pub fn env_init(python: PythonSpec) -> Result<(), AnyError> {
 	let config = Config {
-		python: version,
+		python,
+		env_files: Vec::new(),
 		packages: toml::Table::new(),
+		dependency_groups: BTreeMap::new(),
+		scripts: BTreeMap::new(),
 	};
 
 	let project_path = guard!(env::current_dir(), "Failed to get current directory");
 
 	let project_path_exists = guard!(
-		fs::exists(project_path.join("pen.toml")),
+		fs::exists(project_path.join(CONFIG_FILE_NAME)),
 		"Couldn't see if an existing pen.toml file exists. Do you have permission?"
 	);
 
-	if !project_path_exists {
+	if project_path_exists {
 		return error!("This would override an existing pen.toml config file.");
 	}
-	write_config(project_path, config);
+
+	// A project with a pyproject.toml keeps its dependencies there, only the Python is added, to [tool.pen]
+	let pyproject_path = project_path.join(PYPROJECT_FILE_NAME);
+	if pyproject_path.exists() {
+		init_pyproject_config(&pyproject_path, &config.python)?;
+		println!("Added [tool.pen] to {}", pyproject_path.display());
+		return Ok(());
+	}
+	write_config(project_path, config)?;
 	return Ok(());
 }
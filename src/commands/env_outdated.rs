@@ -0,0 +1,91 @@
+use crate::constants::ENV_DIR_NAME;
+use crate::utils::{
+	get_project_root, normalize_package_name, read_config, read_installed_distributions, read_lockfile, resolve_lockfile_from, AnyError,
+	PackageSource, PypiSource,
+};
+use semver::Version;
+use std::collections::BTreeMap;
+
+/// Lists the packages of the project, and their dependencies, that have newer releases on PyPI.
+///
+/// # Output
+/// - For each outdated package, its current version, the newest version the requirements allow (what `pen upgrade` would pick)
+///   and the newest version on PyPI. The current version is the locked one, or the one in the .venv for a package that isn't locked.
+///
+/// # Termination
+/// - This function returns an error if PyPI can't be reached or if the requirements can't be resolved.
+pub fn env_outdated() -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let config = read_config(&project_path)?;
+	let lockfile = read_lockfile(&project_path)?;
+
+	let site_packages_path = project_path
+		.join(ENV_DIR_NAME)
+		.join("lib")
+		.join(config.python.lib_dir_name())
+		.join("site-packages");
+	let mut current: BTreeMap<String, Version> = BTreeMap::new();
+	if site_packages_path.exists() {
+		for distribution in read_installed_distributions(&site_packages_path)? {
+			if let Some(package) = distribution.to_package() {
+				current.insert(normalize_package_name(&package.name), package.version);
+			}
+		}
+	}
+	for package in lockfile.iter().flat_map(|lockfile| lockfile.packages.iter()) {
+		current.insert(normalize_package_name(&package.name), package.version.clone());
+	}
+
+	// What `pen upgrade` would lock, the versions requested for it are reused for the newest ones
+	let source = PypiSource::default();
+	let upgraded = resolve_lockfile_from(&config, None, &source)?;
+
+	let mut rows = Vec::new();
+	for package in &upgraded.packages {
+		let version = match current.get(&normalize_package_name(&package.name)) {
+			Some(version) => version,
+			None => continue, // Neither locked nor installed yet, `pen sync` installs the newest version allowed
+		};
+		let (_, versions) = source.get_versions(&package.name)?;
+		let latest = versions.iter().max().unwrap_or(version).clone();
+		if latest <= *version {
+			continue;
+		}
+
+		rows.push([
+			package.name.clone(),
+			version.to_string(),
+			package.version.to_string(),
+			latest.to_string(),
+			package.groups.join(", "),
+		]);
+	}
+
+	if rows.is_empty() {
+		println!("All packages are up to date.");
+		return Ok(());
+	}
+
+	let header = [
+		String::from("Package"),
+		String::from("Current"),
+		String::from("Wanted"),
+		String::from("Latest"),
+		String::from("Groups"),
+	];
+	let mut widths = [0; 5];
+	for row in rows.iter().chain([&header]) {
+		for (i, cell) in row.iter().enumerate() {
+			widths[i] = widths[i].max(cell.len());
+		}
+	}
+	for row in [&header].into_iter().chain(rows.iter()) {
+		let cells: Vec<String> = row
+			.iter()
+			.enumerate()
+			.map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+			.collect();
+		println!("{}", cells.join("  ").trim_end());
+	}
+	return Ok(());
+}
@@ -0,0 +1,114 @@
+use crate::constants::ENV_DIR_NAME;
+use crate::utils::{
+	self, error, get_project_root, normalize_package_name, read_config, read_lockfile, read_registry, register_environment, resolve_lockfile,
+	unlink_entry_points, unlink_package, write_config, write_lockfile, AnyError,
+};
+
+/// Removes packages from the project and from its .venv.
+///
+/// # Arguments
+/// - `names`: The packages to remove, from `[packages]` and from every dependency group listing them.
+///
+/// # Termination
+/// - This function returns an error if a package isn't a dependency of the project, or if the lockfile can't be resolved.
+///
+/// # Guarantees
+/// - The packages the lockfile no longer needs, including dependencies no other package needs, are unlinked from the .venv,
+///   with the entry point scripts `pen sync` wrote for them.
+///   The package cache is left untouched, `pen cache prune` removes what no environment uses anymore.
+pub fn env_remove(names: Vec<&String>) -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let mut config = read_config(&project_path)?;
+	// Groups that were already empty are the user's to remove, only the ones this removal empties are dropped
+	let empty_groups: Vec<String> = config
+		.dependency_groups
+		.iter()
+		.filter(|(_, packages)| packages.is_empty())
+		.map(|(group, _)| group.clone())
+		.collect();
+
+	for name in names {
+		let normalized_name = normalize_package_name(name);
+		let tables = [&mut config.packages].into_iter().chain(config.dependency_groups.values_mut());
+
+		let mut found = false;
+		for packages in tables {
+			let before = packages.len();
+			packages.retain(|package_name, _| normalize_package_name(package_name) != normalized_name);
+			found |= packages.len() != before;
+		}
+		if !found {
+			return error!("{} is not a dependency of the project.", name);
+		}
+	}
+	config
+		.dependency_groups
+		.retain(|group, packages| !packages.is_empty() || empty_groups.contains(group));
+
+	let previous = read_lockfile(&project_path)?;
+	let lockfile = resolve_lockfile(&config, previous.as_ref())?;
+	let removed_packages: Vec<_> = match &previous {
+		Some(previous) => previous
+			.packages
+			.iter()
+			.filter(|p| !lockfile.packages.iter().any(|l| l.name == p.name && l.version == p.version))
+			.map(|p| p.to_package())
+			.collect(),
+		None => Vec::new(),
+	};
+
+	let env_path = project_path.join(ENV_DIR_NAME);
+	let site_packages_path = env_path.join("lib").join(config.python.lib_dir_name()).join("site-packages");
+
+	write_config(project_path.clone(), config)?;
+	write_lockfile(&project_path, &lockfile)?;
+
+	if !site_packages_path.exists() {
+		return Ok(());
+	}
+
+	let mut registry = read_registry()?;
+	let removed_paths: Vec<_> = removed_packages.iter().map(utils::get_package_path).collect();
+	// The packages staying in the .venv, whose files are kept even if a removed package lists them too
+	let remaining_paths: Vec<_> = match registry.environments.get(&env_path) {
+		Some(environment) => environment
+			.packages
+			.iter()
+			.filter(|path| !removed_paths.contains(path))
+			.cloned()
+			.collect(),
+		None => lockfile.packages.iter().map(|p| utils::get_package_path(&p.to_package())).collect(),
+	};
+	let written_scripts = match registry.environments.get(&env_path) {
+		Some(environment) => environment.entry_points.clone(),
+		None => Vec::new(), // Without the registry, pen can't tell its scripts from the ones of other tools
+	};
+	let mut removed_scripts = Vec::new();
+	for package in &removed_packages {
+		// A package only in the lockfile of a group that was never synced isn't in the .venv
+		let is_linked = match registry.environments.get(&env_path) {
+			Some(environment) => environment.packages.contains(&utils::get_package_path(package)),
+			None => true,
+		};
+		if !is_linked {
+			continue;
+		}
+		if !utils::get_package_path(package).exists() {
+			println!(
+				"{} {} is no longer in the cache, run `pen sync` to remove it from the .venv.",
+				package.name, package.version
+			);
+			continue;
+		}
+		unlink_package(package, &site_packages_path, &remaining_paths)?;
+		removed_scripts.extend(unlink_entry_points(package, &env_path.join("bin"), &written_scripts)?);
+		println!("Removed {} {}", package.name, package.version);
+	}
+
+	if let Some(mut environment) = registry.environments.remove(&env_path) {
+		environment.packages.retain(|path| !removed_paths.contains(path));
+		environment.entry_points.retain(|script_name| !removed_scripts.contains(script_name));
+		register_environment(&env_path, environment)?;
+	}
+	return Ok(());
+}
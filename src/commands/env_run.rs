@@ -0,0 +1,102 @@
+use crate::commands::env_sync;
+use crate::constants::{CONFIG_FILE_NAME, ENV_DIR_NAME};
+use crate::utils::{
+	error, get_activated_path, get_project_root, is_virtual_env_up_to_date, read_config, read_env_files, read_registry, run_script, AnyError,
+	FileLock,
+};
+use std::{
+	os::unix::process::{CommandExt, ExitStatusExt},
+	path::PathBuf,
+	process,
+};
+
+/// Runs a command or a script of the `[scripts]` table inside the virtual environment of the project, without activating it.
+///
+/// # Arguments
+/// - `command`: The command or script name, and its arguments. Scripts take precedence over commands of the same name.
+/// - `env_files`: Dotenv files given on the command line, loaded after those of `pen.toml`.
+/// - `sync`: Whether to sync the environment first when it is out of date, with the dependency groups of its last sync.
+///   When `false`, an out of date environment is an error.
+/// - `cache_lock`: The lock of the cache taken by pen, released once the environment is synced and before the command starts.
+///
+/// # Output
+/// - None, a command replaces the pen process and pen exits with the exit status of a script.
+///
+/// # Termination
+/// - This function returns an error if the environment can't be synced or if the command can't be started.
+///
+/// # Guarantees
+/// - The command gets the exit code and signals it would get if run directly, since pen is no longer running.
+pub fn env_run(command: Vec<&String>, env_files: Vec<PathBuf>, sync: bool, cache_lock: FileLock) -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let env_path = project_path.join(ENV_DIR_NAME);
+
+	if !is_virtual_env_up_to_date(&project_path)? {
+		if !sync {
+			return error!(
+				"The environment at {} is not in sync with {}, run `pen sync` first",
+				env_path.display(),
+				CONFIG_FILE_NAME
+			);
+		}
+		// The groups of the last sync, minus the ones removed from the config since
+		let config = read_config(&project_path)?;
+		let synced = read_registry()?
+			.environments
+			.remove(&env_path)
+			.map(|environment| environment.groups)
+			.unwrap_or_default();
+		let groups = synced
+			.groups
+			.into_iter()
+			.filter(|group| config.dependency_groups.contains_key(group))
+			.collect();
+		env_sync(None, groups, synced.no_dev)?;
+	}
+
+	let (program, args) = command.split_first().expect("required argument");
+
+	let config = read_config(&project_path)?;
+	let env_vars = read_env_files(&project_path, &config.env_files, &env_files)?;
+
+	// pen waits on scripts, which can run for hours, and they may call pen themselves
+	drop(cache_lock);
+
+	if config.scripts.contains_key(program.as_str()) {
+		let status = run_script(&config.scripts, program, args, &project_path, &env_path, &env_vars)?;
+		// Killed steps are reported the way shells do, 128 + the signal number
+		let code = match (status.code(), status.signal()) {
+			(Some(code), _) => code,
+			(None, Some(signal)) => 128 + signal,
+			(None, None) => 1,
+		};
+		process::exit(code);
+	}
+
+	let e = process::Command::new(program)
+		.args(args)
+		.env("PATH", get_activated_path(&env_path, &env_vars))
+		.envs(env_vars.into_iter().filter(|(key, _)| key != "PATH"))
+		.env("VIRTUAL_ENV", &env_path)
+		.env_remove("PYTHONHOME")
+		.exec();
+	return error!("Failed to run {}: {}", program, e);
+}
+
+/// Prints the scripts of the `[scripts]` table of the project.
+pub fn env_run_list() -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let config = read_config(&project_path)?;
+
+	if config.scripts.is_empty() {
+		println!("No scripts in {}, add them to its [scripts] table", CONFIG_FILE_NAME);
+		return Ok(());
+	}
+
+	let width = config.scripts.keys().map(|name| name.len()).max().unwrap_or(0);
+	println!("Scripts of {}:", CONFIG_FILE_NAME);
+	for (name, script) in &config.scripts {
+		println!("- {:width$}  {}", name, script.description(), width = width);
+	}
+	return Ok(());
+}
@@ -0,0 +1,148 @@
+use crate::constants::LOCKFILE_NAME;
+use crate::utils::{
+	error, get_dependency_graph, get_project_root, normalize_package_name, read_config, read_lockfile, AnyError, DependencyGraph, MAIN_GROUP,
+};
+use std::collections::HashSet;
+
+/// Prints the dependency tree of the project.
+///
+/// # Arguments
+/// - `package`: The package to start from, every package of `pen.toml` when `None`.
+/// - `depth`: How many levels of dependencies to show, all of them when `None`.
+/// - `invert`: Whether to show the packages depending on each package instead of its dependencies.
+///   Without `package`, the inverted tree starts from the packages without dependencies.
+///
+/// # Termination
+/// - This function returns an error if the project has no lockfile, if it is out of date, or if `package` isn't in the dependency graph.
+///
+/// # Guarantees
+/// - A package is only expanded once, its next occurrences are marked with `(*)`, which also stops dependency cycles.
+pub fn env_tree(package: Option<&String>, depth: Option<usize>, invert: bool) -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let config = read_config(&project_path)?;
+	let lockfile = match read_lockfile(&project_path)? {
+		Some(lockfile) => lockfile,
+		None => return error!("No {} in the project, run `pen sync` first.", LOCKFILE_NAME),
+	};
+	let graph = get_dependency_graph(&config, &lockfile, &project_path)?;
+
+	let roots: Vec<(String, Vec<String>)> = match package {
+		Some(package) => {
+			let name = normalize_package_name(package);
+			if !graph.nodes.contains_key(&name) {
+				return error!("{} is not in the dependency graph of the project.", package);
+			}
+			vec![(name, Vec::new())]
+		}
+		None if invert => graph
+			.nodes
+			.iter()
+			.filter(|(_, node)| node.dependencies.is_empty())
+			.map(|(name, _)| (name.clone(), Vec::new()))
+			.collect(),
+		None => graph.roots.clone(),
+	};
+
+	let mut tree = Tree {
+		graph: &graph,
+		depth,
+		invert,
+		expanded: HashSet::new(),
+		has_duplicates: false,
+	};
+	for (name, groups) in roots {
+		let groups: Vec<String> = groups.into_iter().filter(|group| group != MAIN_GROUP).collect();
+		let mut label = tree.get_label(&name);
+		if !groups.is_empty() {
+			label = format!("{} [{}]", label, groups.join(", "));
+		}
+		if tree.expanded.contains(&name) && !tree.get_children(&name).is_empty() {
+			label = format!("{} (*)", label);
+			tree.has_duplicates = true;
+		}
+		println!("{}", label);
+		tree.print_children(&name, "", 1);
+	}
+
+	if tree.has_duplicates {
+		println!("\n(*) shown above");
+	}
+	return Ok(());
+}
+
+struct Tree<'a> {
+	graph: &'a DependencyGraph,
+	depth: Option<usize>,
+	invert: bool,
+	expanded: HashSet<String>,
+	has_duplicates: bool,
+}
+
+impl Tree<'_> {
+	fn get_label(&self, name: &str) -> String {
+		let node = &self.graph.nodes[name];
+		return match node.is_installed {
+			true => format!("{} {}", node.name, node.version),
+			false => format!("{} {} (not installed)", node.name, node.version),
+		};
+	}
+
+	/// Gets the packages below `name` in the tree, with the version specifier linking them.
+	fn get_children(&self, name: &str) -> Vec<(String, String)> {
+		if !self.invert {
+			return self.graph.nodes[name]
+				.dependencies
+				.iter()
+				.map(|dependency| (dependency.name.clone(), dependency.specifier.clone()))
+				.collect();
+		}
+		return self
+			.graph
+			.get_dependents(name)
+			.into_iter()
+			.map(|dependent| {
+				let specifier = self.graph.nodes[dependent]
+					.dependencies
+					.iter()
+					.find(|dependency| dependency.name == name)
+					.map(|dependency| dependency.specifier.clone())
+					.unwrap_or_default();
+				(dependent.clone(), specifier)
+			})
+			.collect();
+	}
+
+	fn print_children(&mut self, name: &str, prefix: &str, level: usize) {
+		let children = self.get_children(name);
+		if children.is_empty() || self.depth.is_some_and(|depth| level > depth) {
+			return;
+		}
+		if !self.expanded.insert(String::from(name)) {
+			return;
+		}
+
+		for (i, (child, specifier)) in children.iter().enumerate() {
+			let is_last = i == children.len() - 1;
+			let (branch, next_prefix) = match is_last {
+				true => ("└── ", format!("{}    ", prefix)),
+				false => ("├── ", format!("{}│   ", prefix)),
+			};
+
+			let mut label = self.get_label(child);
+			if !specifier.is_empty() {
+				let relation = if self.invert { "requires" } else { "required" };
+				label = format!("{} ({}: {})", label, relation, specifier);
+			}
+			let is_duplicate = self.expanded.contains(child) && !self.get_children(child).is_empty();
+			if is_duplicate {
+				label = format!("{} (*)", label);
+				self.has_duplicates = true;
+			}
+			println!("{}{}{}", prefix, branch, label);
+
+			if !is_duplicate {
+				self.print_children(child, &next_prefix, level + 1);
+			}
+		}
+	}
+}
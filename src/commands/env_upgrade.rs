@@ -0,0 +1,80 @@
+use crate::utils::{
+	error, get_project_root, get_requirements, normalize_package_name, print_lockfile_diff, read_config, read_lockfile, resolve_lockfile_from,
+	write_config, write_lockfile, AnyError, Lockfile, PackageSource, PypiSource,
+};
+use semver::VersionReq;
+
+/// Upgrades locked packages to newer versions.
+///
+/// # Arguments
+/// - `names`: The packages to upgrade, every package of the project when empty.
+/// - `latest`: Whether to rewrite the requirements of `pen.toml` that exclude the newest version, to `^<newest version>`.
+///
+/// # Termination
+/// - This function returns an error if a package isn't a dependency of the project, or if PyPI can't be reached.
+///
+/// # Guarantees
+/// - Packages not being upgraded keep their locked version.
+/// - The .venv isn't touched, `pen sync` installs the new versions.
+pub fn env_upgrade(names: Vec<&String>, latest: bool) -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let mut config = read_config(&project_path)?;
+	let previous = read_lockfile(&project_path)?;
+	let requirements = get_requirements(&config)?;
+
+	let targets: Vec<String> = names.iter().map(|name| normalize_package_name(name)).collect();
+	for (name, target) in names.iter().zip(&targets) {
+		if !requirements.iter().any(|r| normalize_package_name(&r.name) == *target) {
+			return error!("{} is not a dependency of the project.", name);
+		}
+	}
+	let is_target = |name: &str| targets.is_empty() || targets.contains(&normalize_package_name(name));
+
+	// The versions requested for `latest` are reused by the resolver
+	let source = PypiSource::default();
+	if latest {
+		for requirement in requirements.iter().filter(|r| is_target(&r.name)) {
+			let (_, versions) = source.get_versions(&requirement.name)?;
+			let newest = match versions.into_iter().max() {
+				Some(newest) => newest,
+				None => continue,
+			};
+
+			let tables = [&mut config.packages].into_iter().chain(config.dependency_groups.values_mut());
+			for packages in tables {
+				for (name, version) in packages.iter_mut() {
+					if normalize_package_name(name) != normalize_package_name(&requirement.name) {
+						continue;
+					}
+					let allows_newest = version
+						.as_str()
+						.and_then(|v| VersionReq::parse(v).ok())
+						.is_some_and(|v| v.matches(&newest));
+					if !allows_newest {
+						println!("{} requirement {} -> ^{}", name, version, newest);
+						*version = toml::Value::String(format!("^{}", newest));
+					}
+				}
+			}
+		}
+	}
+
+	// The packages being upgraded are left out of the previous lockfile, so that they resolve to the newest version allowed
+	let kept = previous.as_ref().map(|previous| Lockfile {
+		version: previous.version,
+		packages: previous.packages.iter().filter(|p| !is_target(&p.name)).cloned().collect(),
+	});
+	let lockfile = resolve_lockfile_from(&config, kept.as_ref(), &source)?;
+
+	let changed = print_lockfile_diff(previous.as_ref(), &lockfile);
+	if latest {
+		write_config(project_path.clone(), config)?;
+	}
+	write_lockfile(&project_path, &lockfile)?;
+
+	match changed {
+		true => println!("Run `pen sync` to install the new versions."),
+		false => println!("Every package is already at the newest version allowed."),
+	}
+	return Ok(());
+}
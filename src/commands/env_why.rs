@@ -0,0 +1,55 @@
+use crate::constants::{CONFIG_FILE_NAME, LOCKFILE_NAME};
+use crate::utils::{error, get_dependency_graph, get_project_root, normalize_package_name, read_config, read_lockfile, AnyError, DependencyGraph};
+
+/// Prints every path from a package of `pen.toml` to `package`, explaining why it is in the environment.
+///
+/// # Termination
+/// - This function returns an error if the project has no lockfile, if it is out of date, or if `package` isn't in the dependency graph.
+pub fn env_why(package: &String) -> Result<(), AnyError> {
+	let project_path = get_project_root()?;
+	let config = read_config(&project_path)?;
+	let lockfile = match read_lockfile(&project_path)? {
+		Some(lockfile) => lockfile,
+		None => return error!("No {} in the project, run `pen sync` first.", LOCKFILE_NAME),
+	};
+	let graph = get_dependency_graph(&config, &lockfile, &project_path)?;
+
+	let target = normalize_package_name(package);
+	if !graph.nodes.contains_key(&target) {
+		return error!("{} is not in the dependency graph of the project.", package);
+	}
+
+	let mut paths = Vec::new();
+	for (root, groups) in &graph.roots {
+		if *root == target {
+			println!("{} is listed in {} ({})", graph.nodes[root].name, CONFIG_FILE_NAME, groups.join(", "));
+			continue;
+		}
+		find_paths(&graph, root, &target, &mut vec![root.clone()], &mut paths);
+	}
+
+	for path in &paths {
+		let labels: Vec<String> = path
+			.iter()
+			.map(|name| format!("{} {}", graph.nodes[name].name, graph.nodes[name].version))
+			.collect();
+		println!("{}", labels.join(" -> "));
+	}
+	return Ok(());
+}
+
+/// Depth first search of the paths from the last package of `path` to `target`, skipping the packages already on the path.
+fn find_paths(graph: &DependencyGraph, name: &str, target: &str, path: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+	for dependency in &graph.nodes[name].dependencies {
+		if path.contains(&dependency.name) {
+			continue; // Dependency cycle
+		}
+		path.push(dependency.name.clone());
+		if dependency.name == target {
+			paths.push(path.clone());
+		} else {
+			find_paths(graph, &dependency.name, target, path, paths);
+		}
+		path.pop();
+	}
+}
@@ -1,19 +1,49 @@
+mod cache_clean;
+mod cache_info;
+mod cache_prune;
 mod env_add;
+mod env_adopt;
+mod env_check;
+mod env_export;
+mod env_import;
 mod env_init;
+mod env_run;
 mod env_sync;
+mod env_tree;
+mod env_upgrade;
+mod env_why;
 mod pen_activate;
+mod pen_shell_hook;
 mod pen_uninstall;
 // mod pen_update;
+mod env_outdated;
 mod env_pkgs;
+mod env_remove;
 mod py_delete_version;
 mod py_list_versions;
+mod py_prune_versions;
 
+pub use cache_clean::cache_clean;
+pub use cache_info::cache_info;
+pub use cache_prune::cache_prune;
 pub use env_add::env_add;
+pub use env_adopt::env_adopt;
+pub use env_check::env_check;
+pub use env_export::env_export;
+pub use env_import::env_import;
 pub use env_init::env_init;
+pub use env_run::{env_run, env_run_list};
 pub use env_sync::env_sync;
+pub use env_tree::env_tree;
+pub use env_upgrade::env_upgrade;
+pub use env_why::env_why;
 pub use pen_activate::pen_activate;
+pub use pen_shell_hook::{pen_shell_hook, pen_shell_hook_print_root};
 pub use pen_uninstall::pen_uninstall;
 // pub use pen_update::pen_update;
+pub use env_outdated::env_outdated;
 pub use env_pkgs::env_pkgs;
+pub use env_remove::env_remove;
 pub use py_delete_version::py_delete_version;
 pub use py_list_versions::py_list_versions;
+pub use py_prune_versions::py_prune_versions;
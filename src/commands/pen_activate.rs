@@ -1,43 +1,88 @@
 use crate::constants::ENV_DIR_NAME;
-use crate::utils::{get_project_root, guard, read_config, AnyError};
-use std::process;
+use crate::utils::{error, get_activated_path, get_project_root, guard, read_config, read_env_files, AnyError};
+use std::{env, os::unix::process::CommandExt, path::PathBuf, process};
 
-pub fn pen_activate() -> Result<(), AnyError> {
-	let project_path = guard!(get_project_root(), "todo");
-	let config = guard!(read_config(&project_path), "todo");
-
-	let command = format!(
-		r#"
-            VIRTUAL_ENV="{0}"
-            if [ ! -L "$VIRTUAL_ENV/bin/python3" ] || [ ! -f "$(readlink -f "$VIRTUAL_ENV/bin/python3")" ]; then
-                echo "python3 not found in $VIRTUAL_ENV/bin"
-                exit 1
-            fi
+/// Starts the user's shell with the virtual environment of the project activated.
+///
+/// # Arguments
+/// - `env_files`: Dotenv files given on the command line, loaded after those of `pen.toml`.
+///
+/// # Output
+/// - None, the shell replaces the pen process.
+///
+/// # Termination
+/// - This function returns an error if the environment was not synced yet or if the shell can't be started.
+///
+/// # Guarantees
+/// - The shell loads the user's own startup files before the activate script, and `deactivate` leaves it.
+///   `exit` keeps its usual meaning.
+/// - Shells without a known way of running the activate script (csh, nushell, ...) still get `VIRTUAL_ENV` and
+///   `PATH`, only their prompt is left unchanged.
+pub fn pen_activate(env_files: Vec<PathBuf>) -> Result<(), AnyError> {
+	let project_path = guard!(
+		get_project_root(),
+		"Couldn't find a pen.toml or a pyproject.toml in this directory or its parents"
+	);
+	let env_path = project_path.join(ENV_DIR_NAME);
 
-            export PATH="$VIRTUAL_ENV/bin:$PATH"
-            export PYTHON_VERSION="{1}"
+	if !env_path.join("bin/activate").is_file() {
+		return error!("{} has no activate script, run `pen sync` first", env_path.display());
+	}
+	if env::var_os("VIRTUAL_ENV").is_some_and(|active| active == env_path) {
+		println!("The environment is already active");
+		return Ok(());
+	}
 
-            PROMPT_COMMAND='PS1="($PYTHON_VERSION) [\W]\$ "'
-            export PROMPT_COMMAND
+	let config = read_config(&project_path)?;
+	let env_vars = read_env_files(&project_path, &config.env_files, &env_files)?;
 
-            exit() {{
-                command exit &> /dev/null
-            }}
-            deactivate() {{
-                command exit &> /dev/null
-            }}
-            export -f exit
-            export -f deactivate
+	let shell = env::var("SHELL").unwrap_or_else(|_| String::from("/bin/sh"));
+	let shell_name = match shell.rsplit_once('/') {
+		Some((_, name)) => name,
+		None => shell.as_str(),
+	};
 
-            $SHELL
-        "#,
-		project_path.join(ENV_DIR_NAME).to_string_lossy(), // todo .display() instead?
-		config.python
-	);
+	let mut command = process::Command::new(&shell);
+	command.envs(env_vars.clone());
+	match shell_name {
+		"bash" => {
+			command.arg("--rcfile").arg(env_path.join("pen/bashrc")).arg("-i");
+		}
+		"zsh" => {
+			// zsh has no option for another startup file, its startup folder is swapped and restored in the new .zshrc
+			if let Some(zdotdir) = env::var_os("ZDOTDIR") {
+				command.env("PEN_USER_ZDOTDIR", zdotdir);
+			}
+			command.env("ZDOTDIR", env_path.join("pen/zsh")).arg("-i");
+		}
+		"sh" | "dash" | "ksh" | "mksh" | "ash" => {
+			// POSIX shells run the file in $ENV when interactive
+			if let Some(user_env) = env::var_os("ENV") {
+				command.env("PEN_USER_ENV", user_env);
+			}
+			command.env("ENV", env_path.join("pen/shrc")).arg("-i");
+		}
+		"fish" => {
+			let init = format!(
+				"source '{}'; function deactivate; exit; end",
+				env_path.join("bin/activate.fish").to_string_lossy()
+			);
+			command.arg("-C").arg(init);
+		}
+		_ => {
+			command
+				.env("VIRTUAL_ENV", &env_path)
+				.env(
+					"VIRTUAL_ENV_PROMPT",
+					format!("({}) ", project_path.file_name().unwrap_or_default().to_string_lossy()),
+				)
+				.env("PATH", get_activated_path(&env_path, &env_vars))
+				.env_remove("PYTHONHOME");
+			println!("The prompt of {} can't be changed by pen, leave the environment with `exit`", shell_name);
+		}
+	}
 
-	// todo make it work with plain sh
-	let activate_process = process::Command::new("bash").arg("-c").arg(command).spawn();
-	let mut child = guard!(activate_process, "Failed to start shell");
-	child.wait().expect("Child process wasn't running.");
-	return Ok(());
+	// Replacing the pen process means no lock is held while the shell is open
+	let e = command.exec();
+	return error!("Failed to start {}: {}", shell, e);
 }
@@ -0,0 +1,99 @@
+use crate::constants::ENV_DIR_NAME;
+use crate::utils::{error, get_project_root, guard, AnyError};
+use std::env;
+
+// The hooks only call pen when the directory changed, so that the prompt stays instant.
+// __PEN__ and __ENV_DIR_NAME__ are replaced when printing them.
+
+static HOOK_BASH: &str = r#"_pen_hook() {
+    [ "$PWD" = "${_PEN_HOOK_PWD:-}" ] && return
+    _PEN_HOOK_PWD="$PWD"
+    local root env=""
+    root="$('__PEN__' shell-hook --print-root 2> /dev/null)"
+    [ -n "$root" ] && [ -f "$root/__ENV_DIR_NAME__/bin/activate" ] && env="$root/__ENV_DIR_NAME__"
+    [ "$env" = "${_PEN_HOOK_ENV:-}" ] && return
+    if [ -n "${_PEN_HOOK_ENV:-}" ] && [ "${VIRTUAL_ENV:-}" = "$_PEN_HOOK_ENV" ]; then
+        deactivate
+    fi
+    _PEN_HOOK_ENV="$env"
+    [ -n "$env" ] && . "$env/bin/activate"
+    return 0
+}
+case ";${PROMPT_COMMAND:-};" in
+    *";_pen_hook;"*) ;;
+    *) PROMPT_COMMAND="_pen_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+"#;
+
+static HOOK_ZSH: &str = r#"_pen_hook() {
+    local root env=""
+    root="$('__PEN__' shell-hook --print-root 2> /dev/null)"
+    [ -n "$root" ] && [ -f "$root/__ENV_DIR_NAME__/bin/activate" ] && env="$root/__ENV_DIR_NAME__"
+    [ "$env" = "${_PEN_HOOK_ENV:-}" ] && return
+    if [ -n "${_PEN_HOOK_ENV:-}" ] && [ "${VIRTUAL_ENV:-}" = "$_PEN_HOOK_ENV" ]; then
+        deactivate
+    fi
+    _PEN_HOOK_ENV="$env"
+    [ -n "$env" ] && . "$env/bin/activate"
+    return 0
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _pen_hook
+_pen_hook
+"#;
+
+static HOOK_FISH: &str = r#"function _pen_hook --on-variable PWD
+    set -l root ('__PEN__' shell-hook --print-root 2> /dev/null)
+    set -l env ""
+    if test -n "$root"; and test -f "$root/__ENV_DIR_NAME__/bin/activate.fish"
+        set env "$root/__ENV_DIR_NAME__"
+    end
+    test "$env" = "$_PEN_HOOK_ENV"; and return
+    if test -n "$_PEN_HOOK_ENV"; and test "$VIRTUAL_ENV" = "$_PEN_HOOK_ENV"
+        deactivate
+    end
+    set -g _PEN_HOOK_ENV $env
+    if test -n "$env"
+        source "$env/bin/activate.fish"
+    end
+end
+_pen_hook
+"#;
+
+/// Prints the snippet activating project environments automatically when changing directory.
+///
+/// # Arguments
+/// - `shell`: The shell to print the snippet for, `bash`, `zsh` or `fish`.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if the shell is not supported.
+///
+/// # Limitations
+/// - The environment must have been synced, the hook never creates it.
+pub fn pen_shell_hook(shell: &str) -> Result<(), AnyError> {
+	let hook = match shell {
+		"bash" => HOOK_BASH,
+		"zsh" => HOOK_ZSH,
+		"fish" => HOOK_FISH,
+		_ => return error!("Unsupported shell {}, expected bash, zsh or fish", shell),
+	};
+	let pen_path = guard!(env::current_exe(), "Couldn't find the path of pen");
+
+	print!(
+		"{}",
+		hook.replace("__PEN__", &pen_path.to_string_lossy())
+			.replace("__ENV_DIR_NAME__", ENV_DIR_NAME)
+	);
+	return Ok(());
+}
+
+/// Prints the root of the project the current directory is in, or nothing outside of a project. Used by the hooks.
+pub fn pen_shell_hook_print_root() -> Result<(), AnyError> {
+	if let Ok(project_path) = get_project_root() {
+		println!("{}", project_path.display());
+	}
+	return Ok(());
+}
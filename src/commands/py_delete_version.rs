@@ -1,13 +1,27 @@
-use crate::utils::{self, error, guard, AnyError};
+use crate::utils::{self, error, get_environments_using, guard, read_registry, AnyError};
 
-pub fn py_delete_version(py_version: &String) -> Result<(), AnyError> {
-	let py_version = guard!(utils::user_string_to_version(py_version), "todo");
+pub fn py_delete_version(py_version: &str, force: bool) -> Result<(), AnyError> {
+	let py_version = guard!(utils::user_string_to_python(py_version), "todo");
 	let py_version_dir = utils::get_python_path(&py_version);
 
 	if !py_version_dir.exists() || !py_version_dir.is_dir() {
 		return error!("Error: The Python version {} is not installed.", &py_version);
 	}
 
+	let registry = guard!(read_registry(), "Couldn't read the environments registered in pen.");
+	let dependent_environments = get_environments_using(&registry, &py_version_dir);
+
+	if !dependent_environments.is_empty() {
+		println!("The Python version {} is used by:", &py_version);
+		for env_path in &dependent_environments {
+			println!("  - {}", env_path.display());
+		}
+		if !force {
+			return error!("Python version {} is still in use. Use --force to delete it anyway.", &py_version);
+		}
+		println!("These environments will be broken until the version is installed again.");
+	}
+
 	let prompt = format!("Are you sure you want to remove the Python version {} from pen? (y/N)", &py_version);
 	let user_said_yes = guard!(utils::confirm_action(&prompt), "todo");
 	if !user_said_yes {
@@ -1,5 +1,5 @@
 use crate::constants::PYTHON_VERSIONS_DIR;
-use crate::utils::{error, guard, AnyError};
+use crate::utils::{discover_system_pythons, error, guard, AnyError, PythonSource};
 use std::fs;
 
 pub fn py_list_versions() -> Result<(), AnyError> {
@@ -20,12 +20,25 @@ pub fn py_list_versions() -> Result<(), AnyError> {
 		}
 	}
 
-	if installed_versions.is_empty() {
-		println!("No Python versions installed with pen.");
+	let mut system_pythons = guard!(discover_system_pythons(), "Failed to look for Python versions on the system");
+
+	if installed_versions.is_empty() && system_pythons.is_empty() {
+		println!("No Python versions installed with pen or found on the system.");
 	} else {
 		installed_versions.sort_unstable();
 		for version in installed_versions {
-			println!("  - {}", version);
+			println!("  - {} ({})", version, PythonSource::Pen);
+		}
+		system_pythons.sort_unstable_by(|a, b| a.version.cmp(&b.version));
+		for python in system_pythons {
+			println!(
+				"  - {} ({}) {} [{} {}]",
+				python.version,
+				python.source,
+				python.executable.display(),
+				python.implementation,
+				python.abi
+			);
 		}
 	}
 	return Ok(());
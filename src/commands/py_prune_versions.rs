@@ -0,0 +1,43 @@
+use crate::constants::PYTHON_VERSIONS_DIR;
+use crate::utils::{self, get_environments_using, guard, read_registry, AnyError};
+use std::fs;
+
+pub fn py_prune_versions() -> Result<(), AnyError> {
+	let registry = guard!(read_registry(), "Couldn't read the environments registered in pen.");
+	let directory_entries = guard!(fs::read_dir(&*PYTHON_VERSIONS_DIR), "Failed to read {}", PYTHON_VERSIONS_DIR.display());
+
+	let mut unused_versions = Vec::new();
+
+	for directory_entry in directory_entries {
+		let directory_entry = guard!(directory_entry, "Failed to read directory entry");
+		let entry_metadata = guard!(directory_entry.file_type(), "Failed to read metadata");
+
+		if entry_metadata.is_dir() && get_environments_using(&registry, &directory_entry.path()).is_empty() {
+			unused_versions.push(directory_entry.path());
+		}
+	}
+
+	if unused_versions.is_empty() {
+		println!("Every Python version installed with pen is used by an environment.");
+		return Ok(());
+	}
+
+	unused_versions.sort_unstable();
+	println!("Python versions not used by any environment:");
+	for version_dir in &unused_versions {
+		println!("  - {}", version_dir.file_name().unwrap_or_default().to_string_lossy());
+	}
+
+	let user_said_yes = guard!(utils::confirm_action("Remove them from pen? (y/N)"), "todo");
+	if !user_said_yes {
+		println!("Pruning canceled");
+		return Ok(());
+	}
+
+	for version_dir in unused_versions {
+		guard!(utils::try_deleting_dir(&version_dir), "Failed to delete {}", version_dir.display());
+	}
+
+	println!("Pruning successful");
+	return Ok(());
+}
@@ -1,8 +1,9 @@
-use home;
-use std::{path::PathBuf, sync::LazyLock};
+use std::{path::PathBuf, process, sync::LazyLock};
 
 pub static ENV_DIR_NAME: &str = ".venv";
 pub static CONFIG_FILE_NAME: &str = "pen.toml";
+pub static PYPROJECT_FILE_NAME: &str = "pyproject.toml";
+pub static LOCKFILE_NAME: &str = "pen.lock";
 // pub static UPDATE_SCRIPT_URL: &str = "todo";
 
 pub static HOME_DIR: LazyLock<PathBuf> = LazyLock::new(|| match home::home_dir() {
@@ -19,9 +20,18 @@ pub static BIN_DIR: LazyLock<PathBuf> = LazyLock::new(|| HOME_DIR.join(".local/b
 pub static PEN_DIR: LazyLock<PathBuf> = LazyLock::new(|| HOME_DIR.join(".cache/pen"));
 pub static CONFIG_DIR: LazyLock<PathBuf> = LazyLock::new(|| HOME_DIR.join(".config"));
 
-pub static TMP_DIR: LazyLock<PathBuf> = LazyLock::new(|| PEN_DIR.join("temp"));
+// Every pen process gets its own temporary directory so that concurrent processes don't clear each other's files
+pub static TEMP_ROOT_DIR: LazyLock<PathBuf> = LazyLock::new(|| PEN_DIR.join("temp"));
+pub static TMP_DIR: LazyLock<PathBuf> = LazyLock::new(|| TEMP_ROOT_DIR.join(process::id().to_string()));
+pub static LOCKS_DIR: LazyLock<PathBuf> = LazyLock::new(|| PEN_DIR.join("locks"));
+pub static CACHE_LOCK_FILE: LazyLock<PathBuf> = LazyLock::new(|| LOCKS_DIR.join("cache.lock"));
 pub static PYTHON_VERSIONS_DIR: LazyLock<PathBuf> = LazyLock::new(|| PEN_DIR.join("python"));
 pub static PYTHON_PACKAGES_DIR: LazyLock<PathBuf> = LazyLock::new(|| PEN_DIR.join("packages"));
+pub static REGISTRY_FILE: LazyLock<PathBuf> = LazyLock::new(|| PEN_DIR.join("environments.toml"));
 
 pub static PEN_BIN_FILE: LazyLock<PathBuf> = LazyLock::new(|| BIN_DIR.join("pen"));
 pub static PEN_CONFIG_FILE: LazyLock<PathBuf> = LazyLock::new(|| CONFIG_DIR.join("pen"));
+
+// GraalPy doesn't publish which release implements which Python version in a machine readable form.
+// Each entry maps a Python version to the newest GraalPy release implementing it.
+pub static GRAALPY_RELEASES: [(&str, &str); 3] = [("3.10", "24.0.2"), ("3.11", "24.2.1"), ("3.12", "25.0.0")];
@@ -1,5 +1,9 @@
-use clap::{Arg, Command};
+// Functions end with an explicit `return`, like in the rest of the code
+#![allow(clippy::needless_return)]
+
+use clap::{Arg, ArgAction, Command};
 use semver::VersionReq;
+use std::path::PathBuf;
 use utils::{error, guard, AnyError};
 
 mod commands;
@@ -13,6 +17,23 @@ mod utils;
 // spec_vals.push(format!("[aliases: {all_als}]"));
 
 fn main() -> Result<(), AnyError> {
+	let delete_command = Command::new("delete")
+		.about("Delete a Python version")
+		.long_about("Delete a specific Python version. Deleting a version still used by an environment requires --force")
+		.arg(Arg::new("pyversion").help("Specify the Python version to delete").required(true).index(1))
+		.arg(
+			Arg::new("force")
+				.long("force")
+				.help("Delete the version even if environments still use it (they will be broken until the version is installed again)")
+				.action(ArgAction::SetTrue),
+		);
+
+	let env_file_arg = Arg::new("env-file")
+		.long("env-file")
+		.help("Load the variables of a dotenv file, after those of the env-files of pen.toml. Can be repeated")
+		.value_parser(clap::value_parser!(PathBuf))
+		.action(ArgAction::Append);
+
 	let matches = Command::new("pen")
 		.bin_name("pen")
 		.version("0.5.0")
@@ -30,15 +51,83 @@ fn main() -> Result<(), AnyError> {
 				.long_about("Create a new virtual environment with the specified Python version in the current directory")
 				.arg(
 					Arg::new("pyversion") // todo do we need required(true)
-						.help("Specify the Python version (ex. pen init 3.11.9)")
+						.help("Specify the Python version (ex. pen init 3.11.9 or pen init pypy@3.10)")
 						.index(1),
 				),
 		)
+		.subcommand(
+			Command::new("adopt")
+				.about("Turn an existing .venv made by venv or virtualenv into a pen project")
+				.long_about("Write a pen.toml and a pen.lock matching the Python and the packages installed in the .venv of the current directory, then rebuild it with pen. What pen can't install is kept in the .venv and listed")
+				.arg(
+					Arg::new("link-mode")
+						.long("link-mode")
+						.help("How packages are put into the .venv: symlink, hardlink, clone (reflink) or copy. Falls back to the next mode when one isn't supported")
+						.value_parser(["symlink", "hardlink", "clone", "reflink", "copy"]),
+				),
+		)
 		.subcommand(
 			Command::new("sync")
 				.visible_alias("s")
 				.about("Syncs the installed packages and the .venv with the pen.toml file")
-				.long_about("Creates the .venv according to the config"),
+				.long_about("Creates the .venv according to the config")
+				.arg(
+					Arg::new("link-mode")
+						.long("link-mode")
+						.help("How packages are put into the .venv: symlink, hardlink, clone (reflink) or copy. Falls back to the next mode when one isn't supported")
+						.value_parser(["symlink", "hardlink", "clone", "reflink", "copy"]),
+				)
+				.arg(
+					Arg::new("group")
+						.long("group")
+						.help("Also install a dependency group. Can be repeated")
+						.action(ArgAction::Append),
+				)
+				.arg(
+					Arg::new("no-dev")
+						.long("no-dev")
+						.help("Don't install the dev dependency group")
+						.action(ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			Command::new("check")
+				.about("Check that the .venv still matches the config and the lockfile")
+				.long_about("List the differences between the .venv and the config: packages that aren't locked, versions that differ, locked packages that are missing, missing entry points, broken links into the package cache and a different interpreter. Exits with a non-zero code when there are any, for CI")
+				.arg(
+					Arg::new("group")
+						.long("group")
+						.help("Also expect a dependency group to be installed. Can be repeated")
+						.action(ArgAction::Append),
+				)
+				.arg(
+					Arg::new("no-dev")
+						.long("no-dev")
+						.help("Don't expect the dev dependency group to be installed")
+						.action(ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			Command::new("run")
+				.about("Run a command or a script inside the virtual environment")
+				.long_about("Run a command, or a script of the [scripts] table of pen.toml, with the .venv of the project on the PATH, without activating it. The .venv is synced first if pen.toml changed")
+				.arg(env_file_arg.clone())
+				.arg(Arg::new("list").long("list").help("List the scripts of pen.toml").action(ArgAction::SetTrue))
+				.arg(
+					Arg::new("no-sync")
+						.long("no-sync")
+						.help("Fail instead of syncing when the .venv is not in sync with pen.toml")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("command")
+						.help("The command or script to run, followed by its arguments")
+						.required_unless_present("list")
+						.num_args(1..)
+						.trailing_var_arg(true)
+						.allow_hyphen_values(true)
+						.index(1),
+				),
 		)
 		.subcommand(Command::new("pkgs").about("Lists packages todo").long_about("todo"))
 		.subcommand(
@@ -46,25 +135,186 @@ fn main() -> Result<(), AnyError> {
 				.about("Add a package to the current project")
 				.long_about("Add a PyPI package to the current project (pip but faster)")
 				.arg(Arg::new("name").help("The package to install").required(true).index(1))
-				.arg(Arg::new("version").help("The version to install").required(false).index(2)),
+				.arg(Arg::new("version").help("The version to install").required(false).index(2))
+				.arg(
+					Arg::new("group")
+						.long("group")
+						.help("Add the package to a dependency group, ex. dev, instead of [packages]"),
+				),
+		)
+		.subcommand(
+			Command::new("import")
+				.about("Import the dependencies of another tool")
+				.long_about("Add the dependencies of a requirements.txt, a Pipfile, a Pipfile.lock or the pyproject.toml of a Poetry project to the current project. Entries that can't be translated are listed")
+				.arg(
+					Arg::new("file")
+						.help("The file to import")
+						.required(true)
+						.value_parser(clap::value_parser!(PathBuf))
+						.index(1),
+				)
+				.arg(
+					Arg::new("group")
+						.long("group")
+						.help("Add the main dependencies of the file to a dependency group, ex. dev, instead of [packages]"),
+				),
+		)
+		.subcommand(
+			Command::new("export")
+				.about("Export the locked packages for other tools")
+				.long_about("Print the locked packages as a requirements.txt with exact pins and hashes, or as a PEP 751 pylock.toml with --format pylock.toml")
+				.arg(
+					Arg::new("format")
+						.long("format")
+						.value_parser(["requirements.txt", "pylock.toml"])
+						.default_value("requirements.txt")
+						.help("The format of the export"),
+				)
+				.arg(
+					Arg::new("output")
+						.long("output")
+						.short('o')
+						.value_parser(clap::value_parser!(PathBuf))
+						.help("Write the export to a file instead of printing it"),
+				)
+				.arg(
+					Arg::new("group")
+						.long("group")
+						.help("Also export a dependency group. Can be repeated")
+						.action(ArgAction::Append),
+				)
+				.arg(
+					Arg::new("no-dev")
+						.long("no-dev")
+						.help("Don't export the dev dependency group")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("no-hashes")
+						.long("no-hashes")
+						.help("Leave out the --hash lines of a requirements.txt, which doesn't need PyPI")
+						.action(ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			Command::new("remove")
+				.about("Remove packages from the current project")
+				.long_about("Remove packages from pen.toml and from the .venv. The package cache is kept, see `pen cache prune`")
+				.arg(Arg::new("names").help("The packages to remove").required(true).num_args(1..).index(1)),
+		)
+		.subcommand(
+			Command::new("outdated")
+				.about("List packages with newer versions")
+				.long_about("List the packages of the project and their dependencies with newer versions on PyPI: the newest version the requirements allow and the newest version overall. Packages that aren't locked are compared by the version in the .venv"),
+		)
+		.subcommand(
+			Command::new("upgrade")
+				.about("Upgrade packages to newer versions")
+				.long_about("Lock newer versions of packages, within the requirements of pen.toml unless --latest is given. Upgrades every package when none is given")
+				.arg(Arg::new("names").help("The packages to upgrade").num_args(0..).index(1))
+				.arg(
+					Arg::new("latest")
+						.long("latest")
+						.help("Rewrite the requirements of pen.toml that don't allow the newest version")
+						.action(ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			Command::new("tree")
+				.about("Show the dependency tree")
+				.long_about("Show the dependency tree of the locked packages, with their versions. Packages already shown are marked with (*)")
+				.arg(Arg::new("package").help("Start the tree from this package").index(1))
+				.arg(
+					Arg::new("depth")
+						.long("depth")
+						.help("How many levels of dependencies to show")
+						.value_parser(clap::value_parser!(usize)),
+				)
+				.arg(
+					Arg::new("invert")
+						.long("invert")
+						.short('i')
+						.help("Show the packages depending on each package instead of its dependencies")
+						.action(ArgAction::SetTrue),
+				),
+		)
+		.subcommand(
+			Command::new("why")
+				.about("Show why a package is installed")
+				.long_about("Show every path from a package of pen.toml to the given package")
+				.arg(Arg::new("package").help("The package to explain").required(true).index(1)),
+		)
+		.subcommand(
+			Command::new("activate")
+				.about("Activate the virtual environment")
+				.visible_alias("a")
+				.arg(env_file_arg),
 		)
-		.subcommand(Command::new("activate").about("Activate the virtual environment").visible_alias("a"))
 		.long_about("Activate the virtual environment in the current directory")
+		.subcommand(
+			Command::new("shell-hook")
+				.about("Print a snippet activating environments when changing directory")
+				.long_about("Print a snippet to eval in your shell rc file, ex. eval \"$(pen shell-hook bash)\", activating the environment of a project when entering it and deactivating it when leaving it")
+				.arg(
+					Arg::new("shell")
+						.help("The shell to print the snippet for")
+						.value_parser(["bash", "zsh", "fish"])
+						.required_unless_present("print-root")
+						.index(1),
+				)
+				.arg(Arg::new("print-root").long("print-root").hide(true).action(ArgAction::SetTrue)),
+		)
 		.subcommand(
 			Command::new("list")
 				.visible_alias("l")
 				.about("List Python versions")
-				.long_about("List the installed Python versions from pen"),
+				.long_about("List the Python versions installed by pen and the ones found on the system"),
 		)
 		.subcommand(
-			Command::new("delete")
-				.about("Delete a Python version")
-				.long_about("Delete a specific Python version")
-				.arg(
-					Arg::new("pyversion")
-						.help("Specify the Python version to delete (this might break installed .venv until the version is installed again)")
-						.required(true)
-						.index(1),
+			Command::new("python")
+				.about("Manage Python versions")
+				.long_about("Manage the Python versions installed by pen or found on the system")
+				.subcommand_required(true)
+				.subcommand(
+					Command::new("list")
+						.visible_alias("l")
+						.about("List Python versions")
+						.long_about("List the Python versions installed by pen and the ones found on the system, with where they came from"),
+				)
+				.subcommand(delete_command.clone())
+				.subcommand(
+					Command::new("prune")
+						.about("Delete unused Python versions")
+						.long_about("Delete the Python versions installed by pen that no registered environment uses"),
+				),
+		)
+		.subcommand(delete_command)
+		.subcommand(
+			Command::new("cache")
+				.about("Manage the package cache")
+				.long_about("Inspect and reclaim the space used by the packages downloaded by pen")
+				.subcommand_required(true)
+				.subcommand(
+					Command::new("info")
+						.about("Show the size of the package cache")
+						.long_about("Show the total size of the package cache, and the size and number of cached versions of each package"),
+				)
+				.subcommand(
+					Command::new("prune")
+						.about("Remove unused packages from the cache")
+						.long_about("Remove the cached packages that no registered environment or the pen.lock of its project uses, after confirming"),
+				)
+				.subcommand(
+					Command::new("clean")
+						.about("Remove packages from the cache")
+						.long_about("Remove every cached version of the given packages. Removing a version still used by an environment requires --force")
+						.arg(Arg::new("names").help("The packages to remove").required(true).num_args(1..).index(1))
+						.arg(
+							Arg::new("force")
+								.long("force")
+								.help("Remove the versions environments still use (they will be broken until `pen sync` downloads them again)")
+								.action(ArgAction::SetTrue),
+						),
 				),
 		)
 		.subcommand(
@@ -79,35 +329,107 @@ fn main() -> Result<(), AnyError> {
 		)
 		.get_matches();
 
+	// The shell hook runs on every directory change, it skips the checks and locks below to stay fast
+	if let Some(("shell-hook", args)) = matches.subcommand() {
+		if args.get_flag("print-root") {
+			return commands::pen_shell_hook_print_root();
+		}
+		let shell: &String = args.get_one("shell").expect("required argument");
+		return commands::pen_shell_hook(shell);
+	}
+
 	let dependencies = vec!["tar", "make"]; // todo goal of having no system dependencies
-	utils::assert_dependencies(dependencies);
-	utils::assert_global_paths();
-	utils::clear_temp();
+	utils::assert_dependencies(dependencies)?;
+	utils::assert_global_paths()?;
+
+	// Commands deleting from the cache wait for every other pen process to be done with it
+	let deletes_from_cache = match matches.subcommand() {
+		Some(("cache", args)) => matches!(args.subcommand_name(), Some("prune") | Some("clean")),
+		Some(("python", args)) => matches!(args.subcommand_name(), Some("delete") | Some("prune")),
+		Some(("delete", _)) | Some(("uninstall", _)) => true,
+		_ => false,
+	};
+	let cache_lock = guard!(utils::lock_cache(deletes_from_cache), "Couldn't lock the pen cache.");
+	let _temp_lock = guard!(utils::create_process_temp(), "Couldn't create the temporary directory.");
+	guard!(utils::clear_temp(), "Couldn't clear the temporary directories of previous runs.");
 
 	match matches.subcommand() {
 		// Python
 		Some(("list", _args)) => {
 			return commands::py_list_versions();
 		}
+		Some(("python", args)) => match args.subcommand() {
+			Some(("list", _args)) => {
+				return commands::py_list_versions();
+			}
+			Some(("delete", args)) => {
+				let py_version: &String = args.get_one("pyversion").expect("required argument");
+				return commands::py_delete_version(py_version, args.get_flag("force"));
+			}
+			Some(("prune", _args)) => {
+				return commands::py_prune_versions();
+			}
+			_ => {
+				return error!("Unknown command");
+			}
+		},
 		Some(("delete", args)) => {
 			let py_version: &String = args.get_one("pyversion").expect("required argument");
-			return commands::py_delete_version(py_version);
+			return commands::py_delete_version(py_version, args.get_flag("force"));
 		}
 
+		// Cache
+		Some(("cache", args)) => match args.subcommand() {
+			Some(("info", _args)) => {
+				return commands::cache_info();
+			}
+			Some(("prune", _args)) => {
+				return commands::cache_prune();
+			}
+			Some(("clean", args)) => {
+				let names = args.get_many::<String>("names").expect("required argument").collect();
+				return commands::cache_clean(names, args.get_flag("force"));
+			}
+			_ => {
+				return error!("Unknown command");
+			}
+		},
+
 		//* Pen
 		Some(("init", args)) => {
 			let version = match args.get_one::<String>("pyversion") {
-				Some(version) => guard!(utils::user_string_to_version(version), "todo"),
+				Some(version) => guard!(utils::user_string_to_python(version), "todo"),
 				None => todo!(),
 			};
 
 			return commands::env_init(version);
 		}
-		Some(("sync", _args)) => {
-			// if let Err(e) = commands::env_sync() {
-			// 	todo!()
-			// }
-			todo!();
+		Some(("adopt", args)) => {
+			let link_mode = match args.get_one::<String>("link-mode") {
+				Some(link_mode) => Some(guard!(utils::LinkMode::parse(link_mode), "Invalid link mode")),
+				None => None,
+			};
+			return commands::env_adopt(link_mode);
+		}
+		Some(("sync", args)) => {
+			let link_mode = match args.get_one::<String>("link-mode") {
+				Some(link_mode) => Some(guard!(utils::LinkMode::parse(link_mode), "Invalid link mode")),
+				None => None,
+			};
+			let groups = args.get_many::<String>("group").unwrap_or_default().cloned().collect();
+			return commands::env_sync(link_mode, groups, args.get_flag("no-dev"));
+		}
+		Some(("check", args)) => {
+			let groups = args.get_many::<String>("group").unwrap_or_default().cloned().collect();
+			return commands::env_check(groups, args.get_flag("no-dev"));
+		}
+		Some(("run", args)) => {
+			if args.get_flag("list") {
+				return commands::env_run_list();
+			}
+			let command = args.get_many::<String>("command").expect("required argument").collect();
+			let env_files = args.get_many::<PathBuf>("env-file").unwrap_or_default().cloned().collect();
+			return commands::env_run(command, env_files, !args.get_flag("no-sync"), cache_lock);
 		}
 		Some(("pkgs", _args)) => {
 			return commands::env_pkgs();
@@ -119,10 +441,45 @@ fn main() -> Result<(), AnyError> {
 				None => VersionReq::default(),
 			};
 
-			return commands::env_add(name, &version);
+			return commands::env_add(name, &version, args.get_one::<String>("group"));
+		}
+		Some(("import", args)) => {
+			let file = args.get_one::<PathBuf>("file").expect("required argument");
+			return commands::env_import(file, args.get_one::<String>("group"));
+		}
+		Some(("export", args)) => {
+			let groups = args.get_many::<String>("group").unwrap_or_default().cloned().collect();
+			let pylock = args.get_one::<String>("format").is_some_and(|format| format == "pylock.toml");
+			return commands::env_export(
+				groups,
+				args.get_flag("no-dev"),
+				pylock,
+				args.get_one::<PathBuf>("output"),
+				args.get_flag("no-hashes"),
+			);
+		}
+		Some(("remove", args)) => {
+			let names = args.get_many::<String>("names").expect("required argument").collect();
+			return commands::env_remove(names);
+		}
+		Some(("outdated", _args)) => {
+			return commands::env_outdated();
+		}
+		Some(("upgrade", args)) => {
+			let names = args.get_many::<String>("names").unwrap_or_default().collect();
+			return commands::env_upgrade(names, args.get_flag("latest"));
+		}
+		Some(("tree", args)) => {
+			let depth = args.get_one::<usize>("depth").copied();
+			return commands::env_tree(args.get_one::<String>("package"), depth, args.get_flag("invert"));
+		}
+		Some(("why", args)) => {
+			let package: &String = args.get_one("package").expect("required argument");
+			return commands::env_why(package);
 		}
-		Some(("activate", _args)) => {
-			return commands::pen_activate();
+		Some(("activate", args)) => {
+			let env_files = args.get_many::<PathBuf>("env-file").unwrap_or_default().cloned().collect();
+			return commands::pen_activate(env_files);
 		}
 
 		// Installation
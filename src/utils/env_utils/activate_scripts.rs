@@ -0,0 +1,209 @@
+use std::{fs, path::Path};
+
+use crate::utils::{guard, AnyError};
+
+// The activate scripts follow the ones written by `python -m venv`, so that tools expecting them keep working.
+// __VIRTUAL_ENV__ and __VIRTUAL_ENV_PROMPT__ are replaced when writing them.
+
+static ACTIVATE_SH: &str = r#"# This file must be used with "source bin/activate" *from bash, zsh or sh*
+# you cannot run it directly
+
+deactivate () {
+    # reset old environment variables
+    if [ -n "${_OLD_VIRTUAL_PATH:-}" ] ; then
+        PATH="${_OLD_VIRTUAL_PATH:-}"
+        export PATH
+        unset _OLD_VIRTUAL_PATH
+    fi
+    if [ -n "${_OLD_VIRTUAL_PYTHONHOME:-}" ] ; then
+        PYTHONHOME="${_OLD_VIRTUAL_PYTHONHOME:-}"
+        export PYTHONHOME
+        unset _OLD_VIRTUAL_PYTHONHOME
+    fi
+
+    # Forget past commands, otherwise the $PATH changes may not be respected
+    hash -r 2> /dev/null
+
+    if [ -n "${_OLD_VIRTUAL_PS1:-}" ] ; then
+        PS1="${_OLD_VIRTUAL_PS1:-}"
+        export PS1
+        unset _OLD_VIRTUAL_PS1
+    fi
+
+    unset VIRTUAL_ENV
+    unset VIRTUAL_ENV_PROMPT
+    if [ ! "${1:-}" = "nondestructive" ] ; then
+        unset -f deactivate
+    fi
+}
+
+# unset irrelevant variables
+deactivate nondestructive
+
+VIRTUAL_ENV="__VIRTUAL_ENV__"
+export VIRTUAL_ENV
+
+_OLD_VIRTUAL_PATH="$PATH"
+PATH="$VIRTUAL_ENV/bin:$PATH"
+export PATH
+
+VIRTUAL_ENV_PROMPT="(__VIRTUAL_ENV_PROMPT__) "
+export VIRTUAL_ENV_PROMPT
+
+# unset PYTHONHOME if set
+if [ -n "${PYTHONHOME:-}" ] ; then
+    _OLD_VIRTUAL_PYTHONHOME="${PYTHONHOME:-}"
+    unset PYTHONHOME
+fi
+
+if [ -z "${VIRTUAL_ENV_DISABLE_PROMPT:-}" ] ; then
+    _OLD_VIRTUAL_PS1="${PS1:-}"
+    PS1="${VIRTUAL_ENV_PROMPT}${PS1:-}"
+    export PS1
+fi
+
+hash -r 2> /dev/null
+"#;
+
+static ACTIVATE_FISH: &str = r#"# This file must be used with "source bin/activate.fish" *from fish*
+# you cannot run it directly
+
+function deactivate -d "Exit virtual environment and return to normal shell environment"
+    # reset old environment variables
+    if test -n "$_OLD_VIRTUAL_PATH"
+        set -gx PATH $_OLD_VIRTUAL_PATH
+        set -e _OLD_VIRTUAL_PATH
+    end
+    if test -n "$_OLD_VIRTUAL_PYTHONHOME"
+        set -gx PYTHONHOME $_OLD_VIRTUAL_PYTHONHOME
+        set -e _OLD_VIRTUAL_PYTHONHOME
+    end
+
+    if test -n "$_OLD_FISH_PROMPT_OVERRIDE"
+        set -e _OLD_FISH_PROMPT_OVERRIDE
+        if functions -q _old_fish_prompt
+            functions -e fish_prompt
+            functions -c _old_fish_prompt fish_prompt
+            functions -e _old_fish_prompt
+        end
+    end
+
+    set -e VIRTUAL_ENV
+    set -e VIRTUAL_ENV_PROMPT
+    if test "$argv[1]" != "nondestructive"
+        functions -e deactivate
+    end
+end
+
+# unset irrelevant variables
+deactivate nondestructive
+
+set -gx VIRTUAL_ENV "__VIRTUAL_ENV__"
+
+set -gx _OLD_VIRTUAL_PATH $PATH
+set -gx PATH "$VIRTUAL_ENV/bin" $PATH
+set -gx VIRTUAL_ENV_PROMPT "(__VIRTUAL_ENV_PROMPT__) "
+
+# unset PYTHONHOME if set
+if set -q PYTHONHOME
+    set -gx _OLD_VIRTUAL_PYTHONHOME $PYTHONHOME
+    set -e PYTHONHOME
+end
+
+if test -z "$VIRTUAL_ENV_DISABLE_PROMPT"
+    # fish uses a function instead of an env var to generate the prompt
+    functions -c fish_prompt _old_fish_prompt
+
+    function fish_prompt
+        set -l old_status $status
+        printf "%s%s%s" (set_color 4B8BBE) $VIRTUAL_ENV_PROMPT (set_color normal)
+        # Restore the status of the previous command for the original prompt
+        echo "exit $old_status" | .
+        _old_fish_prompt
+    end
+
+    set -gx _OLD_FISH_PROMPT_OVERRIDE "$VIRTUAL_ENV"
+end
+"#;
+
+static ACTIVATE_CSH: &str = r#"# This file must be used with "source bin/activate.csh" *from csh*
+# you cannot run it directly
+
+alias deactivate 'test $?_OLD_VIRTUAL_PATH != 0 && setenv PATH "$_OLD_VIRTUAL_PATH" && unset _OLD_VIRTUAL_PATH; rehash; test $?_OLD_VIRTUAL_PROMPT != 0 && set prompt="$_OLD_VIRTUAL_PROMPT" && unset _OLD_VIRTUAL_PROMPT; unsetenv VIRTUAL_ENV; unsetenv VIRTUAL_ENV_PROMPT; test "\!:*" != "nondestructive" && unalias deactivate'
+
+# unset irrelevant variables
+deactivate nondestructive
+
+setenv VIRTUAL_ENV "__VIRTUAL_ENV__"
+
+set _OLD_VIRTUAL_PATH="$PATH"
+setenv PATH "$VIRTUAL_ENV/bin:$PATH"
+setenv VIRTUAL_ENV_PROMPT "(__VIRTUAL_ENV_PROMPT__) "
+
+set _OLD_VIRTUAL_PROMPT="$prompt"
+
+if (! "$?VIRTUAL_ENV_DISABLE_PROMPT") then
+    set prompt = "$VIRTUAL_ENV_PROMPT$prompt"
+endif
+
+rehash
+"#;
+
+// Startup files used by `pen activate` to start an interactive shell that loads the user's own startup files,
+// then the activate script. In that shell `deactivate` leaves the shell, since it was started for the environment.
+
+static PEN_BASHRC: &str = r#"[ -f "$HOME/.bashrc" ] && . "$HOME/.bashrc"
+. "__VIRTUAL_ENV__/bin/activate"
+deactivate () { exit; }
+"#;
+
+static PEN_ZSHENV: &str = r#"[ -f "${PEN_USER_ZDOTDIR:-$HOME}/.zshenv" ] && . "${PEN_USER_ZDOTDIR:-$HOME}/.zshenv"
+"#;
+
+static PEN_ZSHRC: &str = r#"ZDOTDIR="${PEN_USER_ZDOTDIR:-$HOME}"
+unset PEN_USER_ZDOTDIR
+[ -f "$ZDOTDIR/.zshrc" ] && . "$ZDOTDIR/.zshrc"
+. "__VIRTUAL_ENV__/bin/activate"
+deactivate () { exit }
+"#;
+
+static PEN_SHRC: &str = r#"[ -n "${PEN_USER_ENV:-}" ] && [ -f "$PEN_USER_ENV" ] && . "$PEN_USER_ENV"
+unset PEN_USER_ENV
+. "__VIRTUAL_ENV__/bin/activate"
+deactivate () { exit; }
+"#;
+
+/// Writes the activate scripts of a virtual environment, and the startup files `pen activate` starts shells with.
+///
+/// # Arguments
+/// - `staging_path`: Where the environment is being built.
+/// - `env_path`: Where the environment will be once built, which the scripts refer to.
+/// - `prompt`: The name shown in the shell prompt while the environment is active.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if a script can't be written.
+pub fn write_activate_scripts(staging_path: &Path, env_path: &Path, prompt: &str) -> Result<(), AnyError> {
+	let pen_dir = staging_path.join("pen");
+	guard!(fs::create_dir_all(pen_dir.join("zsh")), "Couldn't create folder {}", pen_dir.display());
+
+	let scripts = [
+		(staging_path.join("bin/activate"), ACTIVATE_SH),
+		(staging_path.join("bin/activate.fish"), ACTIVATE_FISH),
+		(staging_path.join("bin/activate.csh"), ACTIVATE_CSH),
+		(pen_dir.join("bashrc"), PEN_BASHRC),
+		(pen_dir.join("zsh/.zshenv"), PEN_ZSHENV),
+		(pen_dir.join("zsh/.zshrc"), PEN_ZSHRC),
+		(pen_dir.join("shrc"), PEN_SHRC),
+	];
+
+	for (script_path, template) in scripts {
+		let script = template
+			.replace("__VIRTUAL_ENV__", &env_path.to_string_lossy())
+			.replace("__VIRTUAL_ENV_PROMPT__", prompt);
+		guard!(fs::write(&script_path, script), "Couldn't write {}", script_path.display());
+	}
+	return Ok(());
+}
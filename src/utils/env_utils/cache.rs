@@ -0,0 +1,50 @@
+use semver::Version;
+use std::{fs, path::PathBuf};
+
+use crate::constants::PYTHON_PACKAGES_DIR;
+use crate::utils::{get_dir_size, guard, AnyError};
+
+pub struct CachedPackage {
+	pub name: String,
+	pub version: String,
+	pub path: PathBuf,
+	pub size: u64,
+}
+
+/// Lists the packages downloaded in `PYTHON_PACKAGES_DIR`.
+///
+/// # Output
+/// - One entry per cached version of a package, sorted by name then version.
+///
+/// # Termination
+/// - This function returns an error if the cache or one of its entries can't be read.
+///
+/// # Limitations
+/// - Entries whose name doesn't follow the `{name}_{version}` format of `get_package_path` are ignored.
+pub fn list_cached_packages() -> Result<Vec<CachedPackage>, AnyError> {
+	let directory_entries = guard!(fs::read_dir(&*PYTHON_PACKAGES_DIR), "Failed to read {}", PYTHON_PACKAGES_DIR.display());
+
+	let mut packages = Vec::new();
+	for directory_entry in directory_entries {
+		let directory_entry = guard!(directory_entry, "Failed to read directory entry");
+		let file_name = directory_entry.file_name().to_string_lossy().to_string();
+
+		// Package names can contain underscores, versions can't
+		let (name, version) = match file_name.rsplit_once('_') {
+			Some((name, version)) => (name.to_string(), version.to_string()),
+			None => continue,
+		};
+		let path = directory_entry.path();
+		let size = get_dir_size(&path)?;
+		packages.push(CachedPackage { name, version, path, size });
+	}
+
+	// Versions compared as semver, so that 1.10.0 comes after 1.9.0
+	packages.sort_unstable_by(|a, b| {
+		a.name
+			.cmp(&b.name)
+			.then_with(|| Version::parse(&a.version).ok().cmp(&Version::parse(&b.version).ok()))
+			.then_with(|| a.version.cmp(&b.version))
+	});
+	return Ok(packages);
+}
@@ -1,36 +1,228 @@
-use semver::Version;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
-use toml;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, TableLike, Value};
 
-use crate::constants::CONFIG_FILE_NAME;
-use crate::utils::{guard, AnyError};
+use crate::constants::{CONFIG_FILE_NAME, PYPROJECT_FILE_NAME};
+use crate::utils::{error, guard, read_pyproject_config, validate_config, validate_pyproject, write_pyproject_config, AnyError, PythonSpec, Script};
 
-// todo docstring
-pub fn read_config(project_path: &PathBuf) -> Result<Config, AnyError> {
-	let config_path = project_path.join(&*CONFIG_FILE_NAME);
+/// Gets the path of the file holding the config of a project.
+///
+/// # Output
+/// - The `pen.toml` of the project, or its `pyproject.toml` when it has no `pen.toml`.
+///   The `pen.toml` path when there is neither.
+pub fn get_config_path(project_path: &Path) -> PathBuf {
+	let config_path = project_path.join(CONFIG_FILE_NAME);
+	let pyproject_path = project_path.join(PYPROJECT_FILE_NAME);
+	if !config_path.exists() && pyproject_path.exists() {
+		return pyproject_path;
+	}
+	return config_path;
+}
+
+fn is_pyproject(config_path: &Path) -> bool {
+	return config_path.file_name().is_some_and(|name| name == PYPROJECT_FILE_NAME);
+}
+
+/// Reads the config of a project from its `pen.toml`, or from its `pyproject.toml` when it has no `pen.toml`.
+///
+/// # Arguments
+/// - `project_path`: The project root.
+///
+/// # Output
+/// - The config.
+///
+/// # Termination
+/// - This function returns an error if the file can't be read or doesn't match the schema of the config.
+///   Every problem of the file is printed first, with its line and column.
+pub fn read_config(project_path: &Path) -> Result<Config, AnyError> {
+	let config_path = get_config_path(project_path);
 	let contents = guard!(fs::read_to_string(&config_path), "Couldn't read {}.", config_path.display());
+	let problems = match is_pyproject(&config_path) {
+		true => validate_pyproject(&contents, &config_path.display().to_string()),
+		false => validate_config(&contents, &config_path.display().to_string()),
+	};
+	if !problems.is_empty() {
+		for problem in &problems {
+			eprintln!("{}", problem);
+		}
+		return error!("{} is invalid ({} problem(s)).", config_path.display(), problems.len());
+	}
+	if is_pyproject(&config_path) {
+		return read_pyproject_config(&config_path);
+	}
 	let toml = guard!(toml::from_str::<Config>(&contents), "Couldn't parse {}.", config_path.display());
 	return Ok(toml);
 }
 
-// todo docstring
+/// Writes the config of a project to its `pen.toml`, or to its `pyproject.toml` when it has no `pen.toml`.
+///
+/// # Arguments
+/// - `project_path`: The project root.
+/// - `config`: The new config.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if the existing `pen.toml` can't be parsed or if the file can't be written.
+///
+/// # Guarantees
+/// - Only the entries whose value changed are rewritten. Comments, formatting, ordering and the keys pen doesn't know about
+///   are kept as they were.
 pub fn write_config(project_path: PathBuf, config: Config) -> Result<(), AnyError> {
+	let config_path = get_config_path(&project_path);
+	if is_pyproject(&config_path) {
+		return write_pyproject_config(&config_path, &config);
+	}
 	let toml = guard!(
 		toml::to_string_pretty(&config),
 		"Couldn't convert config to valid toml.\nPlease open an issue on Github."
 	);
+	let new_document = guard!(
+		toml.parse::<DocumentMut>(),
+		"Couldn't convert config to valid toml.\nPlease open an issue on Github."
+	);
+
+	let mut document = match fs::read_to_string(&config_path) {
+		Ok(contents) => guard!(contents.parse::<DocumentMut>(), "Couldn't parse {}.", config_path.display()),
+		Err(_) => DocumentMut::new(),
+	};
+	merge_table(document.as_table_mut(), new_document.as_table(), Some(&CONFIG_KEYS));
+
 	guard!(
-		fs::write(project_path.join("pen.toml"), toml),
+		fs::write(&config_path, document.to_string()),
 		"Couldn't write to config file at {}.",
 		project_path.display()
 	);
 	return Ok(());
 }
 
+/// The keys of `pen.toml` that `Config` reads, the others are left alone when writing it.
+static CONFIG_KEYS: [&str; 5] = ["python", "env-files", "packages", "dependency-groups", "scripts"];
+
+/// Applies the entries of `new` to `old`, leaving the entries that didn't change untouched.
+///
+/// # Arguments
+/// - `old`: The table of the document being edited.
+/// - `new`: The same table, as serialized from the config.
+/// - `managed_keys`: The keys of `old` that may be removed when missing from `new`, all of them when `None`.
+pub fn merge_table(old: &mut dyn TableLike, new: &dyn TableLike, managed_keys: Option<&[&str]>) {
+	let removed_keys: Vec<String> = old
+		.iter()
+		.filter(|(key, _)| new.get(key).is_none() && managed_keys.is_none_or(|keys| keys.contains(key)))
+		// An empty table means the same as a missing one, and may hold comments
+		.filter(|(_, item)| !item.as_table_like().is_some_and(|table| table.is_empty()))
+		.map(|(key, _)| String::from(key))
+		.collect();
+	for key in removed_keys {
+		old.remove(&key);
+	}
+
+	for (key, new_item) in new.iter() {
+		let old_item = match old.get_mut(key) {
+			Some(old_item) => old_item,
+			None => {
+				old.insert(key, new_item.clone());
+				continue;
+			}
+		};
+		if is_same_item(old_item, new_item) {
+			continue;
+		}
+
+		match (old_item.as_table_like_mut(), new_item.as_table_like()) {
+			(Some(old_table), Some(new_table)) => merge_table(old_table, new_table, None),
+			_ => match (old_item.as_value_mut(), new_item.as_value()) {
+				(Some(old_value), Some(new_value)) => {
+					// Keeps the comments and spacing around the value
+					let decor = old_value.decor().clone();
+					*old_value = new_value.clone();
+					*old_value.decor_mut() = decor;
+				}
+				_ => *old_item = new_item.clone(),
+			},
+		}
+	}
+}
+
+/// Compares two items by value, ignoring formatting, ex. `'a'` and `"a"`, or `{ a = 1 }` and a `[table]` with `a = 1`.
+fn is_same_item(a: &Item, b: &Item) -> bool {
+	if let (Some(a), Some(b)) = (a.as_table_like(), b.as_table_like()) {
+		return a.len() == b.len()
+			&& a.iter()
+				.all(|(key, a_item)| b.get(key).is_some_and(|b_item| is_same_item(a_item, b_item)));
+	}
+	if let (Some(Value::Array(a)), Some(Value::Array(b))) = (a.as_value(), b.as_value()) {
+		return a.len() == b.len()
+			&& a.iter()
+				.zip(b.iter())
+				.all(|(a, b)| is_same_item(&Item::Value(a.clone()), &Item::Value(b.clone())));
+	}
+	return match (a.as_value(), b.as_value()) {
+		(Some(Value::String(a)), Some(Value::String(b))) => a.value() == b.value(),
+		(Some(Value::Integer(a)), Some(Value::Integer(b))) => a.value() == b.value(),
+		(Some(Value::Float(a)), Some(Value::Float(b))) => a.value() == b.value(),
+		(Some(Value::Boolean(a)), Some(Value::Boolean(b))) => a.value() == b.value(),
+		(Some(Value::Datetime(a)), Some(Value::Datetime(b))) => a.value() == b.value(),
+		_ => false,
+	};
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
-	pub python: Version, // todo do we want to have instead a VersionReq?
+	pub python: PythonSpec, // todo do we want to have instead a VersionReq?
+	/// Dotenv files loaded by `pen run` and `pen activate`, relative to the project root.
+	#[serde(default, rename = "env-files", skip_serializing_if = "Vec::is_empty")]
+	pub env_files: Vec<PathBuf>,
 	pub packages: toml::Table,
+	/// Packages only needed for development, by group name, ex. `dev`, `test` or `docs`.
+	#[serde(default, rename = "dependency-groups", skip_serializing_if = "BTreeMap::is_empty")]
+	pub dependency_groups: BTreeMap<String, toml::Table>,
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub scripts: BTreeMap<String, Script>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn merge(old: &str, new: &str, managed_keys: Option<&[&str]>) -> String {
+		let mut old = old.parse::<DocumentMut>().unwrap();
+		let new = new.parse::<DocumentMut>().unwrap();
+		merge_table(old.as_table_mut(), new.as_table(), managed_keys);
+		return old.to_string();
+	}
+
+	#[test]
+	fn unchanged_entries_keep_their_formatting() {
+		let old = "# The project\npython = '3.12.4'\n\n[packages]\nrequests = { version = \"^2.32\" } # http\n";
+		let new = "python = \"3.12.4\"\n\n[packages]\nrequests = { version = '^2.32' }\n";
+		assert_eq!(merge(old, new, None), old);
+	}
+
+	#[test]
+	fn changed_values_keep_their_comments() {
+		let old = "[packages]\nrequests = \"^2.31\" # http\nrich = \"^13\"\n";
+		let new = "[packages]\nrequests = \"^2.32\"\nrich = \"^13\"\nclick = \"^8\"\n";
+		assert_eq!(
+			merge(old, new, None),
+			"[packages]\nrequests = \"^2.32\" # http\nrich = \"^13\"\nclick = \"^8\"\n"
+		);
+	}
+
+	#[test]
+	fn only_managed_keys_are_removed() {
+		let old = "python = \"3.12.4\"\nowner = \"me\"\n\n[scripts]\ntest = \"pytest\"\n\n[packages]\n";
+		let new = "python = \"3.12.4\"\n\n[packages]\n";
+		assert_eq!(merge(old, new, Some(&CONFIG_KEYS)), "python = \"3.12.4\"\nowner = \"me\"\n\n[packages]\n");
+	}
+
+	#[test]
+	fn empty_tables_are_kept() {
+		let old = "python = \"3.12.4\"\n\n[scripts]\n# Added later\n\n[packages]\n";
+		let new = "python = \"3.12.4\"\n\n[packages]\n";
+		assert_eq!(merge(old, new, Some(&CONFIG_KEYS)), old);
+	}
 }
@@ -0,0 +1,444 @@
+use semver::VersionReq;
+use std::ops::Range;
+use toml_edit::{ImDocument, Item, TableLike};
+
+use crate::utils::{parse_project_dependency, to_version_requirement, PythonSpec, MAIN_GROUP, PEN_TOOL_KEYS};
+
+/// The keys allowed at the top of `pen.toml`. `tool` holds the settings of other tools and isn't checked.
+static KNOWN_TOP_LEVEL_KEYS: [&str; 6] = ["python", "env-files", "packages", "dependency-groups", "scripts", "tool"];
+static SCRIPT_KEYS: [&str; 3] = ["cmd", "env", "help"];
+static VERSION_REQUIREMENT_EXAMPLES: &str = "ex. \"^2.32\", \">=1.4, <2\" or \"*\"";
+
+/// Checks the contents of a `pen.toml` against the schema of the config.
+///
+/// # Arguments
+/// - `contents`: The contents of the config file.
+/// - `file_name`: The name of the config file, which starts every problem.
+///
+/// # Output
+/// - Every problem found, in the order of the file, ex. `pen.toml:4:11: `packages.requests`: invalid version requirement ...`.
+///   The line and column are left out for missing keys. Empty when the config is valid.
+///
+/// # Guarantees
+/// - When no problem is found, the contents deserialize to a `Config` whose requirements are all valid.
+pub fn validate_config(contents: &str, file_name: &str) -> Vec<String> {
+	return validate(contents, file_name, Validator::validate_root);
+}
+
+/// Checks the contents of a `pyproject.toml` used as the config of a project: the dependencies of `[project]` and `[tool.pen]`.
+///
+/// # Arguments
+/// - `contents`: The contents of the file.
+/// - `file_name`: The name of the file, which starts every problem.
+///
+/// # Output
+/// - Every problem found, in the order of the file, like `validate_config`. Empty when the file is valid.
+///
+/// # Guarantees
+/// - The other tables and the other keys of `[project]` are not checked, they belong to other tools.
+pub fn validate_pyproject(contents: &str, file_name: &str) -> Vec<String> {
+	return validate(contents, file_name, Validator::validate_pyproject_root);
+}
+
+fn validate<'a>(contents: &'a str, file_name: &'a str, validate_root: fn(&mut Validator<'a>, &dyn TableLike)) -> Vec<String> {
+	let mut validator = Validator {
+		contents,
+		file_name,
+		problems: Vec::new(),
+	};
+	match ImDocument::parse(contents) {
+		Ok(document) => validate_root(&mut validator, document.as_table()),
+		Err(e) => validator.push(e.span(), &e.message().trim_end().replace('\n', ", ")),
+	}
+
+	validator.problems.sort_by_key(|(offset, _)| offset.unwrap_or(usize::MAX));
+	return validator.problems.into_iter().map(|(_, problem)| problem).collect();
+}
+
+struct Validator<'a> {
+	contents: &'a str,
+	file_name: &'a str,
+	/// The problems found, with the byte offset they are at.
+	problems: Vec<(Option<usize>, String)>,
+}
+
+impl Validator<'_> {
+	fn push(&mut self, span: Option<Range<usize>>, message: &str) {
+		let offset = span.map(|span| span.start.min(self.contents.len()));
+		let problem = match offset {
+			Some(offset) => {
+				let before = &self.contents[..offset];
+				let line = before.matches('\n').count() + 1;
+				let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+				format!("{}:{}:{}: {}", self.file_name, line, column, message)
+			}
+			None => format!("{}: {}", self.file_name, message),
+		};
+		self.problems.push((offset, problem));
+	}
+
+	fn push_type_error(&mut self, item: &Item, span: Option<Range<usize>>, path: &str, expected: &str) {
+		let message = format!("`{}`: expected {}, found {}", path, expected, item.type_name());
+		self.push(item.span().or(span), &message);
+	}
+
+	fn push_unknown_key(&mut self, table: &dyn TableLike, key: &str, path: &str, known_keys: &[&str]) {
+		let path = match path.is_empty() {
+			true => String::new(),
+			false => format!("`{}`: ", path),
+		};
+		let message = match get_closest_key(key, known_keys) {
+			Some(closest) => format!("{}unknown key `{}`, did you mean `{}`?", path, key, closest),
+			None => format!("{}unknown key `{}`, expected one of `{}`", path, key, known_keys.join("`, `")),
+		};
+		self.push(get_key_span(table, key), &message);
+	}
+
+	fn validate_root(&mut self, root: &dyn TableLike) {
+		for (key, item) in root.iter() {
+			let key_span = get_key_span(root, key);
+			match key {
+				"python" => {
+					self.validate_python(item, key_span, "python");
+				}
+				"env-files" => self.validate_env_files(item, key_span, "env-files"),
+				"packages" => self.validate_packages(item, key_span, "packages"),
+				"dependency-groups" => self.validate_dependency_groups(item, key_span),
+				"scripts" => self.validate_scripts(item, key_span, "scripts"),
+				"tool" => {}
+				_ => self.push_unknown_key(root, key, "", &KNOWN_TOP_LEVEL_KEYS),
+			}
+		}
+
+		if !root.contains_key("python") {
+			self.push(None, "missing key `python`, ex. `python = \"3.12.4\"`");
+		}
+		if !root.contains_key("packages") {
+			self.push(None, "missing table `[packages]`, it can be empty");
+		}
+	}
+
+	fn validate_pyproject_root(&mut self, root: &dyn TableLike) {
+		let mut requires_python = None;
+		match root.get("project").map(|item| (item, item.as_table_like())) {
+			Some((_, Some(project))) => {
+				for (key, value) in project.iter() {
+					let key_span = get_key_span(project, key);
+					match key {
+						"dependencies" => self.validate_project_dependencies(value, key_span, "project.dependencies"),
+						"optional-dependencies" => match value.as_table_like() {
+							Some(groups) => {
+								for (group, dependencies) in groups.iter() {
+									let path = format!("project.optional-dependencies.{}", group);
+									if group == MAIN_GROUP {
+										let message = format!(
+											"`{}`: {} is the name of the `dependencies` list, it can't be used as a group",
+											path, MAIN_GROUP
+										);
+										self.push(get_key_span(groups, group), &message);
+									}
+									self.validate_project_dependencies(dependencies, get_key_span(groups, group), &path);
+								}
+							}
+							None => self.push_type_error(value, key_span, "project.optional-dependencies", "a table of dependency lists"),
+						},
+						"requires-python" => match value.as_str().map(|specifier| (specifier, to_version_requirement(specifier))) {
+							Some((specifier, Ok(version))) => requires_python = VersionReq::parse(&version).ok().map(|version| (version, specifier)),
+							Some((_, Err(e))) => self.push(value.span(), &format!("`project.requires-python`: {}", e)),
+							None => self.push_type_error(value, key_span, "project.requires-python", "a version specifier, ex. \">=3.11\""),
+						},
+						_ => {} // The other metadata of the project isn't used by pen
+					}
+				}
+			}
+			Some((item, None)) => self.push_type_error(item, get_key_span(root, "project"), "project", "a table"),
+			None => {}
+		}
+
+		let pen_tool = match root.get("tool").and_then(|tool| tool.as_table_like()).and_then(|tool| tool.get("pen")) {
+			Some(pen_tool) => pen_tool,
+			None => return self.push(None, "missing table `[tool.pen]`, run `pen init <python version>`"),
+		};
+		let pen_tool = match pen_tool.as_table_like() {
+			Some(table) => table,
+			None => return self.push_type_error(pen_tool, None, "tool.pen", "a table"),
+		};
+		for (key, value) in pen_tool.iter() {
+			let key_span = get_key_span(pen_tool, key);
+			let path = format!("tool.pen.{}", key);
+			match key {
+				"python" => {
+					let python = self.validate_python(value, key_span, &path);
+					if let (Some(python), Some((version, specifier))) = (python, &requires_python) {
+						if !version.matches(&python.version) {
+							let message = format!("`{}`: Python {} doesn't satisfy requires-python \"{}\"", path, python, specifier);
+							self.push(value.span(), &message);
+						}
+					}
+				}
+				"env-files" => self.validate_env_files(value, key_span, &path),
+				"scripts" => self.validate_scripts(value, key_span, &path),
+				"packages" | "dependency-groups" => {
+					let message = format!("`{}`: packages go in `project.dependencies` and `project.optional-dependencies`", path);
+					self.push(key_span, &message);
+				}
+				_ => self.push_unknown_key(pen_tool, key, "tool.pen", &PEN_TOOL_KEYS),
+			}
+		}
+		if !pen_tool.contains_key("python") {
+			self.push(None, "missing key `tool.pen.python`, ex. `python = \"3.12.4\"`");
+		}
+	}
+
+	fn validate_project_dependencies(&mut self, item: &Item, span: Option<Range<usize>>, path: &str) {
+		let dependencies = match item.as_array() {
+			Some(dependencies) => dependencies,
+			None => return self.push_type_error(item, span, path, "a list of dependencies, ex. [\"requests>=2.32\"]"),
+		};
+		for dependency in dependencies.iter() {
+			match dependency.as_str().map(parse_project_dependency) {
+				Some(Ok(_)) => {}
+				Some(Err(e)) => {
+					let message = format!("`{}`: invalid dependency {} ({})", path, dependency.to_string().trim(), e);
+					self.push(dependency.span(), &message);
+				}
+				None => self.push(
+					dependency.span(),
+					&format!("`{}`: expected a dependency, found {}", path, dependency.type_name()),
+				),
+			}
+		}
+	}
+
+	/// Checks a Python version, returning it when it is valid.
+	fn validate_python(&mut self, item: &Item, span: Option<Range<usize>>, path: &str) -> Option<PythonSpec> {
+		let python = match item.as_str() {
+			Some(python) => python,
+			None => {
+				self.push_type_error(item, span, path, "a Python version, ex. \"3.12.4\" or \"pypy@3.10\"");
+				return None;
+			}
+		};
+		return match PythonSpec::parse(python) {
+			Ok(python) => Some(python),
+			Err(e) => {
+				self.push(item.span(), &format!("`{}`: {}, expected ex. \"3.12.4\" or \"pypy@3.10\"", path, e));
+				None
+			}
+		};
+	}
+
+	fn validate_env_files(&mut self, item: &Item, span: Option<Range<usize>>, path: &str) {
+		match item.as_array() {
+			Some(files) => {
+				for file in files.iter().filter(|file| !file.is_str()) {
+					self.push(file.span(), &format!("`{}`: expected a path, found {}", path, file.type_name()));
+				}
+			}
+			None => self.push_type_error(item, span, path, "a list of paths, ex. [\".env\"]"),
+		}
+	}
+
+	fn validate_scripts(&mut self, item: &Item, span: Option<Range<usize>>, path: &str) {
+		match item.as_table_like() {
+			Some(scripts) => {
+				for (name, script) in scripts.iter() {
+					self.validate_script(script, get_key_span(scripts, name), &format!("{}.{}", path, name));
+				}
+			}
+			None => self.push_type_error(item, span, path, "a table of scripts, ex. [scripts]"),
+		}
+	}
+
+	fn validate_packages(&mut self, item: &Item, span: Option<Range<usize>>, path: &str) {
+		let packages = match item.as_table_like() {
+			Some(packages) => packages,
+			None => return self.push_type_error(item, span, path, "a table of packages with their version requirement"),
+		};
+		for (name, version) in packages.iter() {
+			match version.as_str() {
+				Some(requirement) => {
+					if let Err(e) = VersionReq::parse(requirement) {
+						let message = format!(
+							"`{}.{}`: invalid version requirement \"{}\" ({}), expected {}",
+							path, name, requirement, e, VERSION_REQUIREMENT_EXAMPLES
+						);
+						self.push(version.span(), &message);
+					}
+				}
+				None => {
+					let expected = format!("a version requirement, {}", VERSION_REQUIREMENT_EXAMPLES);
+					self.push_type_error(version, get_key_span(packages, name), &format!("{}.{}", path, name), &expected);
+				}
+			}
+		}
+	}
+
+	fn validate_dependency_groups(&mut self, item: &Item, span: Option<Range<usize>>) {
+		let groups = match item.as_table_like() {
+			Some(groups) => groups,
+			None => {
+				return self.push_type_error(
+					item,
+					span,
+					"dependency-groups",
+					"a table of dependency groups, ex. [dependency-groups.dev]",
+				)
+			}
+		};
+		for (group, packages) in groups.iter() {
+			let group_span = get_key_span(groups, group);
+			if group == MAIN_GROUP {
+				let message = format!(
+					"`dependency-groups.{}`: {} is the name of the [packages] table, it can't be used as a dependency group",
+					group, MAIN_GROUP
+				);
+				self.push(group_span.clone(), &message);
+			}
+			self.validate_packages(packages, group_span, &format!("dependency-groups.{}", group));
+		}
+	}
+
+	fn validate_script(&mut self, item: &Item, span: Option<Range<usize>>, path: &str) {
+		if item.is_str() || item.is_array() {
+			return self.validate_script_command(item, span, path);
+		}
+		let script = match item.as_table_like() {
+			Some(script) => script,
+			None => return self.push_type_error(item, span, path, "a command, a list of steps or a table with `cmd`"),
+		};
+
+		for (key, value) in script.iter() {
+			let key_span = get_key_span(script, key);
+			let key_path = format!("{}.{}", path, key);
+			match key {
+				"cmd" => self.validate_script_command(value, key_span, &key_path),
+				"help" if !value.is_str() => self.push_type_error(value, key_span, &key_path, "a string"),
+				"help" => {}
+				"env" => match value.as_table_like() {
+					Some(env) => {
+						for (name, env_value) in env.iter().filter(|(_, env_value)| !env_value.is_str()) {
+							self.push_type_error(env_value, get_key_span(env, name), &format!("{}.{}", key_path, name), "a string");
+						}
+					}
+					None => self.push_type_error(value, key_span, &key_path, "a table of environment variables"),
+				},
+				_ => self.push_unknown_key(script, key, path, &SCRIPT_KEYS),
+			}
+		}
+		if !script.contains_key("cmd") {
+			self.push(item.span().or(span), &format!("`{}`: missing key `cmd`", path));
+		}
+	}
+
+	fn validate_script_command(&mut self, item: &Item, span: Option<Range<usize>>, path: &str) {
+		if item.is_str() {
+			return;
+		}
+		match item.as_array() {
+			Some(steps) => {
+				for step in steps.iter().filter(|step| !step.is_str()) {
+					self.push(step.span(), &format!("`{}`: expected a command, found {}", path, step.type_name()));
+				}
+			}
+			None => self.push_type_error(item, span, path, "a command or a list of steps"),
+		}
+	}
+}
+
+fn get_key_span(table: &dyn TableLike, key: &str) -> Option<Range<usize>> {
+	return table.key(key).and_then(|key| key.span());
+}
+
+/// Finds the known key that `key` is most likely a misspelling of, ex. `packages` for `pakages` or `env-files` for `env_files`.
+fn get_closest_key<'a>(key: &str, known_keys: &[&'a str]) -> Option<&'a str> {
+	let key = key.to_ascii_lowercase().replace('_', "-");
+	return known_keys
+		.iter()
+		.map(|known_key| (get_edit_distance(&key, known_key), *known_key))
+		.filter(|(distance, known_key)| *distance <= 2 && *distance < known_key.len() / 2 + 1)
+		.min_by_key(|(distance, _)| *distance)
+		.map(|(_, known_key)| known_key);
+}
+
+/// Computes the Levenshtein distance between two strings.
+fn get_edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+	for (i, a_char) in a.chars().enumerate() {
+		let mut current = vec![i + 1];
+		for (j, b_char) in b.iter().enumerate() {
+			let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+			current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+		}
+		previous = current;
+	}
+	return previous[b.len()];
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn valid_config() {
+		let contents = r#"python = "3.12.4"
+env-files = [".env"]
+
+[packages]
+requests = "^2.32"
+
+[dependency-groups.dev]
+pytest = ">=8, <9"
+
+[scripts]
+test = "pytest"
+lint = { cmd = ["ruff check", "ruff format --check"], help = "Checks the code", env = { RUFF_CACHE_DIR = ".cache" } }
+
+[tool.other]
+anything = 1
+"#;
+		assert_eq!(validate_config(contents, "pen.toml"), Vec::<String>::new());
+	}
+
+	#[test]
+	fn problems_have_their_position() {
+		let contents = "python = \"3.12.4\"\n\n[packages]\nrequests = \"^2.32\"\nrich = \"not a version\"\nclick = 8\n";
+		let problems = validate_config(contents, "pen.toml");
+		assert_eq!(problems.len(), 2);
+		assert!(problems[0].starts_with("pen.toml:5:8: `packages.rich`: invalid version requirement \"not a version\""));
+		assert_eq!(
+			problems[1],
+			"pen.toml:6:9: `packages.click`: expected a version requirement, ex. \"^2.32\", \">=1.4, <2\" or \"*\", found integer"
+		);
+	}
+
+	#[test]
+	fn unknown_and_missing_keys() {
+		let problems = validate_config("pyhton = \"3.12.4\"\n\n[scripts.test]\ncommand = \"pytest\"\n", "pen.toml");
+		assert_eq!(
+			problems,
+			vec![
+				"pen.toml:1:1: unknown key `pyhton`, did you mean `python`?",
+				"pen.toml:3:1: `scripts.test`: missing key `cmd`",
+				"pen.toml:4:1: `scripts.test`: unknown key `command`, expected one of `cmd`, `env`, `help`",
+				"pen.toml: missing key `python`, ex. `python = \"3.12.4\"`",
+				"pen.toml: missing table `[packages]`, it can be empty",
+			]
+		);
+	}
+
+	#[test]
+	fn main_is_not_a_dependency_group() {
+		let problems = validate_config("python = \"3.12.4\"\n[packages]\n[dependency-groups.main]\n", "pen.toml");
+		assert_eq!(problems.len(), 1);
+		assert!(problems[0].starts_with("pen.toml:3:20: `dependency-groups.main`: main is the name of the [packages] table"));
+	}
+
+	#[test]
+	fn invalid_toml() {
+		let problems = validate_config("python = \n", "pen.toml");
+		assert_eq!(problems.len(), 1);
+		assert!(problems[0].starts_with("pen.toml:1:"));
+	}
+}
@@ -0,0 +1,285 @@
+use semver::Version;
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::constants::{ENV_DIR_NAME, LOCKFILE_NAME};
+use crate::utils::{
+	self, error, evaluate_marker, get_package_requires_dist, get_requirements, guard, is_lockfile_up_to_date, normalize_package_name,
+	read_installed_distributions, AnyError, Config, Lockfile, Package, PythonSpec,
+};
+
+/// The locked packages of a project and the packages they depend on, keyed by normalized name.
+pub struct DependencyGraph {
+	/// The packages listed in `pen.toml`, with the groups listing them.
+	pub roots: Vec<(String, Vec<String>)>,
+	pub nodes: BTreeMap<String, DependencyNode>,
+}
+
+pub struct DependencyNode {
+	pub name: String,
+	/// The locked version.
+	pub version: Version,
+	/// Whether the package is in the .venv of the project, the packages of groups that weren't synced aren't.
+	pub is_installed: bool,
+	pub dependencies: Vec<Dependency>,
+}
+
+/// A dependency declared by a package in its metadata (`Requires-Dist`).
+#[derive(Clone)]
+pub struct Dependency {
+	/// The normalized name of the dependency, a key of `DependencyGraph::nodes`.
+	pub name: String,
+	/// The version specifier, ex. `>=1.21.1,<3`, empty when any version works.
+	pub specifier: String,
+	/// The environment marker, ex. `extra == "socks"` or `sys_platform == "win32"`.
+	pub marker: Option<String>,
+}
+
+impl DependencyGraph {
+	/// Lists the packages depending on `name`.
+	pub fn get_dependents(&self, name: &str) -> Vec<&String> {
+		return self
+			.nodes
+			.iter()
+			.filter(|(_, node)| node.dependencies.iter().any(|dependency| dependency.name == name))
+			.map(|(node_name, _)| node_name)
+			.collect();
+	}
+}
+
+/// Builds the dependency graph of a project from its lockfile.
+///
+/// # Arguments
+/// - `config`: The project config.
+/// - `lockfile`: The lockfile of the project.
+/// - `project_path`: The project root, whose .venv tells which packages are installed.
+///
+/// # Output
+/// - The graph of every locked package, of every group. The version specifiers of the dependencies come from the metadata
+///   of the packages in the cache, they are empty for the packages that aren't.
+///
+/// # Termination
+/// - This function returns an error if the lockfile is out of date with the config, or if the metadata of a cached package
+///   or the .venv can't be read.
+pub fn get_dependency_graph(config: &Config, lockfile: &Lockfile, project_path: &Path) -> Result<DependencyGraph, AnyError> {
+	if !is_lockfile_up_to_date(config, lockfile)? {
+		return error!("{} is out of date with the config, run `pen sync` first.", LOCKFILE_NAME);
+	}
+
+	let site_packages_path = project_path
+		.join(ENV_DIR_NAME)
+		.join("lib")
+		.join(config.python.lib_dir_name())
+		.join("site-packages");
+	let installed: Vec<String> = match site_packages_path.exists() {
+		true => read_installed_distributions(&site_packages_path)?
+			.iter()
+			.map(|distribution| normalize_package_name(&distribution.name))
+			.collect(),
+		false => Vec::new(),
+	};
+
+	let mut nodes = BTreeMap::new();
+	for locked in &lockfile.packages {
+		let package = locked.to_package();
+		let declared = match utils::get_package_path(&package).exists() {
+			true => filter_dependencies(&package, read_package_dependencies(&package)?, &config.python)?,
+			false => Vec::new(),
+		};
+		// The locked dependencies are the ones that apply, the metadata only adds their specifier
+		let dependencies = locked
+			.dependencies
+			.iter()
+			.map(|name| Dependency {
+				name: name.clone(),
+				specifier: declared
+					.iter()
+					.filter(|dependency| dependency.name == *name && !dependency.specifier.is_empty())
+					.map(|dependency| dependency.specifier.as_str())
+					.collect::<Vec<&str>>()
+					.join(","),
+				marker: None,
+			})
+			.collect();
+
+		let name = normalize_package_name(&locked.name);
+		nodes.insert(
+			name.clone(),
+			DependencyNode {
+				name: locked.name.clone(),
+				version: locked.version.clone(),
+				is_installed: installed.contains(&name),
+				dependencies,
+			},
+		);
+	}
+
+	let roots = get_requirements(config)?
+		.into_iter()
+		.map(|requirement| (normalize_package_name(&requirement.name), requirement.groups))
+		.collect();
+	return Ok(DependencyGraph { roots, nodes });
+}
+
+/// Gets the dependencies of a package that apply to the Python of a project on this machine.
+///
+/// # Output
+/// - The dependencies declared in the `METADATA` of the package when it is in the cache, or else on PyPI.
+///   Dependencies whose environment marker doesn't match, ex. the ones only needed for an extra, are left out.
+///
+/// # Termination
+/// - This function returns an error if the metadata can't be read, or if a marker can't be evaluated, ex. on `platform_release`.
+pub fn get_package_dependencies(package: &Package, python: &PythonSpec) -> Result<Vec<Dependency>, AnyError> {
+	let dependencies = match utils::get_package_path(package).exists() {
+		true => read_package_dependencies(package)?,
+		false => get_package_requires_dist(package)?
+			.iter()
+			.filter_map(|value| parse_requires_dist(value))
+			.collect(),
+	};
+	return filter_dependencies(package, dependencies, python);
+}
+
+/// Gets the dependencies of an installed distribution that apply to the Python of a project on this machine.
+///
+/// # Arguments
+/// - `package`: The distribution as a package, for error messages.
+/// - `dist_info_path`: The `.dist-info` directory of the distribution.
+///
+/// # Termination
+/// - This function returns an error if its `METADATA` can't be read, or if a marker can't be evaluated.
+pub fn get_installed_dependencies(package: &Package, dist_info_path: &Path, python: &PythonSpec) -> Result<Vec<Dependency>, AnyError> {
+	let dependencies = read_metadata_dependencies(&dist_info_path.join("METADATA"))?;
+	return filter_dependencies(package, dependencies, python);
+}
+
+/// Keeps the dependencies of a package whose environment marker matches `python` on this machine.
+fn filter_dependencies(package: &Package, dependencies: Vec<Dependency>, python: &PythonSpec) -> Result<Vec<Dependency>, AnyError> {
+	let mut applying = Vec::new();
+	for dependency in dependencies {
+		let applies = match &dependency.marker {
+			Some(marker) => evaluate_marker(marker, python),
+			None => Ok(true),
+		};
+		match applies {
+			Ok(true) => applying.push(dependency),
+			Ok(false) => {}
+			Err(e) => return error!("{} {} depends on {}: {}", package.name, package.version, dependency.name, e),
+		}
+	}
+	return Ok(applying);
+}
+
+/// Reads the dependencies declared in the `METADATA` of a package in the cache.
+fn read_package_dependencies(package: &Package) -> Result<Vec<Dependency>, AnyError> {
+	let package_path = utils::get_package_path(package);
+	let entries = guard!(fs::read_dir(&package_path), "Failed to read {}", package_path.display());
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		if !entry.file_name().to_string_lossy().ends_with(".dist-info") {
+			continue;
+		}
+
+		return read_metadata_dependencies(&entry.path().join("METADATA"));
+	}
+	return error!("{} has no .dist-info directory.", package_path.display());
+}
+
+/// Reads the `Requires-Dist` headers of a `METADATA` file.
+fn read_metadata_dependencies(metadata_path: &Path) -> Result<Vec<Dependency>, AnyError> {
+	let metadata = guard!(fs::read_to_string(metadata_path), "Couldn't read {}", metadata_path.display());
+	let dependencies = metadata
+		.lines()
+		.take_while(|line| !line.is_empty()) // The headers end at the first blank line, the description follows
+		.filter_map(|line| line.strip_prefix("Requires-Dist:"))
+		.filter_map(parse_requires_dist)
+		.collect();
+	return Ok(dependencies);
+}
+
+/// Parses a `Requires-Dist` value, ex. `urllib3 (<3,>=1.21.1)` or `PySocks!=1.5.7,>=1.5.6; extra == "socks"`.
+///
+/// # Output
+/// - The dependency, or `None` for a value without a package name.
+fn parse_requires_dist(value: &str) -> Option<Dependency> {
+	let (requirement, marker) = match value.split_once(';') {
+		Some((requirement, marker)) => (requirement, Some(String::from(marker.trim()))),
+		None => (value, None),
+	};
+
+	let requirement = requirement.trim();
+	let name_end = requirement
+		.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+		.unwrap_or(requirement.len());
+	let name = &requirement[..name_end];
+	if name.is_empty() {
+		return None;
+	}
+
+	let mut specifier = requirement[name_end..].trim();
+	if specifier.starts_with('[') {
+		// Extras of the dependency, ex. requests[socks]
+		specifier = specifier.split_once(']').map(|(_, rest)| rest.trim()).unwrap_or("");
+	}
+	let specifier = specifier.trim_start_matches('(').trim_end_matches(')').trim();
+
+	return Some(Dependency {
+		name: normalize_package_name(name),
+		specifier: String::from(specifier),
+		marker,
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(value: &str) -> (String, String, Option<String>) {
+		let dependency = parse_requires_dist(value).unwrap();
+		return (dependency.name, dependency.specifier, dependency.marker);
+	}
+
+	#[test]
+	fn requires_dist_specifiers() {
+		assert_eq!(
+			parse("urllib3 (<3,>=1.21.1)"),
+			(String::from("urllib3"), String::from("<3,>=1.21.1"), None)
+		);
+		assert_eq!(parse("idna<4,>=2.5"), (String::from("idna"), String::from("<4,>=2.5"), None));
+		assert_eq!(parse("certifi"), (String::from("certifi"), String::new(), None));
+		assert_eq!(
+			parse("Typing_Extensions>=4"),
+			(String::from("typing-extensions"), String::from(">=4"), None)
+		);
+	}
+
+	#[test]
+	fn requires_dist_markers_and_extras() {
+		assert_eq!(
+			parse("PySocks!=1.5.7,>=1.5.6; extra == \"socks\""),
+			(
+				String::from("pysocks"),
+				String::from("!=1.5.7,>=1.5.6"),
+				Some(String::from("extra == \"socks\""))
+			)
+		);
+		assert_eq!(
+			parse("requests[socks,use-chardet]>=2"),
+			(String::from("requests"), String::from(">=2"), None)
+		);
+		assert_eq!(
+			parse("colorama ; platform_system == 'Windows'"),
+			(
+				String::from("colorama"),
+				String::new(),
+				Some(String::from("platform_system == 'Windows'"))
+			)
+		);
+	}
+
+	#[test]
+	fn requires_dist_without_a_name() {
+		assert!(parse_requires_dist("").is_none());
+		assert!(parse_requires_dist(">=1.0").is_none());
+		assert!(parse_requires_dist("; python_version < '3.8'").is_none());
+	}
+}
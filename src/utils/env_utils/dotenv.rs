@@ -0,0 +1,303 @@
+use std::{
+	env, fs,
+	path::{Path, PathBuf},
+};
+
+use crate::utils::{error, guard, AnyError};
+
+/// Reads the variables of the dotenv files of a project, those of `pen.toml` first, then those given on the command line.
+///
+/// # Arguments
+/// - `project_path`: The project root, the files of `pen.toml` are relative to it.
+/// - `config_files`: The `env-files` of `pen.toml`.
+/// - `cli_files`: The files given with `--env-file`, relative to the current directory.
+///
+/// # Output
+/// - The variables in the order they are defined. A variable defined several times keeps the last value.
+///
+/// # Termination
+/// - This function returns an error if a file can't be read, or with the line number of the first line that can't be parsed.
+///
+/// # Guarantees
+/// - `VIRTUAL_ENV` is never among the variables, it is always the .venv of the project. `PATH` can be set, see `get_activated_path`.
+pub fn read_env_files(project_path: &Path, config_files: &[PathBuf], cli_files: &[PathBuf]) -> Result<Vec<(String, String)>, AnyError> {
+	let mut vars: Vec<(String, String)> = Vec::new();
+	let files = config_files.iter().map(|file| project_path.join(file)).chain(cli_files.iter().cloned());
+	for file in files {
+		let contents = guard!(fs::read_to_string(&file), "Couldn't read the env file {}", file.display());
+		if let Err(e) = parse_env_file(&contents, &mut vars) {
+			return error!("{}:{}", file.display(), e);
+		}
+	}
+	return Ok(vars);
+}
+
+/// Gets the `PATH` of a command run inside a virtual environment: its `bin` directory first, then the `PATH` set by the
+/// dotenv files (ex. `PATH=$PATH:/opt/tools`), or else the `PATH` of pen.
+pub fn get_activated_path(env_path: &Path, env_vars: &[(String, String)]) -> String {
+	let path = match env_vars.iter().rev().find(|(key, _)| key == "PATH") {
+		Some((_, path)) => path.clone(),
+		None => env::var("PATH").unwrap_or_default(),
+	};
+	return format!("{}:{}", env_path.join("bin").to_string_lossy(), path);
+}
+
+/// Parses a dotenv file, adding its variables to `vars`, which also holds the variables `${VAR}` can refer to.
+///
+/// Supported syntax:
+/// - `KEY=value`, `export KEY=value`, blank lines and `#` comments.
+/// - Unquoted values end at ` #`, double quoted values support `\n`, `\t`, `\"`, `\\` and `\$` escapes,
+///   single quoted values are taken literally. Quoted values can span several lines.
+/// - `$VAR`, `${VAR}` and `${VAR:-default}` in unquoted and double quoted values, looked up in the variables defined before,
+///   then in the environment of pen. Undefined variables are empty.
+///
+/// # Termination
+/// - This function returns an error starting with the line number of the line that can't be parsed, ex. `3: Missing '='`,
+///   or that sets `VIRTUAL_ENV`.
+fn parse_env_file(contents: &str, vars: &mut Vec<(String, String)>) -> Result<(), String> {
+	let mut lines = contents.lines().enumerate();
+
+	while let Some((index, line)) = lines.next() {
+		let line_number = index + 1;
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let line = match line.strip_prefix("export") {
+			Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+			_ => line,
+		};
+		let (key, value) = match line.split_once('=') {
+			Some((key, value)) => (key.trim(), value.trim_start()),
+			None => return Err(format!("{}: Missing '=' in {}", line_number, line)),
+		};
+		if !is_valid_key(key) {
+			return Err(format!(
+				"{}: Invalid variable name '{}', names contain only letters, digits and '_' and don't start with a digit",
+				line_number, key
+			));
+		}
+		if key == "VIRTUAL_ENV" {
+			return Err(format!(
+				"{}: VIRTUAL_ENV is always the .venv of the project, it can't be set",
+				line_number
+			));
+		}
+
+		let value = match value.chars().next() {
+			Some(quote @ ('"' | '\'')) => {
+				// The value goes on until the closing quote, which can be on one of the next lines
+				let mut raw = String::from(&value[1..]);
+				let end = loop {
+					if let Some(end) = find_closing_quote(&raw, quote) {
+						break end;
+					}
+					match lines.next() {
+						Some((_, next_line)) => {
+							raw.push('\n');
+							raw.push_str(next_line);
+						}
+						None => return Err(format!("{}: Missing closing {} for the value of {}", line_number, quote, key)),
+					}
+				};
+				let rest = raw[end + 1..].trim();
+				if !rest.is_empty() && !rest.starts_with('#') {
+					return Err(format!("{}: Unexpected '{}' after the closing {} of {}", line_number, rest, quote, key));
+				}
+				match quote {
+					'"' => expand(&unescape(&raw[..end]), vars).map_err(|e| format!("{}: {}", line_number, e))?,
+					_ => String::from(&raw[..end]),
+				}
+			}
+			_ => {
+				let value = match value.find(" #") {
+					Some(comment) => &value[..comment],
+					None => value,
+				};
+				expand(value.trim_end(), vars).map_err(|e| format!("{}: {}", line_number, e))?
+			}
+		};
+
+		vars.retain(|(existing, _)| existing != key);
+		vars.push((String::from(key), value));
+	}
+	return Ok(());
+}
+
+fn is_valid_key(key: &str) -> bool {
+	let mut chars = key.chars();
+	return match chars.next() {
+		Some(first) => (first.is_ascii_alphabetic() || first == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+		None => false,
+	};
+}
+
+/// Finds the quote closing a value, skipping the ones escaped with `\` in double quoted values.
+fn find_closing_quote(raw: &str, quote: char) -> Option<usize> {
+	let mut escaped = false;
+	for (i, c) in raw.char_indices() {
+		match c {
+			'\\' if quote == '"' && !escaped => escaped = true,
+			c if c == quote && !escaped => return Some(i),
+			_ => escaped = false,
+		}
+	}
+	return None;
+}
+
+/// Replaces the escapes of a double quoted value. `\$` and `\\` are kept for `expand`, so that it leaves them alone.
+fn unescape(raw: &str) -> String {
+	let mut value = String::new();
+	let mut chars = raw.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			value.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => value.push('\n'),
+			Some('t') => value.push('\t'),
+			Some('r') => value.push('\r'),
+			Some('$') => value.push_str("\\$"),
+			Some('\\') => value.push_str("\\\\"),
+			Some(other) => value.push(other),
+			None => value.push('\\'),
+		}
+	}
+	return value;
+}
+
+/// Replaces `$VAR`, `${VAR}` and `${VAR:-default}` with the value of the variable, and `\$` and `\\` with `$` and `\`.
+fn expand(raw: &str, vars: &Vec<(String, String)>) -> Result<String, String> {
+	let mut value = String::new();
+	let mut rest = raw;
+
+	while let Some(start) = rest.find(['$', '\\']) {
+		value.push_str(&rest[..start]);
+		rest = &rest[start..];
+
+		if let Some(after) = rest.strip_prefix("\\$") {
+			value.push('$');
+			rest = after;
+			continue;
+		}
+		if let Some(after) = rest.strip_prefix("\\\\") {
+			value.push('\\');
+			rest = after;
+			continue;
+		}
+		if rest.starts_with('\\') {
+			value.push('\\');
+			rest = &rest[1..];
+			continue;
+		}
+
+		rest = &rest[1..];
+		if let Some(braced) = rest.strip_prefix('{') {
+			let end = match braced.find('}') {
+				Some(end) => end,
+				None => return Err(format!("Missing '}}' in ${{{}", braced)),
+			};
+			let (name, default) = match braced[..end].split_once(":-") {
+				Some((name, default)) => (name, Some(default)),
+				None => (&braced[..end], None),
+			};
+			if !is_valid_key(name) {
+				return Err(format!("Invalid variable name '{}' in ${{{}}}", name, &braced[..end]));
+			}
+			match (lookup(name, vars), default) {
+				(Some(found), _) if !found.is_empty() => value.push_str(&found),
+				(_, Some(default)) => value.push_str(&expand(default, vars)?),
+				(found, None) => value.push_str(&found.unwrap_or_default()),
+			}
+			rest = &braced[end + 1..];
+			continue;
+		}
+
+		let name_len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+		if name_len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+			value.push('$');
+			continue;
+		}
+		value.push_str(&lookup(&rest[..name_len], vars).unwrap_or_default());
+		rest = &rest[name_len..];
+	}
+	value.push_str(rest);
+	return Ok(value);
+}
+
+fn lookup(name: &str, vars: &[(String, String)]) -> Option<String> {
+	if let Some((_, value)) = vars.iter().rev().find(|(key, _)| key == name) {
+		return Some(value.clone());
+	}
+	return env::var(name).ok();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(contents: &str) -> Result<Vec<(String, String)>, String> {
+		let mut vars = Vec::new();
+		parse_env_file(contents, &mut vars)?;
+		return Ok(vars);
+	}
+
+	fn pair(key: &str, value: &str) -> (String, String) {
+		return (String::from(key), String::from(value));
+	}
+
+	#[test]
+	fn plain_values() {
+		let vars = parse("# comment\n\nA=1\nexport B = two words  # trailing\nexported=3\nA=4\n").unwrap();
+		assert_eq!(vars, vec![pair("B", "two words"), pair("exported", "3"), pair("A", "4")]);
+	}
+
+	#[test]
+	fn quoted_values() {
+		let vars = parse("A='$B \\n # kept'\nB=\"tab\\there \\\"q\\\" \\$B\" # comment\nC=\"first\nsecond\"\n").unwrap();
+		assert_eq!(
+			vars,
+			vec![pair("A", "$B \\n # kept"), pair("B", "tab\there \"q\" $B"), pair("C", "first\nsecond")]
+		);
+	}
+
+	#[test]
+	fn expansion() {
+		let vars = parse("HOST=localhost\nURL=http://$HOST:${PORT:-8000}/${HOST}\nEMPTY=${PEN_TEST_UNDEFINED_VARIABLE}\nCOST=5$\n").unwrap();
+		assert_eq!(
+			vars,
+			vec![
+				pair("HOST", "localhost"),
+				pair("URL", "http://localhost:8000/localhost"),
+				pair("EMPTY", ""),
+				pair("COST", "5$"),
+			]
+		);
+	}
+
+	#[test]
+	fn errors_have_line_numbers() {
+		assert_eq!(parse("A=1\nB\n").unwrap_err(), "2: Missing '=' in B");
+		assert!(parse("1A=1").unwrap_err().starts_with("1: Invalid variable name '1A'"));
+		assert!(parse("A=\"open\nB=1").unwrap_err().starts_with("1: Missing closing \""));
+		assert!(parse("A='x' y").unwrap_err().starts_with("1: Unexpected 'y'"));
+		assert!(parse("A=${B").unwrap_err().starts_with("1: Missing '}'"));
+	}
+
+	#[test]
+	fn virtual_env_is_reserved() {
+		assert!(parse("A=1\nVIRTUAL_ENV=/tmp/other")
+			.unwrap_err()
+			.starts_with("2: VIRTUAL_ENV is always the .venv"));
+	}
+
+	#[test]
+	fn path_of_env_files_is_kept_after_bin() {
+		let env_path = PathBuf::from("/project/.venv");
+		let vars = vec![pair("PATH", "/opt/tools:/usr/bin")];
+		assert_eq!(get_activated_path(&env_path, &vars), "/project/.venv/bin:/opt/tools:/usr/bin");
+		assert!(get_activated_path(&env_path, &Vec::new()).starts_with("/project/.venv/bin:"));
+	}
+}
@@ -0,0 +1,575 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::utils::{
+	error, evaluate_marker, guard, normalize_package_name, parse_project_dependency, to_version_requirement, AnyError, PythonSpec, DEV_GROUP,
+};
+
+/// A requirement read from the file of another tool.
+pub struct ImportedRequirement {
+	pub name: String,
+	/// A pen version requirement, ex. `>=2.32.0, <3.0.0`.
+	pub version: String,
+	/// The dependency group, `main` for the packages always installed.
+	pub group: String,
+}
+
+/// The requirements read from the file of another tool, and what couldn't be translated.
+pub struct Import {
+	pub requirements: Vec<ImportedRequirement>,
+	/// The entries left out, with where they come from and why, ex. `requirements.txt:4: -e . (editable installs are not supported)`.
+	pub skipped: Vec<String>,
+	/// What was imported but lost in the translation, ex. hashes or extras.
+	pub notes: Vec<String>,
+	/// The constraints of `-c` files, applied to the requirements of the same packages once everything is read.
+	constraints: Vec<ImportedRequirement>,
+	/// The requirement files being read, to stop `-r` cycles.
+	reading: Vec<PathBuf>,
+	/// The Python of the project, environment markers are evaluated for it.
+	python: PythonSpec,
+}
+
+/// Reads the dependencies of a `requirements.txt`, a `Pipfile`, a `Pipfile.lock` or the `pyproject.toml` of a Poetry project.
+///
+/// # Arguments
+/// - `path`: The file to import, its format is guessed from its name. Files not named `Pipfile`, `Pipfile.lock` or `*.toml`
+///   are read as requirement files.
+/// - `group`: The group of the main dependencies of the file. The development dependencies go to the `dev` group,
+///   and Poetry groups and Pipfile categories to groups of the same name.
+/// - `python`: The Python of the project, environment markers are evaluated for it.
+///
+/// # Output
+/// - The requirements, in the order of the file. A package listed several times gets a single requirement combining them.
+///
+/// # Termination
+/// - This function returns an error if the file can't be read or parsed. Entries that can't be translated are only reported
+///   in `skipped`.
+///
+/// # Limitations
+/// - Only environment markers on the Python and the platform are understood, ex. not `platform_release`.
+pub fn import_requirements(path: &PathBuf, group: &str, python: &PythonSpec) -> Result<Import, AnyError> {
+	let mut import = Import {
+		requirements: Vec::new(),
+		skipped: Vec::new(),
+		notes: Vec::new(),
+		constraints: Vec::new(),
+		reading: Vec::new(),
+		python: python.clone(),
+	};
+
+	let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+	match file_name.as_str() {
+		"Pipfile" => import.read_pipfile(path, group)?,
+		"Pipfile.lock" => import.read_pipfile_lock(path, group)?,
+		name if name.ends_with(".toml") => import.read_poetry(path, group)?,
+		_ => import.read_requirements_txt(path, group, false)?,
+	}
+
+	let constraints = std::mem::take(&mut import.constraints);
+	for constraint in constraints {
+		let name = normalize_package_name(&constraint.name);
+		for requirement in import.requirements.iter_mut().filter(|r| normalize_package_name(&r.name) == name) {
+			requirement.version = combine_versions(&requirement.version, &constraint.version);
+		}
+	}
+	return Ok(import);
+}
+
+impl Import {
+	/// Adds a requirement, unless its environment marker doesn't match the Python of the project.
+	fn add(&mut self, location: &str, name: &str, version: String, extras: &str, marker: Option<&str>, group: &str) {
+		if let Some(marker) = marker {
+			match evaluate_marker(marker, &self.python) {
+				Ok(true) => {}
+				Ok(false) => {
+					self.notes
+						.push(format!("{}: {} left out, it is only needed when {}", location, name, marker));
+					return;
+				}
+				Err(e) => {
+					self.skipped.push(format!("{}: {} ({})", location, name, e));
+					return;
+				}
+			}
+		}
+		if !extras.is_empty() {
+			self.notes
+				.push(format!("{}: the extras {} of {} are not installed by pen", location, extras, name));
+		}
+
+		let normalized_name = normalize_package_name(name);
+		let existing = self
+			.requirements
+			.iter_mut()
+			.find(|r| r.group == group && normalize_package_name(&r.name) == normalized_name);
+		match existing {
+			Some(existing) => existing.version = combine_versions(&existing.version, &version),
+			None => self.requirements.push(ImportedRequirement {
+				name: String::from(name),
+				version,
+				group: String::from(group),
+			}),
+		}
+	}
+
+	/// Adds a PEP 508 requirement, ex. `requests[socks]>=2.32; python_version >= "3.10"`.
+	fn add_pep508(&mut self, location: &str, requirement: &str, group: &str) {
+		if requirement.contains("://") || requirement.starts_with('.') || requirement.starts_with('/') {
+			self.skipped.push(format!(
+				"{}: {} (URLs and paths are not supported, pen installs from PyPI)",
+				location, requirement
+			));
+			return;
+		}
+		match parse_project_dependency(requirement) {
+			Ok(dependency) => self.add(
+				location,
+				&dependency.name,
+				dependency.version,
+				&dependency.extras,
+				dependency.marker.as_deref(),
+				group,
+			),
+			Err(e) => self.skipped.push(format!("{}: {} ({})", location, requirement, e)),
+		}
+	}
+
+	fn read_requirements_txt(&mut self, path: &PathBuf, group: &str, is_constraints: bool) -> Result<(), AnyError> {
+		let contents = guard!(fs::read_to_string(path), "Couldn't read {}", path.display());
+		let canonical_path = fs::canonicalize(path).unwrap_or(path.clone());
+		if self.reading.contains(&canonical_path) {
+			return Ok(()); // Already being read, ex. two files including each other
+		}
+		self.reading.push(canonical_path);
+		let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+		let display_path = path.display().to_string();
+
+		let mut has_hashes = false;
+		for (line_number, line) in join_requirement_lines(&contents) {
+			let location = format!("{}:{}", display_path, line_number);
+			let mut words = line.split_whitespace();
+			let first = words.next().unwrap_or("");
+
+			if !first.starts_with('-') {
+				// Options of a requirement, ex. `requests==2.32.3 --hash=sha256:...`
+				let (requirement, options) = match line.find(" -") {
+					Some(start) => (&line[..start], line[start..].trim()),
+					None => (line.as_str(), ""),
+				};
+				for option in options.split_whitespace() {
+					match option.starts_with("--hash") {
+						true => has_hashes = true,
+						false if option.starts_with('-') => self.notes.push(format!("{}: option {} ignored", location, option)),
+						false => {}
+					}
+				}
+				match is_constraints {
+					true => self.add_constraint(&location, requirement.trim()),
+					false => self.add_pep508(&location, requirement.trim(), group),
+				}
+				continue;
+			}
+
+			// Options with a value, given as `-r file`, `-rfile` or `--requirement=file`
+			let (option, value) = match first.split_once('=') {
+				Some((option, value)) => (option, String::from(value)),
+				None if first.len() > 2 && !first.starts_with("--") => (&first[..2], String::from(&first[2..])),
+				None => (first, words.collect::<Vec<&str>>().join(" ")),
+			};
+			match option {
+				"-r" | "--requirement" | "-c" | "--constraint" => {
+					let is_constraint_file = is_constraints || option == "-c" || option == "--constraint";
+					let included = dir.join(&value);
+					if !included.exists() {
+						self.skipped.push(format!("{}: {} (file not found)", location, line));
+						continue;
+					}
+					self.read_requirements_txt(&included, group, is_constraint_file)?;
+				}
+				"-i" | "--index-url" | "--extra-index-url" => {
+					if !is_pypi_url(&value) {
+						self.skipped.push(format!("{}: {} (pen only installs from PyPI)", location, line));
+					}
+				}
+				"--require-hashes" => has_hashes = true,
+				"-e" | "--editable" => self.skipped.push(format!("{}: {} (editable installs are not supported)", location, line)),
+				_ => self.skipped.push(format!("{}: {} (pip option not supported)", location, line)),
+			}
+		}
+
+		if has_hashes {
+			self.notes
+				.push(format!("{}: hashes are not kept, pen.lock doesn't have any", display_path));
+		}
+		self.reading.pop();
+		return Ok(());
+	}
+
+	fn add_constraint(&mut self, location: &str, requirement: &str) {
+		match parse_project_dependency(requirement) {
+			Ok(dependency) => self.constraints.push(ImportedRequirement {
+				name: dependency.name,
+				version: dependency.version,
+				group: String::new(),
+			}),
+			Err(e) => self.skipped.push(format!("{}: {} ({})", location, requirement, e)),
+		}
+	}
+
+	fn read_pipfile(&mut self, path: &PathBuf, group: &str) -> Result<(), AnyError> {
+		let python = self.python.clone();
+		let contents = guard!(fs::read_to_string(path), "Couldn't read {}", path.display());
+		let pipfile = guard!(toml::from_str::<toml::Table>(&contents), "Couldn't parse {}", path.display());
+		let display_path = path.display().to_string();
+
+		for (key, value) in &pipfile {
+			let location = format!("{} [{}]", display_path, key);
+			let category_group = match key.as_str() {
+				"source" => {
+					let sources = value.as_array().map(|sources| sources.iter().collect()).unwrap_or(Vec::new());
+					for url in sources.iter().filter_map(|source| source.get("url")).filter_map(|url| url.as_str()) {
+						if !is_pypi_url(url) {
+							self.skipped.push(format!("{}: {} (pen only installs from PyPI)", location, url));
+						}
+					}
+					continue;
+				}
+				"requires" => {
+					let required = value.get("python_full_version").or(value.get("python_version")).and_then(|v| v.as_str());
+					if let Some(required) = required.filter(|required| !python.to_string().starts_with(*required)) {
+						self.notes.push(format!(
+							"{}: the Pipfile requires Python {}, the project uses {}",
+							location, required, python
+						));
+					}
+					continue;
+				}
+				"scripts" | "pipenv" => {
+					self.skipped.push(format!("{} (Pipenv settings are not imported)", location));
+					continue;
+				}
+				"packages" => group,
+				"dev-packages" => DEV_GROUP,
+				category => category, // Custom package categories
+			};
+
+			let packages = match value.as_table() {
+				Some(packages) => packages,
+				None => continue,
+			};
+			for (name, spec) in packages {
+				let location = format!("{} [{}] {}", display_path, key, name);
+				let (version, extras, marker) = match spec {
+					toml::Value::String(version) => (version.as_str(), String::new(), None),
+					toml::Value::Table(spec) => {
+						if let Some(source) = ["git", "path", "file", "editable"].iter().find(|key| spec.contains_key(**key)) {
+							self.skipped.push(format!(
+								"{} ({} dependencies are not supported, pen installs from PyPI)",
+								location, source
+							));
+							continue;
+						}
+						let extras: Vec<&str> = spec
+							.get("extras")
+							.and_then(|extras| extras.as_array())
+							.map(|extras| extras.iter().filter_map(|extra| extra.as_str()).collect())
+							.unwrap_or_default();
+						let extras = match extras.is_empty() {
+							true => String::new(),
+							false => format!("[{}]", extras.join(",")),
+						};
+						let version = spec.get("version").and_then(|version| version.as_str()).unwrap_or("*");
+						(version, extras, spec.get("markers").and_then(|marker| marker.as_str()))
+					}
+					_ => {
+						self.skipped.push(format!("{} (unknown format)", location));
+						continue;
+					}
+				};
+
+				let version = match version.trim() {
+					"*" | "" => Ok(String::from("*")),
+					version => to_version_requirement(version),
+				};
+				match version {
+					Ok(version) => self.add(&location, name, version, &extras, marker, category_group),
+					Err(e) => self.skipped.push(format!("{} ({})", location, e)),
+				}
+			}
+		}
+		return Ok(());
+	}
+
+	fn read_pipfile_lock(&mut self, path: &PathBuf, group: &str) -> Result<(), AnyError> {
+		let contents = guard!(fs::read_to_string(path), "Couldn't read {}", path.display());
+		let lockfile = guard!(serde_json::from_str::<serde_json::Value>(&contents), "Couldn't parse {}", path.display());
+		let display_path = path.display().to_string();
+
+		let sources = lockfile["_meta"]["sources"].as_array().cloned().unwrap_or_default();
+		for url in sources.iter().filter_map(|source| source["url"].as_str()) {
+			if !is_pypi_url(url) {
+				self.skipped
+					.push(format!("{} _meta: {} (pen only installs from PyPI)", display_path, url));
+			}
+		}
+
+		let mut has_hashes = false;
+		for (section, section_group) in [("default", group), ("develop", DEV_GROUP)] {
+			let packages = match lockfile[section].as_object() {
+				Some(packages) => packages,
+				None => continue,
+			};
+			for (name, spec) in packages {
+				let location = format!("{} {} {}", display_path, section, name);
+				if let Some(source) = ["git", "path", "file", "editable"].iter().find(|key| spec.get(**key).is_some()) {
+					self.skipped.push(format!(
+						"{} ({} dependencies are not supported, pen installs from PyPI)",
+						location, source
+					));
+					continue;
+				}
+				has_hashes |= spec["hashes"].as_array().is_some_and(|hashes| !hashes.is_empty());
+
+				let version = match spec["version"].as_str().map(to_version_requirement) {
+					Some(Ok(version)) => version,
+					Some(Err(e)) => {
+						self.skipped.push(format!("{} ({})", location, e));
+						continue;
+					}
+					None => String::from("*"),
+				};
+				self.add(&location, name, version, "", spec["markers"].as_str(), section_group);
+			}
+		}
+
+		if has_hashes {
+			self.notes
+				.push(format!("{}: hashes are not kept, pen.lock doesn't have any", display_path));
+		}
+		return Ok(());
+	}
+
+	fn read_poetry(&mut self, path: &PathBuf, group: &str) -> Result<(), AnyError> {
+		let python = self.python.clone();
+		let contents = guard!(fs::read_to_string(path), "Couldn't read {}", path.display());
+		let pyproject = guard!(toml::from_str::<toml::Table>(&contents), "Couldn't parse {}", path.display());
+		let display_path = path.display().to_string();
+
+		let poetry = match pyproject.get("tool").and_then(|tool| tool.get("poetry")) {
+			Some(poetry) => poetry,
+			None => return error!("No [tool.poetry] table in {}", path.display()),
+		};
+		let mut tables = vec![(String::from("dependencies"), group, poetry.get("dependencies"))];
+		tables.push((String::from("dev-dependencies"), DEV_GROUP, poetry.get("dev-dependencies")));
+		if let Some(groups) = poetry.get("group").and_then(|groups| groups.as_table()) {
+			for (name, poetry_group) in groups {
+				tables.push((format!("group.{}.dependencies", name), name.as_str(), poetry_group.get("dependencies")));
+			}
+		}
+
+		for (table, table_group, dependencies) in tables {
+			let dependencies = match dependencies.and_then(|dependencies| dependencies.as_table()) {
+				Some(dependencies) => dependencies,
+				None => continue,
+			};
+			for (name, spec) in dependencies {
+				let location = format!("{} [tool.poetry.{}] {}", display_path, table, name);
+				if name == "python" {
+					let allows_python = spec
+						.as_str()
+						.and_then(|c| poetry_to_version_requirement(c).ok())
+						.and_then(|c| semver::VersionReq::parse(&c).ok());
+					if allows_python.is_some_and(|allows_python| !allows_python.matches(&python.version)) {
+						self.notes.push(format!(
+							"{}: the project requires Python {}, pen uses {}",
+							location,
+							spec.as_str().unwrap_or_default(),
+							python
+						));
+					}
+					continue;
+				}
+
+				let (constraint, extras, marker) = match spec {
+					toml::Value::String(constraint) => (constraint.as_str(), String::new(), None),
+					toml::Value::Table(spec) => {
+						if let Some(source) = ["git", "path", "url"].iter().find(|key| spec.contains_key(**key)) {
+							self.skipped.push(format!(
+								"{} ({} dependencies are not supported, pen installs from PyPI)",
+								location, source
+							));
+							continue;
+						}
+						if spec.get("optional").and_then(|optional| optional.as_bool()) == Some(true) {
+							self.skipped
+								.push(format!("{} (optional, only installed with an extra of the project)", location));
+							continue;
+						}
+						if let Some(python_constraint) = spec.get("python").and_then(|python| python.as_str()) {
+							let allows_python = poetry_to_version_requirement(python_constraint)
+								.ok()
+								.and_then(|c| semver::VersionReq::parse(&c).ok());
+							if allows_python.is_some_and(|allows_python| !allows_python.matches(&python.version)) {
+								self.notes
+									.push(format!("{}: left out, it is only needed for Python {}", location, python_constraint));
+								continue;
+							}
+						}
+						let extras: Vec<&str> = spec
+							.get("extras")
+							.and_then(|extras| extras.as_array())
+							.map(|extras| extras.iter().filter_map(|extra| extra.as_str()).collect())
+							.unwrap_or_default();
+						let extras = match extras.is_empty() {
+							true => String::new(),
+							false => format!("[{}]", extras.join(",")),
+						};
+						let constraint = spec.get("version").and_then(|version| version.as_str()).unwrap_or("*");
+						(constraint, extras, spec.get("markers").and_then(|marker| marker.as_str()))
+					}
+					_ => {
+						self.skipped
+							.push(format!("{} (several constraints for different environments are not supported)", location));
+						continue;
+					}
+				};
+
+				match poetry_to_version_requirement(constraint) {
+					Ok(version) => self.add(&location, name, version, &extras, marker, table_group),
+					Err(e) => self.skipped.push(format!("{} ({})", location, e)),
+				}
+			}
+		}
+		return Ok(());
+	}
+}
+
+/// Removes the comments of a requirement file and joins the lines ending with `\`.
+///
+/// # Output
+/// - The non empty lines, with the number of their first line.
+fn join_requirement_lines(contents: &str) -> Vec<(usize, String)> {
+	let mut lines = Vec::new();
+	let mut pending: Option<(usize, String)> = None;
+	for (index, line) in contents.lines().enumerate() {
+		let line = match line.trim_start().starts_with('#') {
+			true => "",
+			false => line.split(" #").next().unwrap_or("").split("\t#").next().unwrap_or(""),
+		};
+		let (start, mut joined) = pending.take().unwrap_or((index + 1, String::new()));
+		match line.trim_end().strip_suffix('\\') {
+			Some(line) => {
+				joined.push_str(line);
+				pending = Some((start, joined));
+			}
+			None => {
+				joined.push_str(line);
+				if !joined.trim().is_empty() {
+					lines.push((start, String::from(joined.trim())));
+				}
+			}
+		}
+	}
+	if let Some((start, joined)) = pending.filter(|(_, joined)| !joined.trim().is_empty()) {
+		lines.push((start, String::from(joined.trim())));
+	}
+	return lines;
+}
+
+/// Translates a Poetry version constraint to a pen version requirement, ex. `^2.32`, `~1.4`, `>=1.2,<1.5` or `2.0.1`.
+fn poetry_to_version_requirement(constraint: &str) -> Result<String, String> {
+	if constraint.contains("||") {
+		return Err(format!("`{}` can't be translated, pen version requirements have no `||`", constraint));
+	}
+
+	let mut comparators = Vec::new();
+	for clause in constraint.split(',').map(str::trim).filter(|clause| !clause.is_empty() && *clause != "*") {
+		let comparator = match clause {
+			_ if clause.starts_with("~=") => to_version_requirement(clause)?,
+			_ if clause.starts_with("!=") => return Err(format!("`{}` can't be translated, pen version requirements have no `!=`", clause)),
+			_ if clause.starts_with("==") => format!("={}", &clause[2..]),
+			// A bare version is an exact version for Poetry, and a caret requirement for pen
+			_ if clause.starts_with(|c: char| c.is_ascii_digit()) && !clause.contains('*') => format!("={}", clause),
+			_ => String::from(clause),
+		};
+		comparators.push(comparator);
+	}
+	if comparators.is_empty() {
+		return Ok(String::from("*"));
+	}
+
+	let version = comparators.join(", ");
+	return match semver::VersionReq::parse(&version) {
+		Ok(_) => Ok(version),
+		Err(e) => Err(format!("`{}` can't be translated: {}", constraint, e)),
+	};
+}
+
+/// Combines two pen version requirements into one that both must satisfy.
+fn combine_versions(a: &str, b: &str) -> String {
+	return match (a, b) {
+		("*", b) => String::from(b),
+		(a, "*") => String::from(a),
+		(a, b) if a == b => String::from(a),
+		(a, b) => format!("{}, {}", a, b),
+	};
+}
+
+fn is_pypi_url(url: &str) -> bool {
+	let url = url.trim_end_matches('/');
+	return ["https://pypi.org/simple", "https://pypi.python.org/simple", "https://pypi.org/pypi"].contains(&url);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils::MAIN_GROUP;
+	use std::env;
+
+	#[test]
+	fn poetry_constraints() {
+		assert_eq!(poetry_to_version_requirement("^2.32"), Ok(String::from("^2.32")));
+		assert_eq!(poetry_to_version_requirement("~1.4"), Ok(String::from("~1.4")));
+		assert_eq!(poetry_to_version_requirement(">=1.2,<1.5"), Ok(String::from(">=1.2, <1.5")));
+		assert_eq!(poetry_to_version_requirement("2.0.1"), Ok(String::from("=2.0.1")));
+		assert_eq!(poetry_to_version_requirement("==2.0.1"), Ok(String::from("=2.0.1")));
+		assert_eq!(poetry_to_version_requirement("1.*"), Ok(String::from("1.*")));
+		assert_eq!(poetry_to_version_requirement("*"), Ok(String::from("*")));
+		assert_eq!(poetry_to_version_requirement(""), Ok(String::from("*")));
+	}
+
+	#[test]
+	fn poetry_constraints_pen_cant_express() {
+		assert!(poetry_to_version_requirement("^1.2 || ^2.0").is_err());
+		assert!(poetry_to_version_requirement(">=1.2,!=1.3.0").is_err());
+		assert!(poetry_to_version_requirement(">=banana").is_err());
+	}
+
+	#[test]
+	fn included_requirement_and_constraint_files() {
+		let dir = env::temp_dir().join(format!("pen-test-import-{}", std::process::id()));
+		fs::create_dir_all(dir.join("requirements")).unwrap();
+		fs::write(
+			dir.join("requirements.txt"),
+			"-r requirements/base.txt\n-c constraints.txt\nrich>=13\n-rmissing.txt\n",
+		)
+		.unwrap();
+		// Paths are relative to the file including them, and cycles are stopped
+		fs::write(dir.join("requirements/base.txt"), "requests>=2.31\n--requirement=../requirements.txt\n").unwrap();
+		fs::write(dir.join("constraints.txt"), "requests<3\nclick==8.1.7\n").unwrap();
+
+		let import = import_requirements(&dir.join("requirements.txt"), MAIN_GROUP, &PythonSpec::parse("3.12.4").unwrap());
+		fs::remove_dir_all(&dir).unwrap();
+		let import = import.unwrap();
+
+		let requirements: Vec<(&str, &str)> = import
+			.requirements
+			.iter()
+			.map(|requirement| (requirement.name.as_str(), requirement.version.as_str()))
+			.collect();
+		// Constraints only apply to the packages required elsewhere
+		assert_eq!(requirements, vec![("requests", ">=2.31.0, <3.0.0"), ("rich", ">=13.0.0")]);
+		assert_eq!(import.skipped.len(), 1);
+		assert!(import.skipped[0].ends_with("requirements.txt:4: -rmissing.txt (file not found)"));
+	}
+}
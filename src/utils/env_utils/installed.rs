@@ -0,0 +1,256 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::utils::{error, guard, parse_release_version, AnyError, Package, PythonSpec};
+
+/// A distribution installed in the site-packages of an environment, read from its `.dist-info` directory.
+pub struct InstalledDistribution {
+	pub name: String,
+	/// The version as written in its metadata, ex. `2.32.3` or `2.0rc1`.
+	pub version: String,
+	pub dist_info_path: PathBuf,
+	/// The `url` of its `direct_url.json`, for a distribution installed from a URL or a local path (ex. `pip install -e .`) instead of an index.
+	pub direct_url: Option<String>,
+}
+
+/// An entry point script of a distribution, ex. `black = black:patched_main` in its `console_scripts`.
+pub struct EntryPoint {
+	pub name: String,
+	/// The module to import, ex. `black`.
+	pub module: String,
+	/// The object of the module the script calls, ex. `patched_main` or `Cli.main`.
+	pub function: String,
+}
+
+/// A line of the `RECORD` of a distribution.
+pub struct RecordEntry {
+	/// The path of the file, relative to site-packages.
+	pub path: PathBuf,
+	/// The hash of the file, ex. `sha256=<urlsafe base64>`, `None` for files that can change (ex. `.pyc` files or the `RECORD` itself).
+	pub hash: Option<String>,
+}
+
+impl InstalledDistribution {
+	/// The distribution as a package of the cache, `None` when its version isn't semver once padded, which pen can't install.
+	pub fn to_package(&self) -> Option<Package> {
+		let version = parse_release_version(&self.version)?;
+		return Some(Package {
+			name: self.name.clone(),
+			version,
+		});
+	}
+
+	/// Reads the files of the distribution that are in site-packages, as listed in its `RECORD`.
+	///
+	/// # Output
+	/// - The entries of `RECORD` with a path inside site-packages. Files installed elsewhere (ex. scripts in `bin`) are left out.
+	///
+	/// # Termination
+	/// - This function returns an error if the distribution has no `RECORD`.
+	pub fn read_record(&self) -> Result<Vec<RecordEntry>, AnyError> {
+		let record = read_record(&self.dist_info_path.join("RECORD"))?;
+		return Ok(record
+			.into_iter()
+			.filter(|entry| entry.path.components().all(|c| matches!(c, Component::Normal(_))))
+			.collect());
+	}
+
+	/// Lists the files of the distribution that were changed or deleted since it was installed.
+	///
+	/// # Output
+	/// - The paths, relative to `site_packages_path`, of the files whose content no longer matches the hash of `RECORD`.
+	pub fn get_modified_files(&self, site_packages_path: &Path) -> Result<Vec<PathBuf>, AnyError> {
+		let mut modified = Vec::new();
+		for entry in self.read_record()? {
+			let expected = match entry.hash.as_deref().and_then(|hash| hash.strip_prefix("sha256=")) {
+				Some(expected) => expected,
+				None => continue,
+			};
+			let matches = match fs::read(site_packages_path.join(&entry.path)) {
+				Ok(contents) => to_record_hash(&Sha256::digest(&contents)) == expected,
+				Err(_) => false,
+			};
+			if !matches {
+				modified.push(entry.path);
+			}
+		}
+		return Ok(modified);
+	}
+}
+
+/// Lists the distributions installed in a site-packages directory.
+///
+/// # Output
+/// - One entry per `.dist-info` directory, sorted by name.
+///
+/// # Termination
+/// - This function returns an error if the directory or the `METADATA` of a distribution can't be read.
+///
+/// # Limitations
+/// - Distributions installed the legacy way, with an `.egg-info` directory, are not listed, nor are broken links to a `.dist-info` directory.
+pub fn read_installed_distributions(site_packages_path: &PathBuf) -> Result<Vec<InstalledDistribution>, AnyError> {
+	let entries = guard!(fs::read_dir(site_packages_path), "Failed to read {}", site_packages_path.display());
+
+	let mut distributions = Vec::new();
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		// Broken links into the package cache are skipped
+		if !entry.file_name().to_string_lossy().ends_with(".dist-info") || !entry.path().exists() {
+			continue;
+		}
+
+		let dist_info_path = entry.path();
+		let metadata_path = dist_info_path.join("METADATA");
+		let metadata = guard!(fs::read_to_string(&metadata_path), "Couldn't read {}", metadata_path.display());
+		let header = |key: &str| {
+			metadata
+				.lines()
+				.take_while(|line| !line.is_empty()) // The headers end at the first blank line, the description follows
+				.find_map(|line| line.strip_prefix(key))
+				.map(|value| value.trim().to_string())
+		};
+		let (name, version) = match (header("Name:"), header("Version:")) {
+			(Some(name), Some(version)) => (name, version),
+			_ => return error!("{} has no name or version.", metadata_path.display()),
+		};
+
+		let direct_url = fs::read_to_string(dist_info_path.join("direct_url.json"))
+			.ok()
+			.and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+			.map(|json| json["url"].as_str().unwrap_or_default().to_string());
+
+		distributions.push(InstalledDistribution {
+			name,
+			version,
+			dist_info_path,
+			direct_url,
+		});
+	}
+	distributions.sort_by_key(|distribution| distribution.name.to_lowercase());
+	return Ok(distributions);
+}
+
+/// Reads the entry point scripts of a distribution, its `console_scripts` and `gui_scripts`.
+///
+/// # Output
+/// - The scripts, none when the distribution has no `entry_points.txt`. Entries that don't name an object to call are left out.
+pub fn read_entry_points(dist_info_path: &Path) -> Vec<EntryPoint> {
+	// Most packages have no entry points
+	let entry_points = match fs::read_to_string(dist_info_path.join("entry_points.txt")) {
+		Ok(entry_points) => entry_points,
+		Err(_) => return Vec::new(),
+	};
+
+	let mut scripts = Vec::new();
+	let mut section = "";
+	for line in entry_points.lines().map(|line| line.trim()) {
+		if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+			section = name;
+			continue;
+		}
+		if section != "console_scripts" && section != "gui_scripts" {
+			continue;
+		}
+		// `name = module:object [extras]`, the extras only matter to installers
+		let (name, target) = match line.split_once('=') {
+			Some((name, target)) if !name.trim().is_empty() => (name.trim(), target.split('[').next().unwrap_or_default().trim()),
+			_ => continue,
+		};
+		if let Some((module, function)) = target.split_once(':') {
+			scripts.push(EntryPoint {
+				name: String::from(name),
+				module: String::from(module.trim()),
+				function: String::from(function.trim()),
+			});
+		}
+	}
+	return scripts;
+}
+
+/// Reads the `RECORD` file of a distribution.
+///
+/// # Termination
+/// - This function returns an error if the file can't be read.
+pub fn read_record(record_path: &PathBuf) -> Result<Vec<RecordEntry>, AnyError> {
+	let record = guard!(fs::read_to_string(record_path), "Couldn't read {}", record_path.display());
+	let entries = record
+		.lines()
+		.filter_map(|line| {
+			let (path, rest) = match line.strip_prefix('"') {
+				// Paths containing commas are quoted
+				Some(quoted) => quoted.split_once('"').map(|(path, rest)| (path, rest.trim_start_matches(',')))?,
+				None => line.split_once(',').unwrap_or((line, "")),
+			};
+			if path.is_empty() {
+				return None;
+			}
+			let hash = rest.split(',').next().filter(|hash| !hash.is_empty()).map(String::from);
+			Some(RecordEntry {
+				path: PathBuf::from(path),
+				hash,
+			})
+		})
+		.collect();
+	return Ok(entries);
+}
+
+/// Reads the Python of a virtual environment from its `pyvenv.cfg`.
+///
+/// # Termination
+/// - This function returns an error if the file can't be read or has no version.
+pub fn read_pyvenv_python(env_path: &Path) -> Result<PythonSpec, AnyError> {
+	let pyvenv_path = env_path.join("pyvenv.cfg");
+	let contents = guard!(fs::read_to_string(&pyvenv_path), "Couldn't read {}", pyvenv_path.display());
+	let values: BTreeMap<String, String> = contents
+		.lines()
+		.filter_map(|line| line.split_once('='))
+		.map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+		.collect();
+
+	// pen writes the Python of the config, ex. `3.12.4` or `pypy@3.10`
+	if let Some(python) = values.get("version").filter(|_| contents.starts_with("# Created using pen")) {
+		return match PythonSpec::parse(python) {
+			Ok(python) => Ok(python),
+			Err(e) => error!("Unsupported Python in {}: {}", pyvenv_path.display(), e),
+		};
+	}
+
+	// `python -m venv` writes `version`, virtualenv `version_info`, ex. `3.12.4.final.0`
+	let version = match values.get("version").or(values.get("version_info")) {
+		Some(version) => version.split('.').take(3).collect::<Vec<&str>>().join("."),
+		None => return error!("{} has no Python version.", pyvenv_path.display()),
+	};
+
+	// Only virtualenv writes the implementation, otherwise the lib directory (`pypy3.10`) and the interpreter path tell it
+	let is_pypy = fs::read_dir(env_path.join("lib"))
+		.map(|entries| entries.flatten().any(|entry| entry.file_name().to_string_lossy().starts_with("pypy")))
+		.unwrap_or(false);
+	let home = values.get("home").map(|home| home.to_lowercase()).unwrap_or_default();
+	let implementation = match values.get("implementation").map(|implementation| implementation.to_lowercase()) {
+		Some(implementation) => implementation,
+		None if is_pypy => String::from("pypy"),
+		None if home.contains("graalpy") => String::from("graalpy"),
+		None => String::from("cpython"),
+	};
+
+	return match PythonSpec::parse(&format!("{}@{}", implementation, version)) {
+		Ok(python) => Ok(python),
+		Err(e) => error!("Unsupported Python in {}: {}", pyvenv_path.display(), e),
+	};
+}
+
+/// Encodes a digest as in `RECORD` files: urlsafe base64 without padding.
+fn to_record_hash(digest: &[u8]) -> String {
+	let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+	let mut encoded = String::new();
+	for chunk in digest.chunks(3) {
+		let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+		for i in 0..=chunk.len() {
+			encoded.push(alphabet[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+		}
+	}
+	return encoded;
+}
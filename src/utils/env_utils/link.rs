@@ -0,0 +1,232 @@
+use serde::Deserialize;
+use std::{fmt, fs, io, os::unix, path::PathBuf};
+
+use crate::utils::{error, guard, AnyError};
+
+/// How files of the package cache are put into virtual environments.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+	/// Symlink each top-level entry of a package, the default.
+	Symlink,
+	/// Hardlink every file.
+	Hardlink,
+	/// Copy-on-write clone (reflink) every file, on filesystems supporting it.
+	#[serde(alias = "reflink")]
+	Clone,
+	/// Copy every file, making the environment independent from the cache.
+	Copy,
+}
+
+impl LinkMode {
+	pub fn parse(mode: &str) -> Result<LinkMode, String> {
+		match mode {
+			"symlink" => return Ok(LinkMode::Symlink),
+			"hardlink" => return Ok(LinkMode::Hardlink),
+			"clone" | "reflink" => return Ok(LinkMode::Clone),
+			"copy" => return Ok(LinkMode::Copy),
+			_ => return Err(format!("Unknown link mode {}, expected symlink, hardlink, clone or copy", mode)),
+		}
+	}
+
+	/// The mode to use when this one isn't supported, ex. hardlinks across filesystems.
+	fn fallback(&self) -> Option<LinkMode> {
+		match self {
+			LinkMode::Symlink => Some(LinkMode::Copy),
+			// Clones are chosen so that editing a file of the environment doesn't edit the cache, which a hardlink would
+			LinkMode::Clone => Some(LinkMode::Copy),
+			LinkMode::Hardlink => Some(LinkMode::Copy),
+			LinkMode::Copy => None,
+		}
+	}
+}
+
+impl fmt::Display for LinkMode {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let name = match self {
+			LinkMode::Symlink => "symlink",
+			LinkMode::Hardlink => "hardlink",
+			LinkMode::Clone => "clone",
+			LinkMode::Copy => "copy",
+		};
+		return write!(f, "{}", name);
+	}
+}
+
+/// Puts a file or directory of the package cache at `destination` using `mode`.
+///
+/// # Arguments
+/// - `source`: The file or directory in the cache.
+/// - `destination`: Where it goes in the environment. It must not exist yet.
+/// - `mode`: The preferred link mode. When it fails, its fallback (always copy) is used instead.
+///
+/// # Output
+/// - The link mode that worked, so that callers can skip the modes known to fail for the next entries.
+///
+/// # Termination
+/// - This function returns an error if `destination` already exists or if even copying fails.
+pub fn link_tree(source: &PathBuf, destination: &PathBuf, mode: LinkMode) -> Result<LinkMode, AnyError> {
+	if fs::symlink_metadata(destination).is_ok() {
+		return error!("{} already exists", destination.display());
+	}
+
+	if mode == LinkMode::Symlink {
+		match unix::fs::symlink(source, destination) {
+			Ok(()) => return Ok(mode),
+			Err(e) => eprintln!("Couldn't symlink {} ({}), falling back to {}.", destination.display(), e, LinkMode::Copy),
+		}
+		return link_tree(source, destination, LinkMode::Copy);
+	}
+
+	let metadata = guard!(fs::symlink_metadata(source), "Failed to read metadata of {}", source.display());
+
+	if metadata.is_symlink() {
+		let target = guard!(fs::read_link(source), "Couldn't read link {}", source.display());
+		guard!(
+			unix::fs::symlink(&target, destination),
+			"Couldn't symlink {} to {}",
+			target.display(),
+			destination.display()
+		);
+		return Ok(mode);
+	}
+
+	if metadata.is_dir() {
+		guard!(fs::create_dir(destination), "Couldn't create folder {}", destination.display());
+		let mut mode = mode;
+		let entries = guard!(fs::read_dir(source), "Failed to read {}", source.display());
+		for entry in entries {
+			let entry = guard!(entry, "Failed to read directory entry");
+			mode = link_tree(&entry.path(), &destination.join(entry.file_name()), mode)?;
+		}
+		return Ok(mode);
+	}
+
+	let mut mode = mode;
+	loop {
+		let result = match mode {
+			LinkMode::Clone => clone_file(source, destination),
+			LinkMode::Hardlink => fs::hard_link(source, destination),
+			LinkMode::Copy | LinkMode::Symlink => fs::copy(source, destination).map(|_| ()),
+		};
+		match (result, mode.fallback()) {
+			(Ok(()), _) => return Ok(mode),
+			(Err(e), Some(fallback)) => {
+				eprintln!("Couldn't {} {} ({}), falling back to {}.", mode, destination.display(), e, fallback);
+				mode = fallback;
+			}
+			(Err(e), None) => {
+				eprintln!(
+					"[{}:{}] - Couldn't copy {} to {}",
+					file!(),
+					line!(),
+					source.display(),
+					destination.display()
+				);
+				return Err(e.into());
+			}
+		}
+	}
+}
+
+/// Puts a file or directory of the package cache at `destination`, merging it with what other packages already put there.
+///
+/// # Arguments
+/// - `source`: The file or directory in the cache.
+/// - `destination`: Where it goes in the environment.
+/// - `mode`: The preferred link mode, see `link_tree`.
+///
+/// # Output
+/// - The link mode that worked.
+///
+/// # Termination
+/// - This function returns an error if one package has a directory where another one has a file.
+///
+/// # Guarantees
+/// - Directories shared by several packages (namespace packages like `google`, or `bin`) become real directories
+///   and only the files or subdirectories that are not shared are linked.
+/// - A file shipped by several packages is taken from the last one linked, like pip does.
+pub fn merge_tree(source: &PathBuf, destination: &PathBuf, mode: LinkMode) -> Result<LinkMode, AnyError> {
+	let existing = match fs::symlink_metadata(destination) {
+		Ok(existing) => existing,
+		Err(_) => return link_tree(source, destination, mode),
+	};
+
+	let source_is_dir = source.is_dir();
+	let existing_is_dir = destination.is_dir();
+
+	if source_is_dir != existing_is_dir {
+		return error!(
+			"{} is a directory in one package and a file in another, they can't be installed together",
+			destination.display()
+		);
+	}
+
+	if !source_is_dir {
+		// Namespace packages often all ship the same __init__.py, only differing files are worth a warning
+		if fs::read(source).ok() != fs::read(destination).ok() {
+			eprintln!(
+				"{} is shipped by several packages, using the one from {}.",
+				destination.display(),
+				source.display()
+			);
+		}
+		guard!(fs::remove_file(destination), "Couldn't remove {}", destination.display());
+		return link_tree(source, destination, mode);
+	}
+
+	if existing.is_symlink() {
+		// The directory of another package was linked as a whole, it becomes a real directory linking to its content
+		let target = guard!(fs::read_link(destination), "Couldn't read link {}", destination.display());
+		guard!(fs::remove_file(destination), "Couldn't remove {}", destination.display());
+		guard!(fs::create_dir(destination), "Couldn't create folder {}", destination.display());
+
+		let entries = guard!(fs::read_dir(&target), "Failed to read {}", target.display());
+		for entry in entries {
+			let entry = guard!(entry, "Failed to read directory entry");
+			link_tree(&entry.path(), &destination.join(entry.file_name()), LinkMode::Symlink)?;
+		}
+	}
+
+	let mut mode = mode;
+	let entries = guard!(fs::read_dir(source), "Failed to read {}", source.display());
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		mode = merge_tree(&entry.path(), &destination.join(entry.file_name()), mode)?;
+	}
+	return Ok(mode);
+}
+
+#[cfg(target_os = "linux")]
+fn clone_file(source: &PathBuf, destination: &PathBuf) -> io::Result<()> {
+	use std::os::fd::AsRawFd;
+
+	let source_file = fs::File::open(source)?;
+	let destination_file = fs::File::create_new(destination)?;
+	let result = unsafe { libc::ioctl(destination_file.as_raw_fd(), libc::FICLONE as _, source_file.as_raw_fd()) };
+	if result != 0 {
+		let e = io::Error::last_os_error();
+		drop(destination_file);
+		let _ = fs::remove_file(destination);
+		return Err(e);
+	}
+	return destination_file.set_permissions(source_file.metadata()?.permissions());
+}
+
+#[cfg(target_os = "macos")]
+fn clone_file(source: &PathBuf, destination: &PathBuf) -> io::Result<()> {
+	use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+	let source = CString::new(source.as_os_str().as_bytes())?;
+	let destination = CString::new(destination.as_os_str().as_bytes())?;
+	let result = unsafe { libc::clonefile(source.as_ptr(), destination.as_ptr(), 0) };
+	if result != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	return Ok(());
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn clone_file(_source: &PathBuf, _destination: &PathBuf) -> io::Result<()> {
+	return Err(io::Error::new(io::ErrorKind::Unsupported, "clones are not supported on this platform"));
+}
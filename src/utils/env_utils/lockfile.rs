@@ -0,0 +1,705 @@
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::{fs, path::Path};
+
+use crate::constants::{CONFIG_FILE_NAME, LOCKFILE_NAME};
+use crate::utils::{
+	error, get_package_dependencies, get_package_versions, guard, normalize_package_name, to_version_requirement, AnyError, Config, Dependency,
+	Package, PythonSpec,
+};
+
+/// The group of the packages of the `[packages]` table, always installed.
+pub static MAIN_GROUP: &str = "main";
+/// The dependency group installed by default, unless `--no-dev` is given.
+pub static DEV_GROUP: &str = "dev";
+
+/// Version 1 only locked the packages of the config, version 2 locks their dependencies too.
+static LOCKFILE_VERSION: u32 = 2;
+/// How many times the resolver goes over the requirements before giving up, each round only changes the versions that conflict.
+static MAX_RESOLUTION_ROUNDS: usize = 100;
+
+/// The versions every package of a project resolved to, for all of its groups, stored in `pen.lock`.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct Lockfile {
+	pub version: u32,
+	#[serde(default, rename = "package")]
+	pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockedPackage {
+	pub name: String,
+	pub version: Version,
+	/// The groups requiring this package, directly or through other packages, `main` for the `[packages]` table.
+	pub groups: Vec<String>,
+	/// The normalized names of the locked packages this one depends on.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub dependencies: Vec<String>,
+}
+
+impl Lockfile {
+	/// Creates a lockfile of the current format version.
+	pub fn new(packages: Vec<LockedPackage>) -> Lockfile {
+		return Lockfile {
+			version: LOCKFILE_VERSION,
+			packages,
+		};
+	}
+}
+
+impl LockedPackage {
+	pub fn to_package(&self) -> Package {
+		return Package {
+			name: self.name.clone(),
+			version: self.version.clone(),
+		};
+	}
+}
+
+/// A package required by the config, with the requirements of every group needing it combined.
+pub struct Requirement {
+	pub name: String,
+	pub version: VersionReq,
+	pub groups: Vec<String>,
+}
+
+/// Where the resolver gets the versions and the dependencies of packages.
+pub trait PackageSource {
+	/// Gets the name of a package as written by its index, and its versions.
+	fn get_versions(&self, name: &str) -> Result<(String, Vec<Version>), AnyError>;
+	/// Gets the dependencies of a version of a package that apply to `python` on this machine.
+	fn get_dependencies(&self, package: &Package, python: &PythonSpec) -> Result<Vec<Dependency>, AnyError>;
+}
+
+/// PyPI, with the dependencies of the packages already in the cache read from their metadata.
+///
+/// # Guarantees
+/// - The versions of a package are requested once, however many times they are needed.
+#[derive(Default)]
+pub struct PypiSource {
+	versions: RefCell<HashMap<String, (String, Vec<Version>)>>,
+}
+
+impl PackageSource for PypiSource {
+	fn get_versions(&self, name: &str) -> Result<(String, Vec<Version>), AnyError> {
+		let normalized_name = normalize_package_name(name);
+		if let Some(versions) = self.versions.borrow().get(&normalized_name) {
+			return Ok(versions.clone());
+		}
+		let versions = get_package_versions(name)?;
+		self.versions.borrow_mut().insert(normalized_name, versions.clone());
+		return Ok(versions);
+	}
+
+	fn get_dependencies(&self, package: &Package, python: &PythonSpec) -> Result<Vec<Dependency>, AnyError> {
+		return get_package_dependencies(package, python);
+	}
+}
+
+/// A version requirement on a package during resolution, from the config or from the packages depending on it.
+struct ResolverRequirement {
+	name: String,
+	version: VersionReq,
+	/// The versions excluded with `!=`, ex. `=1.5.7` for `!=1.5.7`.
+	excluded: Vec<VersionReq>,
+	/// Who requires the package, ex. `pen.toml` or `requests 2.32.3`, for error messages.
+	required_by: Vec<String>,
+}
+
+impl ResolverRequirement {
+	fn matches(&self, version: &Version) -> bool {
+		return self.version.matches(version) && !self.excluded.iter().any(|excluded| excluded.matches(version));
+	}
+}
+
+/// Reads the lockfile of a project.
+///
+/// # Output
+/// - The lockfile, `None` if the project has none. A lockfile of an older version is read as it is, it is never up to date.
+///
+/// # Termination
+/// - This function returns an error if the lockfile can't be read or parsed, or if it was written by a newer pen.
+pub fn read_lockfile(project_path: &Path) -> Result<Option<Lockfile>, AnyError> {
+	let lockfile_path = project_path.join(LOCKFILE_NAME);
+	if !guard!(lockfile_path.try_exists(), "Unable to know if {} exists", lockfile_path.display()) {
+		return Ok(None);
+	}
+	let contents = guard!(fs::read_to_string(&lockfile_path), "Couldn't read {}.", lockfile_path.display());
+	let lockfile = guard!(toml::from_str::<Lockfile>(&contents), "Couldn't parse {}.", lockfile_path.display());
+	if lockfile.version > LOCKFILE_VERSION {
+		return error!(
+			"{} has version {}, but this pen only reads version {}. Delete it to lock again.",
+			lockfile_path.display(),
+			lockfile.version,
+			LOCKFILE_VERSION
+		);
+	}
+	return Ok(Some(lockfile));
+}
+
+// todo docstring
+pub fn write_lockfile(project_path: &Path, lockfile: &Lockfile) -> Result<(), AnyError> {
+	let toml = guard!(
+		toml::to_string(lockfile),
+		"Couldn't convert the lockfile to valid toml.\nPlease open an issue on Github."
+	);
+	let lockfile_path = project_path.join(LOCKFILE_NAME);
+	guard!(
+		fs::write(
+			&lockfile_path,
+			format!("# This file is generated by pen, do not edit it by hand.\n\n{}", toml)
+		),
+		"Couldn't write to lockfile at {}.",
+		lockfile_path.display()
+	);
+	return Ok(());
+}
+
+/// Collects the packages of `[packages]` and of every dependency group of the config.
+///
+/// # Output
+/// - One requirement per package, sorted by name. A package listed in several groups must satisfy all of their requirements,
+///   so that every group resolves to the same version.
+///
+/// # Termination
+/// - This function returns an error if a version requirement is not a valid one, or if a group is named `main`.
+pub fn get_requirements(config: &Config) -> Result<Vec<Requirement>, AnyError> {
+	if config.dependency_groups.contains_key(MAIN_GROUP) {
+		return error!(
+			"{} is the name of the [packages] table, it can't be used as a dependency group",
+			MAIN_GROUP
+		);
+	}
+
+	let groups = [(MAIN_GROUP, &config.packages)]
+		.into_iter()
+		.chain(config.dependency_groups.iter().map(|(group, packages)| (group.as_str(), packages)));
+
+	let mut requirements: BTreeMap<String, Requirement> = BTreeMap::new();
+	for (group, packages) in groups {
+		for (name, version) in packages {
+			let table = match group == MAIN_GROUP {
+				true => String::from("packages"),
+				false => format!("dependency-groups.{}", group),
+			};
+			let version = match version.as_str().map(VersionReq::parse) {
+				Some(Ok(version)) => version,
+				Some(Err(e)) => return error!("Invalid version requirement for {} in [{}] of {}: {}", name, table, CONFIG_FILE_NAME, e),
+				None => return error!("The version of {} in [{}] of {} must be a string", name, table, CONFIG_FILE_NAME),
+			};
+
+			let requirement = requirements.entry(normalize_package_name(name)).or_insert_with(|| Requirement {
+				name: name.clone(),
+				version: VersionReq::STAR,
+				groups: Vec::new(),
+			});
+			requirement.version.comparators.extend(version.comparators);
+			requirement.groups.push(String::from(group));
+		}
+	}
+	return Ok(requirements.into_values().collect());
+}
+
+/// Resolves the packages of every group of the config and their dependencies into a lockfile from PyPI,
+/// keeping the versions of the previous lockfile when possible.
+///
+/// # Arguments
+/// - `config`: The project config.
+/// - `previous`: The current lockfile of the project, if any.
+///
+/// # Output
+/// - The new lockfile, with the packages sorted by name.
+///
+/// # Termination
+/// - This function returns an error if a requirement is invalid, if no version of a package satisfies every package requiring it,
+///   or if the dependencies of a package can't be read.
+pub fn resolve_lockfile(config: &Config, previous: Option<&Lockfile>) -> Result<Lockfile, AnyError> {
+	return resolve_lockfile_from(config, previous, &PypiSource::default());
+}
+
+/// Resolves the packages of every group of the config and their dependencies into a lockfile, from a given package source.
+///
+/// # Output
+/// - The new lockfile, with the packages sorted by name. Each package is in the groups of every package of the config leading to it.
+///
+/// # Termination
+/// - This function returns an error if a requirement is invalid, if no version of a package satisfies every package requiring it,
+///   or if the dependencies of a package can't be read.
+///
+/// # Guarantees
+/// - A package whose locked version still satisfies its requirements keeps it, only new or changed requirements ask the source.
+/// - Only the dependencies whose environment marker matches the Python of the project on this machine are locked.
+///
+/// # Limitations
+/// - The resolver doesn't backtrack: a package gets the newest version allowed by the packages requiring it, and a conflict
+///   between their requirements is an error instead of trying older versions of them.
+pub fn resolve_lockfile_from(config: &Config, previous: Option<&Lockfile>, source: &dyn PackageSource) -> Result<Lockfile, AnyError> {
+	let roots = get_requirements(config)?;
+	// The version chosen for each package, by normalized name, with its dependencies
+	let mut chosen: BTreeMap<String, (Package, Vec<Dependency>)> = BTreeMap::new();
+
+	for _ in 0..MAX_RESOLUTION_ROUNDS {
+		let requirements = collect_requirements(&roots, &chosen)?;
+		let mut changed = false;
+		for (name, requirement) in &requirements {
+			if chosen.get(name).is_some_and(|(package, _)| requirement.matches(&package.version)) {
+				continue;
+			}
+
+			let locked = previous
+				.into_iter()
+				.flat_map(|lockfile| lockfile.packages.iter())
+				.find(|p| normalize_package_name(&p.name) == *name && requirement.matches(&p.version));
+			let package = match locked {
+				Some(locked) => locked.to_package(),
+				None => {
+					let (package_name, versions) = source.get_versions(&requirement.name)?;
+					match versions.into_iter().filter(|v| requirement.matches(v)).max() {
+						Some(version) => Package { name: package_name, version },
+						None => {
+							return error!(
+								"No version of {} matches {} (required by {}).",
+								package_name,
+								requirement.version,
+								requirement.required_by.join(", ")
+							)
+						}
+					}
+				}
+			};
+			let dependencies = source.get_dependencies(&package, &config.python)?;
+			chosen.insert(name.clone(), (package, dependencies));
+			changed = true;
+		}
+		if changed {
+			continue;
+		}
+
+		let groups = propagate_groups(&roots, |name| {
+			chosen
+				.get(name)
+				.map(|(_, dependencies)| dependencies.iter().map(|dependency| dependency.name.clone()).collect())
+		});
+		let mut packages = Vec::new();
+		for (name, groups) in groups.unwrap_or_default() {
+			let (package, dependencies) = &chosen[&name];
+			let mut dependency_names: Vec<String> = dependencies.iter().map(|dependency| dependency.name.clone()).collect();
+			dependency_names.sort();
+			dependency_names.dedup();
+			packages.push(LockedPackage {
+				name: package.name.clone(),
+				version: package.version.clone(),
+				groups,
+				dependencies: dependency_names,
+			});
+		}
+		return Ok(Lockfile::new(packages));
+	}
+	return error!(
+		"Couldn't resolve the packages of the project after {} rounds, their requirements keep changing each other.",
+		MAX_RESOLUTION_ROUNDS
+	);
+}
+
+/// Collects the requirements on every package reachable from the config through the dependencies of the versions chosen so far.
+fn collect_requirements(
+	roots: &[Requirement],
+	chosen: &BTreeMap<String, (Package, Vec<Dependency>)>,
+) -> Result<BTreeMap<String, ResolverRequirement>, AnyError> {
+	let mut requirements: BTreeMap<String, ResolverRequirement> = BTreeMap::new();
+	let mut queue = Vec::new();
+	for root in roots {
+		let name = normalize_package_name(&root.name);
+		requirements.insert(
+			name.clone(),
+			ResolverRequirement {
+				name: root.name.clone(),
+				version: root.version.clone(),
+				excluded: Vec::new(),
+				required_by: vec![String::from(CONFIG_FILE_NAME)],
+			},
+		);
+		queue.push(name);
+	}
+
+	let mut visited = Vec::new();
+	while let Some(name) = queue.pop() {
+		if visited.contains(&name) {
+			continue;
+		}
+		visited.push(name.clone());
+		let (package, dependencies) = match chosen.get(&name) {
+			Some(chosen) => chosen,
+			None => continue, // Its version is chosen in this round, its dependencies are collected in the next one
+		};
+
+		for dependency in dependencies {
+			let (version, excluded) = match to_dependency_requirement(&dependency.specifier) {
+				Ok(requirement) => requirement,
+				Err(e) => {
+					return error!(
+						"{} {} requires {} {}, which pen can't resolve: {}",
+						package.name, package.version, dependency.name, dependency.specifier, e
+					)
+				}
+			};
+			let requirement = requirements.entry(dependency.name.clone()).or_insert_with(|| ResolverRequirement {
+				name: dependency.name.clone(),
+				version: VersionReq::STAR,
+				excluded: Vec::new(),
+				required_by: Vec::new(),
+			});
+			requirement.version.comparators.extend(version.comparators);
+			requirement.excluded.extend(excluded);
+			requirement.required_by.push(format!("{} {}", package.name, package.version));
+			queue.push(dependency.name.clone());
+		}
+	}
+	return Ok(requirements);
+}
+
+/// Translates the PEP 440 version specifier of a dependency, ex. `>=1.5.6,!=1.5.7`, to a version requirement and the excluded versions.
+fn to_dependency_requirement(specifier: &str) -> Result<(VersionReq, Vec<VersionReq>), String> {
+	let mut clauses = Vec::new();
+	let mut excluded = Vec::new();
+	for clause in specifier.split(',').map(str::trim) {
+		match clause.strip_prefix("!=") {
+			// `!=1.5.*` excludes every 1.5 version, like the `=1.5` requirement matches them
+			Some(version) => {
+				let version = version.trim().trim_end_matches(".*");
+				match VersionReq::parse(&format!("={}", version)) {
+					Ok(version) => excluded.push(version),
+					Err(_) => return Err(format!("unsupported version in `{}`", clause)),
+				}
+			}
+			None => clauses.push(clause),
+		}
+	}
+	let version = to_version_requirement(&clauses.join(","))?;
+	return match VersionReq::parse(&version) {
+		Ok(version) => Ok((version, excluded)),
+		Err(e) => Err(e.to_string()),
+	};
+}
+
+/// Gives every package reachable from the config the groups of the packages of the config leading to it.
+///
+/// # Arguments
+/// - `roots`: The requirements of the config.
+/// - `get_dependencies`: The normalized names of the dependencies of a package, `None` if it isn't known.
+///
+/// # Output
+/// - The groups of each reachable package by normalized name, `main` first. `None` if a reachable package isn't known.
+fn propagate_groups(roots: &[Requirement], get_dependencies: impl Fn(&str) -> Option<Vec<String>>) -> Option<BTreeMap<String, Vec<String>>> {
+	let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+	for root in roots {
+		let mut queue = vec![normalize_package_name(&root.name)];
+		while let Some(name) = queue.pop() {
+			let dependencies = get_dependencies(&name)?;
+			let package_groups = groups.entry(name).or_default();
+			let new_groups: Vec<String> = root.groups.iter().filter(|group| !package_groups.contains(group)).cloned().collect();
+			// A package that already has every group of this root passed them on to its dependencies too
+			if new_groups.is_empty() {
+				continue;
+			}
+			package_groups.extend(new_groups);
+			package_groups.sort_by_key(|group| (group != MAIN_GROUP, group.clone()));
+			queue.extend(dependencies);
+		}
+	}
+	return Some(groups);
+}
+
+/// Checks if a lockfile still matches the config, without contacting PyPI.
+///
+/// # Output
+/// - `true` if the lockfile is of the current version, every package of the config is locked to a version satisfying its
+///   requirements, every locked dependency of a locked package is locked too, every package has the groups leading to it,
+///   and no other package is locked.
+///
+/// # Limitations
+/// - The requirements of the locked packages on their dependencies aren't checked again, they were when the lockfile was resolved.
+pub fn is_lockfile_up_to_date(config: &Config, lockfile: &Lockfile) -> Result<bool, AnyError> {
+	if lockfile.version != LOCKFILE_VERSION {
+		return Ok(false);
+	}
+	let requirements = get_requirements(config)?;
+	let find = |name: &str| lockfile.packages.iter().find(|p| normalize_package_name(&p.name) == name);
+
+	for requirement in &requirements {
+		match find(&normalize_package_name(&requirement.name)) {
+			Some(locked) if requirement.version.matches(&locked.version) => {}
+			_ => return Ok(false),
+		}
+	}
+	let groups = match propagate_groups(&requirements, |name| find(name).map(|locked| locked.dependencies.clone())) {
+		Some(groups) => groups,
+		None => return Ok(false),
+	};
+	if groups.len() != lockfile.packages.len() {
+		return Ok(false);
+	}
+	for locked in &lockfile.packages {
+		if groups.get(&normalize_package_name(&locked.name)) != Some(&locked.groups) {
+			return Ok(false);
+		}
+	}
+	return Ok(true);
+}
+
+/// Resolves the lockfile of the project and writes it if it changed, printing the version changes.
+///
+/// # Output
+/// - The up to date lockfile.
+pub fn update_lockfile(project_path: &Path, config: &Config) -> Result<Lockfile, AnyError> {
+	let previous = read_lockfile(project_path)?;
+	let lockfile = resolve_lockfile(config, previous.as_ref())?;
+	if previous.as_ref() != Some(&lockfile) {
+		print_lockfile_diff(previous.as_ref(), &lockfile);
+		write_lockfile(project_path, &lockfile)?;
+	}
+	return Ok(lockfile);
+}
+
+/// Selects the groups to install or export: `[packages]`, the `dev` group unless `no_dev`, and `groups`.
+///
+/// # Termination
+/// - This function returns an error if one of `groups` doesn't exist in the config.
+pub fn select_groups(config: &Config, groups: Vec<String>, no_dev: bool) -> Result<Vec<String>, AnyError> {
+	let mut selected_groups = vec![String::from(MAIN_GROUP)];
+	if !no_dev && config.dependency_groups.contains_key(DEV_GROUP) {
+		selected_groups.push(String::from(DEV_GROUP));
+	}
+	for group in groups {
+		if !config.dependency_groups.contains_key(&group) {
+			return error!("No dependency group named {} in {}", group, CONFIG_FILE_NAME);
+		}
+		if !selected_groups.contains(&group) {
+			selected_groups.push(group);
+		}
+	}
+	return Ok(selected_groups);
+}
+
+/// Gets the locked packages needed by any of `groups`.
+pub fn get_group_packages(lockfile: &Lockfile, groups: &[String]) -> Vec<Package> {
+	return lockfile
+		.packages
+		.iter()
+		.filter(|p| p.groups.iter().any(|group| groups.contains(group)))
+		.map(|p| p.to_package())
+		.collect();
+}
+
+/// Prints the version changes between two lockfiles, ex. `~ requests 2.31.0 -> 2.32.3`.
+///
+/// # Output
+/// - `true` if any version changed.
+pub fn print_lockfile_diff(previous: Option<&Lockfile>, lockfile: &Lockfile) -> bool {
+	let previous_packages = match previous {
+		Some(previous) => previous.packages.as_slice(),
+		None => &[],
+	};
+	let find = |packages: &[LockedPackage], name: &str| {
+		let name = normalize_package_name(name);
+		packages
+			.iter()
+			.find(|p| normalize_package_name(&p.name) == name)
+			.map(|p| p.version.clone())
+	};
+
+	let mut changed = false;
+	for package in &lockfile.packages {
+		match find(previous_packages, &package.name) {
+			Some(version) if version == package.version => {}
+			Some(version) => {
+				eprintln!("~ {} {} -> {}", package.name, version, package.version);
+				changed = true;
+			}
+			None => {
+				eprintln!("+ {} {}", package.name, package.version);
+				changed = true;
+			}
+		}
+	}
+	for package in previous_packages {
+		if find(&lockfile.packages, &package.name).is_none() {
+			eprintln!("- {} {}", package.name, package.version);
+			changed = true;
+		}
+	}
+	return changed;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils::parse_release_version;
+
+	/// An index of packages, each version with its `Requires-Dist` values.
+	struct FakeSource {
+		packages: Vec<(&'static str, &'static str, Vec<&'static str>)>,
+	}
+
+	impl PackageSource for FakeSource {
+		fn get_versions(&self, name: &str) -> Result<(String, Vec<Version>), AnyError> {
+			let versions: Vec<Version> = self
+				.packages
+				.iter()
+				.filter(|(package_name, _, _)| normalize_package_name(package_name) == normalize_package_name(name))
+				.filter_map(|(_, version, _)| parse_release_version(version))
+				.collect();
+			if versions.is_empty() {
+				return error!("No package named {}.", name);
+			}
+			return Ok((String::from(name), versions));
+		}
+
+		fn get_dependencies(&self, package: &Package, python: &PythonSpec) -> Result<Vec<Dependency>, AnyError> {
+			let (_, _, requires_dist) = self
+				.packages
+				.iter()
+				.find(|(name, version, _)| *name == package.name && parse_release_version(version).as_ref() == Some(&package.version))
+				.unwrap();
+			let mut dependencies = Vec::new();
+			for value in requires_dist {
+				let (requirement, marker) = match value.split_once(';') {
+					Some((requirement, marker)) => (requirement.trim(), Some(marker.trim())),
+					None => (value.trim(), None),
+				};
+				if marker.is_some_and(|marker| !crate::utils::evaluate_marker(marker, python).unwrap()) {
+					continue;
+				}
+				let name_end = requirement.find(|c: char| "<>=!~ ".contains(c)).unwrap_or(requirement.len());
+				dependencies.push(Dependency {
+					name: normalize_package_name(&requirement[..name_end]),
+					specifier: String::from(requirement[name_end..].trim()),
+					marker: marker.map(String::from),
+				});
+			}
+			return Ok(dependencies);
+		}
+	}
+
+	fn source() -> FakeSource {
+		return FakeSource {
+			packages: vec![
+				("requests", "2.31.0", vec!["urllib3<3,>=1.21.1", "idna>=2.5"]),
+				(
+					"requests",
+					"2.32.3",
+					vec!["urllib3<3,>=1.21.1", "idna>=2.5", "PySocks!=1.5.7,>=1.5.6; extra == \"socks\""],
+				),
+				("urllib3", "1.26.20", vec![]),
+				("urllib3", "2.2.3", vec![]),
+				("urllib3", "3.0.0", vec![]),
+				("idna", "3.7", vec![]),
+				("idna", "3.10", vec![]),
+				("idna", "4.0rc1", vec![]),
+				("pysocks", "1.7.1", vec![]),
+				("pytest", "8.3.3", vec!["iniconfig", "colorama; sys_platform == \"win32\"", "urllib3<2"]),
+				("iniconfig", "2.0.0", vec![]),
+				("colorama", "0.4.6", vec![]),
+			],
+		};
+	}
+
+	fn config(packages: &[(&str, &str)], dev: &[(&str, &str)]) -> Config {
+		let table = |packages: &[(&str, &str)]| -> toml::Table {
+			packages
+				.iter()
+				.map(|(name, version)| (String::from(*name), toml::Value::String(String::from(*version))))
+				.collect()
+		};
+		let mut dependency_groups = BTreeMap::new();
+		if !dev.is_empty() {
+			dependency_groups.insert(String::from(DEV_GROUP), table(dev));
+		}
+		return Config {
+			python: PythonSpec::parse("3.12.4").unwrap(),
+			env_files: Vec::new(),
+			packages: table(packages),
+			dependency_groups,
+			scripts: BTreeMap::new(),
+		};
+	}
+
+	fn locked(lockfile: &Lockfile) -> Vec<String> {
+		return lockfile
+			.packages
+			.iter()
+			.map(|p| format!("{} {} {}", p.name, p.version, p.groups.join(",")))
+			.collect();
+	}
+
+	#[test]
+	fn dependencies_are_locked_with_the_groups_leading_to_them() {
+		let lockfile = resolve_lockfile_from(&config(&[("requests", "*")], &[("pytest", "*")]), None, &source()).unwrap();
+		// colorama is only for Windows, PySocks only for the socks extra, and pytest needs an older urllib3
+		assert_eq!(
+			locked(&lockfile),
+			vec![
+				"idna 3.10.0 main",
+				"iniconfig 2.0.0 dev",
+				"pytest 8.3.3 dev",
+				"requests 2.32.3 main",
+				"urllib3 1.26.20 main,dev",
+			]
+		);
+		assert_eq!(lockfile.packages[3].dependencies, vec!["idna", "urllib3"]);
+	}
+
+	#[test]
+	fn locked_versions_are_kept() {
+		let previous = resolve_lockfile_from(&config(&[("requests", "<2.32")], &[]), None, &source()).unwrap();
+		let lockfile = resolve_lockfile_from(&config(&[("requests", "*")], &[]), Some(&previous), &source()).unwrap();
+		assert_eq!(locked(&lockfile), vec!["idna 3.10.0 main", "requests 2.31.0 main", "urllib3 2.2.3 main"]);
+	}
+
+	#[test]
+	fn short_release_versions_are_locked() {
+		// PyPI releases like `3.10` aren't semver until padded, `4.0rc1` still isn't and is left out
+		let lockfile = resolve_lockfile_from(&config(&[("idna", ">=3.8")], &[]), None, &source()).unwrap();
+		assert_eq!(locked(&lockfile), vec!["idna 3.10.0 main"]);
+	}
+
+	#[test]
+	fn conflicts_are_errors() {
+		let result = resolve_lockfile_from(&config(&[("urllib3", ">=2")], &[("pytest", "*")]), None, &source());
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn up_to_date_lockfiles() {
+		let config = config(&[("requests", "*")], &[("pytest", "*")]);
+		let lockfile = resolve_lockfile_from(&config, None, &source()).unwrap();
+		assert!(is_lockfile_up_to_date(&config, &lockfile).unwrap());
+
+		let mut missing_dependency = Lockfile::new(lockfile.packages.clone());
+		missing_dependency.packages.retain(|p| p.name != "idna");
+		assert!(!is_lockfile_up_to_date(&config, &missing_dependency).unwrap());
+
+		let mut wrong_groups = Lockfile::new(lockfile.packages.clone());
+		wrong_groups.packages[4].groups = vec![String::from(MAIN_GROUP)];
+		assert!(!is_lockfile_up_to_date(&config, &wrong_groups).unwrap());
+
+		let old_version = Lockfile {
+			version: 1,
+			packages: lockfile.packages.clone(),
+		};
+		assert!(!is_lockfile_up_to_date(&config, &old_version).unwrap());
+	}
+
+	#[test]
+	fn dependency_specifiers() {
+		let (version, excluded) = to_dependency_requirement("!=1.5.7,>=1.5.6").unwrap();
+		assert!(version.matches(&Version::new(1, 5, 7)) && excluded.iter().any(|e| e.matches(&Version::new(1, 5, 7))));
+		assert_eq!(version.to_string(), ">=1.5.6");
+		let (_, excluded) = to_dependency_requirement("!=2.0.*").unwrap();
+		assert!(excluded[0].matches(&Version::new(2, 0, 3)) && !excluded[0].matches(&Version::new(2, 1, 0)));
+		assert_eq!(to_dependency_requirement("").unwrap().0, VersionReq::STAR);
+		assert!(to_dependency_requirement(">=2.0rc1").is_err());
+	}
+}
@@ -0,0 +1,331 @@
+use std::env::consts::{ARCH, OS};
+
+use crate::utils::{PythonImplementation, PythonSpec};
+
+/// Evaluates a PEP 508 environment marker for the Python of a project on this machine, ex. `sys_platform == "win32"`.
+///
+/// # Arguments
+/// - `marker`: The marker, without the `;` separating it from the requirement.
+/// - `python`: The Python of the project.
+///
+/// # Output
+/// - Whether the requirement applies. `extra` is always empty, since pen doesn't install extras.
+///
+/// # Termination
+/// - This function returns an error for invalid markers, and for variables pen can't know, ex. `platform_release`.
+pub fn evaluate_marker(marker: &str, python: &PythonSpec) -> Result<bool, String> {
+	let tokens = tokenize_marker(marker)?;
+	let mut parser = MarkerParser {
+		tokens: &tokens,
+		position: 0,
+		python,
+	};
+	let result = parser.parse_or()?;
+	if parser.position != tokens.len() {
+		return Err(format!(
+			"unexpected `{}` in the environment marker `{}`",
+			tokens[parser.position].text(),
+			marker
+		));
+	}
+	return Ok(result);
+}
+
+#[derive(PartialEq)]
+enum Token {
+	Word(String),
+	Quoted(String),
+	Operator(String),
+	Open,
+	Close,
+}
+
+impl Token {
+	fn text(&self) -> String {
+		return match self {
+			Token::Word(word) | Token::Operator(word) => word.clone(),
+			Token::Quoted(value) => format!("\"{}\"", value),
+			Token::Open => String::from("("),
+			Token::Close => String::from(")"),
+		};
+	}
+}
+
+fn tokenize_marker(marker: &str) -> Result<Vec<Token>, String> {
+	let mut tokens = Vec::new();
+	let mut chars = marker.char_indices().peekable();
+	while let Some((start, c)) = chars.next() {
+		match c {
+			c if c.is_whitespace() => {}
+			'(' => tokens.push(Token::Open),
+			')' => tokens.push(Token::Close),
+			'"' | '\'' => {
+				let end = match marker[start + 1..].find(c) {
+					Some(end) => start + 1 + end,
+					None => return Err(format!("unclosed quote in the environment marker `{}`", marker)),
+				};
+				tokens.push(Token::Quoted(String::from(&marker[start + 1..end])));
+				while chars.next_if(|(i, _)| *i <= end).is_some() {}
+			}
+			'<' | '>' | '=' | '!' | '~' => {
+				let mut operator = String::from(c);
+				while let Some((_, next)) = chars.next_if(|(_, next)| "<>=!~".contains(*next)) {
+					operator.push(next);
+				}
+				tokens.push(Token::Operator(operator));
+			}
+			c if c.is_ascii_alphanumeric() || c == '_' || c == '.' => {
+				let mut word = String::from(c);
+				while let Some((_, next)) = chars.next_if(|(_, next)| next.is_ascii_alphanumeric() || *next == '_' || *next == '.') {
+					word.push(next);
+				}
+				tokens.push(Token::Word(word));
+			}
+			c => return Err(format!("unexpected `{}` in the environment marker `{}`", c, marker)),
+		}
+	}
+	return Ok(tokens);
+}
+
+struct MarkerParser<'a> {
+	tokens: &'a [Token],
+	position: usize,
+	python: &'a PythonSpec,
+}
+
+impl MarkerParser<'_> {
+	fn next_is_word(&self, word: &str) -> bool {
+		return matches!(self.tokens.get(self.position), Some(Token::Word(w)) if w == word);
+	}
+
+	// `and` binds tighter than `or`, both evaluate every side so that errors aren't hidden by short-circuiting
+	fn parse_or(&mut self) -> Result<bool, String> {
+		let mut result = self.parse_and()?;
+		while self.next_is_word("or") {
+			self.position += 1;
+			result |= self.parse_and()?;
+		}
+		return Ok(result);
+	}
+
+	fn parse_and(&mut self) -> Result<bool, String> {
+		let mut result = self.parse_atom()?;
+		while self.next_is_word("and") {
+			self.position += 1;
+			result &= self.parse_atom()?;
+		}
+		return Ok(result);
+	}
+
+	fn parse_atom(&mut self) -> Result<bool, String> {
+		if self.tokens.get(self.position) == Some(&Token::Open) {
+			self.position += 1;
+			let result = self.parse_or()?;
+			if self.tokens.get(self.position) != Some(&Token::Close) {
+				return Err(String::from("missing `)` in the environment marker"));
+			}
+			self.position += 1;
+			return Ok(result);
+		}
+
+		let left = self.parse_value()?;
+		let operator = match self.tokens.get(self.position) {
+			Some(Token::Operator(operator)) => operator.clone(),
+			Some(Token::Word(word)) if word == "in" => String::from("in"),
+			Some(Token::Word(word)) if word == "not" && matches!(self.tokens.get(self.position + 1), Some(Token::Word(w)) if w == "in") => {
+				self.position += 1;
+				String::from("not in")
+			}
+			Some(token) => return Err(format!("expected an operator in the environment marker, found `{}`", token.text())),
+			None => return Err(String::from("the environment marker ends too early")),
+		};
+		self.position += 1;
+		let right = self.parse_value()?;
+
+		return match (&left, &right) {
+			(MarkerValue::Variable(variable), MarkerValue::Literal(value)) => self.compare(variable, &operator, value, false),
+			(MarkerValue::Literal(value), MarkerValue::Variable(variable)) => self.compare(variable, &operator, value, true),
+			_ => Err(String::from("environment markers compare a variable to a quoted value")),
+		};
+	}
+
+	fn parse_value(&mut self) -> Result<MarkerValue, String> {
+		let value = match self.tokens.get(self.position) {
+			Some(Token::Quoted(value)) => MarkerValue::Literal(value.clone()),
+			Some(Token::Word(word)) => MarkerValue::Variable(word.clone()),
+			Some(token) => return Err(format!("expected a value in the environment marker, found `{}`", token.text())),
+			None => return Err(String::from("the environment marker ends too early")),
+		};
+		self.position += 1;
+		return Ok(value);
+	}
+
+	/// Compares the value of `variable` to `value`, `reversed` when the variable is on the right, ex. `"3.8" > python_version`.
+	fn compare(&self, variable: &str, operator: &str, value: &str, reversed: bool) -> Result<bool, String> {
+		let actual = match get_marker_variable(variable, self.python) {
+			Some(actual) => actual,
+			None => return Err(format!("the environment marker variable `{}` can't be evaluated by pen", variable)),
+		};
+		let (left, right) = match reversed {
+			true => (value, actual.as_str()),
+			false => (actual.as_str(), value),
+		};
+
+		match operator {
+			"in" => return Ok(right.contains(left)),
+			"not in" => return Ok(!right.contains(left)),
+			"===" => return Ok(left == right),
+			_ => {}
+		}
+		let is_version = matches!(variable, "python_version" | "python_full_version");
+		if !is_version {
+			return match operator {
+				"==" => Ok(left == right),
+				"!=" => Ok(left != right),
+				_ => Err(format!("`{}` can only compare versions, not `{}`", operator, variable)),
+			};
+		}
+
+		let parse = |version: &str| -> Result<Vec<u64>, String> {
+			return version
+				.trim_end_matches(".*")
+				.split('.')
+				.map(|part| {
+					part.parse::<u64>()
+						.map_err(|_| format!("unsupported version `{}` in an environment marker", version))
+				})
+				.collect();
+		};
+		let (mut left_parts, mut right_parts) = (parse(left)?, parse(right)?);
+		// `== "3.*"` matches every version starting with 3, ex. 3.12
+		if let Some(prefix) = value.strip_suffix(".*") {
+			let prefix_length = prefix.split('.').count();
+			let version = match reversed {
+				true => &mut right_parts,
+				false => &mut left_parts,
+			};
+			version.truncate(prefix_length);
+		}
+		let length = left_parts.len().max(right_parts.len());
+		left_parts.resize(length, 0);
+		right_parts.resize(length, 0);
+
+		return match operator {
+			"<=" => Ok(left_parts <= right_parts),
+			">=" => Ok(left_parts >= right_parts),
+			"==" => Ok(left_parts == right_parts),
+			"!=" => Ok(left_parts != right_parts),
+			"<" => Ok(left_parts < right_parts),
+			">" => Ok(left_parts > right_parts),
+			// `~= "3.8"` is `>= 3.8, == 3.*`
+			"~=" if !reversed && right.split('.').count() >= 2 => {
+				let prefix_length = right.split('.').count() - 1;
+				Ok(left_parts >= right_parts && left_parts[..prefix_length] == right_parts[..prefix_length])
+			}
+			_ => Err(format!("unsupported operator `{}` in an environment marker", operator)),
+		};
+	}
+}
+
+enum MarkerValue {
+	Variable(String),
+	Literal(String),
+}
+
+/// Gets the value of an environment marker variable, `None` for the ones that depend on more than the Python and the platform.
+fn get_marker_variable(variable: &str, python: &PythonSpec) -> Option<String> {
+	let value = match variable {
+		"python_version" => python.short_version(),
+		"python_full_version" => python.version.to_string(),
+		"implementation_name" => String::from(python.implementation.name()),
+		"platform_python_implementation" => String::from(match python.implementation {
+			PythonImplementation::CPython => "CPython",
+			PythonImplementation::PyPy => "PyPy",
+			PythonImplementation::GraalPy => "GraalVM",
+		}),
+		"sys_platform" => String::from(match OS {
+			"macos" => "darwin",
+			"windows" => "win32",
+			os => os,
+		}),
+		"platform_system" => String::from(match OS {
+			"linux" => "Linux",
+			"macos" => "Darwin",
+			"windows" => "Windows",
+			"freebsd" => "FreeBSD",
+			os => os,
+		}),
+		"os_name" => String::from(match OS {
+			"windows" => "nt",
+			_ => "posix",
+		}),
+		"platform_machine" => String::from(match (OS, ARCH) {
+			("macos", "aarch64") => "arm64",
+			(_, arch) => arch,
+		}),
+		"extra" => String::new(),
+		_ => return None,
+	};
+	return Some(value);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn evaluate(marker: &str) -> Result<bool, String> {
+		return evaluate_marker(marker, &PythonSpec::parse("3.12.4").unwrap());
+	}
+
+	#[test]
+	fn python_versions() {
+		assert_eq!(evaluate("python_version >= \"3.8\""), Ok(true));
+		assert_eq!(evaluate("python_version < '3.12'"), Ok(false));
+		assert_eq!(evaluate("\"3.13\" > python_version"), Ok(true));
+		assert_eq!(evaluate("python_full_version == \"3.12.4\""), Ok(true));
+		assert_eq!(evaluate("python_version == \"3.*\""), Ok(true));
+		assert_eq!(evaluate("python_version ~= \"3.10\""), Ok(true));
+		assert_eq!(evaluate("python_full_version ~= \"3.11.2\""), Ok(false));
+	}
+
+	#[test]
+	fn platforms() {
+		let linux = cfg!(target_os = "linux");
+		assert_eq!(evaluate("sys_platform == \"win32\""), Ok(false));
+		assert_eq!(evaluate("platform_system == 'Windows' or os_name == 'nt'"), Ok(false));
+		assert_eq!(evaluate("sys_platform == \"linux\""), Ok(linux));
+		assert_eq!(evaluate("'linux' in sys_platform"), Ok(linux));
+		assert_eq!(evaluate("sys_platform not in 'win32 cygwin'"), Ok(true));
+		assert_eq!(
+			evaluate("implementation_name == \"cpython\" and platform_python_implementation != 'PyPy'"),
+			Ok(true)
+		);
+	}
+
+	#[test]
+	fn extras_are_never_installed() {
+		assert_eq!(evaluate("extra == \"socks\""), Ok(false));
+		assert_eq!(evaluate("python_version >= \"3.8\" and extra == 'test'"), Ok(false));
+	}
+
+	#[test]
+	fn precedence_and_parentheses() {
+		assert_eq!(
+			evaluate("python_version < '3.8' or python_version >= '3.12' and os_name == 'posix'"),
+			Ok(true)
+		);
+		assert_eq!(
+			evaluate("(python_version < '3.8' or python_version >= '3.12') and os_name == 'nt'"),
+			Ok(false)
+		);
+	}
+
+	#[test]
+	fn invalid_markers() {
+		assert!(evaluate("platform_release >= '5.0'").is_err());
+		assert!(evaluate("sys_platform > 'linux'").is_err());
+		assert!(evaluate("(python_version >= '3.8'").is_err());
+		assert!(evaluate("python_version >= '3.8' garbage").is_err());
+		assert!(evaluate("python_version >= \"3.8").is_err());
+	}
+}
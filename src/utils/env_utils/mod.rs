@@ -1,7 +1,33 @@
+mod activate_scripts;
+mod cache;
 mod config;
+mod config_schema;
+mod dependencies;
+mod dotenv;
+mod import;
+mod installed;
+mod link;
+mod lockfile;
+mod marker;
 mod package;
+mod pyproject;
+mod registry;
+mod scripts;
 mod virtual_env;
 
+pub use activate_scripts::*;
+pub use cache::*;
 pub use config::*;
+pub use config_schema::*;
+pub use dependencies::*;
+pub use dotenv::*;
+pub use import::*;
+pub use installed::*;
+pub use link::*;
+pub use lockfile::*;
+pub use marker::*;
 pub use package::*;
+pub use pyproject::*;
+pub use registry::*;
+pub use scripts::*;
 pub use virtual_env::*;
@@ -1,4 +1,5 @@
-use crate::utils::{self, error, guard, AnyError};
+use crate::constants::TMP_DIR;
+use crate::utils::{self, error, guard, AnyError, PythonSpec};
 use semver::{Version, VersionReq};
 use serde::Deserialize;
 use std::{
@@ -10,11 +11,16 @@ use std::{
 use zip::ZipArchive;
 
 // todo docstring
-pub fn download_package(package: &Package, py_version: &Version) -> Result<(), AnyError> {
-	println!("Downloading: {} v{}", package.name, package.version);
-	let url = match find_package_download_url(package, py_version)? {
+pub fn download_package(package: &Package, python: &PythonSpec) -> Result<(), AnyError> {
+	eprintln!("Downloading: {} v{}", package.name, package.version);
+	let url = match find_package_download_url(package, python)? {
 		Some(url) => url,
-		None => return error!("This package doesn't seem compatible with your os."),
+		None => {
+			return error!(
+				"{} {} has no wheel for {} {}, and pen can't build source distributions.",
+				package.name, package.version, OS, ARCH
+			)
+		}
 	};
 	let request = minreq::get(&url).with_header("Accept", "application/json");
 	let response = guard!(request.send(), "Couldn't request PyPi");
@@ -26,7 +32,10 @@ pub fn download_package(package: &Package, py_version: &Version) -> Result<(), A
 	let buffer = response.into_bytes();
 	let mut zip = guard!(ZipArchive::new(Cursor::new(buffer)), "Couldn't uncompress {}.", package.name);
 
-	let extract_dir = utils::get_package_path(&package);
+	// Extracted aside and moved in place at the end, so that the cache never holds a partially extracted package
+	let package_path = utils::get_package_path(package);
+	let extract_dir = TMP_DIR.join("temp_package_extract");
+	guard!(utils::try_deleting_dir(&extract_dir), "Failed to delete {}", extract_dir.display());
 	guard!(fs::create_dir_all(&extract_dir), "Couldn't create folder.");
 	for i in 0..zip.len() {
 		let mut file = zip.by_index(i).expect("File count changed while iterating.");
@@ -43,11 +52,30 @@ pub fn download_package(package: &Package, py_version: &Version) -> Result<(), A
 			guard!(copy(&mut file, &mut out_file), "Couldn't write {} to disk.", out_path.display());
 		}
 	}
+	guard!(fs::rename(&extract_dir, &package_path), "Couldn't move {} into the cache.", package.name);
 	return Ok(());
 }
 
 // todo docstring
 pub fn find_matching_package_version(name: &str, version_requirements: &VersionReq) -> Result<Package, AnyError> {
+	let (name, versions) = get_package_versions(name)?;
+	let best_version = match versions.into_iter().filter(|v| version_requirements.matches(v)).max() {
+		Some(version) => version,
+		None => return error!("No version matched {} for package {}.", version_requirements, name),
+	};
+
+	return Ok(Package { name, version: best_version });
+}
+
+/// Gets every release of a package on PyPI.
+///
+/// # Output
+/// - The name of the package as written on PyPI, and its versions that have files, in no particular order.
+///   Releases with fewer than 3 parts are padded with zeros (ex. `3.10` is `3.10.0`), those that still aren't semver (ex. `2.0rc1`) are left out.
+///
+/// # Termination
+/// - This function returns an error if PyPI can't be reached or doesn't know the package.
+pub fn get_package_versions(name: &str) -> Result<(String, Vec<Version>), AnyError> {
 	let url = format!("https://pypi.org/pypi/{}/json", name);
 	let request = minreq::get(&url).with_header("Accept", "application/json");
 	let response = guard!(request.send(), "Couldn't request PyPi");
@@ -58,28 +86,47 @@ pub fn find_matching_package_version(name: &str, version_requirements: &VersionR
 	// Parse the response as JSON if expected
 	let json = guard!(response.json::<ApiPackageResponse>(), "Received an invalid response from PyPi.");
 
-	let best_version = match json
+	let versions = json
 		.releases
 		.iter()
-		.filter_map(|(version, vec)| {
-			Some(version)
-				.filter(|_| !vec.is_empty())
-				.and_then(|v| Version::parse(&v).ok().filter(|v| version_requirements.matches(&v)))
+		.filter(|(_, files)| !files.is_empty())
+		.filter_map(|(version, _)| parse_release_version(version))
+		.collect();
+	return Ok((json.info.name, versions));
+}
+
+/// Gets the files PyPI has for a version of a package, its wheels and source distributions.
+/// Other kinds of files, ex. eggs or Windows installers, are left out, pip doesn't install them.
+///
+/// # Termination
+/// - This function returns an error if PyPI can't be reached or doesn't know the version.
+pub fn get_package_files(package: &Package) -> Result<Vec<PackageFile>, AnyError> {
+	let json = request_package_version(package)?;
+	let files = json
+		.urls
+		.into_iter()
+		.filter(|file| file.packagetype == "bdist_wheel" || file.packagetype == "sdist")
+		.map(|file| PackageFile {
+			is_wheel: file.packagetype == "bdist_wheel",
+			filename: file.filename,
+			url: file.url,
+			sha256: file.digests.sha256,
+			size: file.size,
 		})
-		.max()
-	{
-		Some(version) => version,
-		None => todo!("No version matched {} for package {}.", version_requirements, name),
-	};
+		.collect();
+	return Ok(files);
+}
 
-	return Ok(Package {
-		name: String::from(&json.info.name), // todo why is this not just name?
-		version: best_version,
-	});
+/// Gets the dependencies PyPI lists for a version of a package, as `Requires-Dist` values, ex. `urllib3<3,>=1.21.1`.
+///
+/// # Termination
+/// - This function returns an error if PyPI can't be reached or doesn't know the version.
+pub fn get_package_requires_dist(package: &Package) -> Result<Vec<String>, AnyError> {
+	let json = request_package_version(package)?;
+	return Ok(json.info.requires_dist.unwrap_or_default());
 }
 
-// todo docstring
-fn find_package_download_url(package: &Package, py_version: &Version) -> Result<Option<String>, AnyError> {
+fn request_package_version(package: &Package) -> Result<ApiPackageVersionResponse, AnyError> {
 	let url = format!("https://pypi.org/pypi/{}/{}/json", package.name, package.version);
 	let request = minreq::get(&url).with_header("Accept", "application/json");
 	let response = guard!(request.send(), "Couldn't request PyPi");
@@ -89,44 +136,168 @@ fn find_package_download_url(package: &Package, py_version: &Version) -> Result<
 
 	// Parse the response as JSON if expected
 	let json = guard!(response.json::<ApiPackageVersionResponse>(), "Received an invalid response from PyPi");
+	return Ok(json);
+}
 
-	let python = format!("py{}{}", py_version.major, py_version.minor);
-	let arch = match OS {
-		"macos" if ARCH == "aarch64" => "arm64",
-		_ => ARCH,
-	};
+// todo docstring
+fn find_package_download_url(package: &Package, python: &PythonSpec) -> Result<Option<String>, AnyError> {
+	let json = request_package_version(package)?;
+	let filenames: Vec<&str> = json.urls.iter().map(|p| p.filename.as_str()).collect();
+	let url = select_wheel(&filenames, python, OS, ARCH, is_musl())
+		.and_then(|filename| json.urls.iter().find(|p| p.filename == filename))
+		.map(|p| p.url.clone());
+	return Ok(url);
+}
 
-	let idk = json
-		.urls
+/// Picks the wheel to install among the files of a release.
+///
+/// # Arguments
+/// - `os`, `arch`: The running system, as in `std::env::consts`, ex. `linux` and `x86_64`.
+/// - `is_musl`: Whether the C library of the system is musl rather than glibc.
+///
+/// # Output
+/// - The wheel built for the ABI of the interpreter (ex. not a cp310 wheel in PyPy) and for the platform, preferring the
+///   exact python tag and then the most specific platform tag. `None` if there is none, pen can't build source distributions.
+fn select_wheel<'a>(filenames: &[&'a str], python: &PythonSpec, os: &str, arch: &str, is_musl: bool) -> Option<&'a str> {
+	let python_tag = python.wheel_python_tag();
+	return filenames
 		.iter()
-		.max_by(|a, b| {
-			let a = a.url.split("-").collect::<Vec<&str>>();
-			let b = b.url.split("-").collect::<Vec<&str>>();
-
-			(a.len() > 3).cmp(&(b.len() > 3)).then_with(|| {
-				let wheel = a[a.len() - 1].ends_with(".whl").cmp(&b[b.len() - 1].ends_with(".whl"));
-				let python = a[a.len() - 3].contains(&python).cmp(&b[b.len() - 3].contains(&python));
-				let arch = a[a.len() - 1].contains(arch).cmp(&b[b.len() - 1].contains(arch));
-				let os = a[a.len() - 1].contains(OS).cmp(&b[b.len() - 1].contains(OS));
-
-				wheel.then(python).then(arch).then(os)
-			})
+		.filter_map(|filename| parse_wheel_tags(filename).map(|tags| (*filename, tags)))
+		.filter(|(_, (python_tags, abi_tags, _))| {
+			python_tags
+				.iter()
+				.any(|python_tag| abi_tags.iter().any(|abi_tag| python.supports_wheel_tags(python_tag, abi_tag)))
 		})
-		.map(|p| p.url.clone());
-	return Ok(idk);
+		.filter_map(|(filename, (python_tags, _, platform_tags))| {
+			let platform_rank = platform_tags.iter().filter_map(|tag| rank_platform_tag(tag, os, arch, is_musl)).max()?;
+			return Some((filename, (python_tags.contains(&python_tag.as_str()), platform_rank)));
+		})
+		.max_by_key(|(_, rank)| *rank)
+		.map(|(filename, _)| filename);
+}
+
+/// Ranks a wheel platform tag for the running system, higher being more specific.
+///
+/// # Output
+/// - `None` if wheels with the tag don't run on the system, ex. `win_amd64` on Linux, or `musllinux` on a glibc Linux.
+fn rank_platform_tag(tag: &str, os: &str, arch: &str, is_musl: bool) -> Option<u8> {
+	if tag == "any" {
+		return Some(0);
+	}
+	match os {
+		"linux" => {
+			let arch = match arch {
+				"x86" => "i686",
+				"arm" => "armv7l",
+				"powerpc64" => "ppc64le",
+				arch => arch,
+			};
+			if !tag.ends_with(&format!("_{}", arch)) {
+				return None;
+			}
+			return match tag {
+				tag if tag.starts_with("manylinux") && !is_musl => Some(2),
+				tag if tag.starts_with("musllinux") && is_musl => Some(2),
+				tag if tag.starts_with("linux_") => Some(1),
+				_ => None,
+			};
+		}
+		"macos" => {
+			let arch = match arch {
+				"aarch64" => "arm64",
+				arch => arch,
+			};
+			return match tag {
+				tag if !tag.starts_with("macosx_") => None,
+				tag if tag.ends_with(&format!("_{}", arch)) => Some(2),
+				tag if tag.ends_with("_universal2") => Some(1),
+				_ => None,
+			};
+		}
+		"windows" => {
+			return match (tag, arch) {
+				("win_amd64", "x86_64") | ("win32", "x86") | ("win_arm64", "aarch64") => Some(2),
+				_ => None,
+			};
+		}
+		_ => return None,
+	}
 }
 
+/// Whether the C library of the system is musl (ex. Alpine), whose wheels are `musllinux` rather than `manylinux`.
+fn is_musl() -> bool {
+	let Ok(entries) = fs::read_dir("/lib") else {
+		return false;
+	};
+	return entries.flatten().any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"));
+}
+
+/// Splits the file name of a wheel into its python, ABI and platform tags, ex. `["py2", "py3"]`, `["none"]` and `["any"]`.
+///
+/// # Output
+/// - `None` if the file isn't a wheel, ex. a source distribution.
+fn parse_wheel_tags(filename: &str) -> Option<(Vec<&str>, Vec<&str>, Vec<&str>)> {
+	// {name}-{version}(-{build})?-{python tags}-{abi tags}-{platform tags}.whl, each tag set being separated by dots
+	let parts = filename.strip_suffix(".whl")?.split('-').collect::<Vec<&str>>();
+	if parts.len() != 5 && parts.len() != 6 {
+		return None;
+	}
+	let tags = &parts[parts.len() - 3..];
+	return Some((tags[0].split('.').collect(), tags[1].split('.').collect(), tags[2].split('.').collect()));
+}
+
+/// Normalizes a package name as PyPI does, so that `Typing_Extensions` and `typing-extensions` are the same package.
+pub fn normalize_package_name(name: &str) -> String {
+	let mut normalized = String::new();
+	for c in name.chars() {
+		match c {
+			'-' | '_' | '.' if normalized.ends_with('-') => {}
+			'-' | '_' | '.' => normalized.push('-'),
+			c => normalized.push(c.to_ascii_lowercase()),
+		}
+	}
+	return normalized;
+}
+
+/// Parses the version of a Python release, padding a missing minor or patch with zeros as pip does, ex. `3.10` is `3.10.0`.
+///
+/// # Output
+/// - `None` when the version isn't semver once padded, ex. `2.0rc1` or `1.0.0.post1`.
+pub fn parse_release_version(version: &str) -> Option<Version> {
+	let parts = version.split('.').count();
+	if parts < 3
+		&& version
+			.split('.')
+			.all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+	{
+		return Version::parse(&format!("{}{}", version, ".0".repeat(3 - parts))).ok();
+	}
+	return Version::parse(version).ok();
+}
+
+#[derive(Clone)]
 pub struct Package {
 	pub name: String,
 	pub version: Version,
 }
 
+/// A file of a package version on PyPI.
+pub struct PackageFile {
+	pub filename: String,
+	pub url: String,
+	pub sha256: String,
+	/// The size in bytes, when PyPI gives it.
+	pub size: Option<u64>,
+	/// Whether the file is a wheel rather than a source distribution.
+	pub is_wheel: bool,
+}
+
 // See: https://warehouse.pypa.io/api-reference/json.html#get--pypi--project_name--json
+// Only the fields pen reads are listed, serde ignores the others
 #[derive(Deserialize, Debug)]
 struct ApiPackageResponse {
 	info: PackageInfo,
 	releases: HashMap<String, Vec<PackageVersionUrl>>,
-	urls: Vec<PackageVersionUrl>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -138,29 +309,93 @@ struct ApiPackageVersionResponse {
 #[derive(Deserialize, Debug)]
 struct PackageInfo {
 	name: String,
-	summary: String,
-	version: String,
-	yanked: bool,
-	yanked_reason: Option<String>,
+	#[serde(default)]
+	requires_dist: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Debug)]
 struct PackageVersionUrl {
-	comment_text: String,
 	digests: UrlDigests,
 	filename: String,
-	md5_digest: String,
 	packagetype: String,
-	requires_python: Option<String>,
-	upload_time_iso_8601: String,
+	size: Option<u64>,
 	url: String,
-	yanked: bool,
-	yanked_reason: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 struct UrlDigests {
-	blake2b_256: String,
-	md5: String,
 	sha256: String,
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn wheel_tags_are_parsed() {
+		assert_eq!(
+			parse_wheel_tags("numpy-2.1.2-pp310-pypy310_pp73-manylinux_2_17_x86_64.manylinux2014_x86_64.whl"),
+			Some((vec!["pp310"], vec!["pypy310_pp73"], vec!["manylinux_2_17_x86_64", "manylinux2014_x86_64"]))
+		);
+		assert_eq!(
+			parse_wheel_tags("six-1.16.0-py2.py3-none-any.whl"),
+			Some((vec!["py2", "py3"], vec!["none"], vec!["any"]))
+		);
+		// With a build tag
+		assert_eq!(
+			parse_wheel_tags("pkg-1.0-1-cp38-abi3-win_amd64.whl"),
+			Some((vec!["cp38"], vec!["abi3"], vec!["win_amd64"]))
+		);
+		assert_eq!(parse_wheel_tags("six-1.16.0.tar.gz"), None);
+		assert_eq!(parse_wheel_tags("broken-name.whl"), None);
+	}
+
+	#[test]
+	fn wheels_are_selected_for_the_platform() {
+		let python = PythonSpec::parse("3.12.4").unwrap();
+		let numpy = [
+			"numpy-2.1.2-cp312-cp312-macosx_14_0_arm64.whl",
+			"numpy-2.1.2-cp312-cp312-musllinux_1_2_x86_64.whl",
+			"numpy-2.1.2-cp312-cp312-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+			"numpy-2.1.2-cp312-cp312-manylinux_2_17_aarch64.manylinux2014_aarch64.whl",
+			"numpy-2.1.2-cp312-cp312-win_amd64.whl",
+			"numpy-2.1.2.tar.gz",
+		];
+		assert_eq!(select_wheel(&numpy, &python, "linux", "x86_64", false), Some(numpy[2]));
+		assert_eq!(select_wheel(&numpy, &python, "linux", "x86_64", true), Some(numpy[1]));
+		assert_eq!(select_wheel(&numpy, &python, "linux", "aarch64", false), Some(numpy[3]));
+		assert_eq!(select_wheel(&numpy, &python, "macos", "aarch64", false), Some(numpy[0]));
+		assert_eq!(select_wheel(&numpy, &python, "windows", "x86_64", false), Some(numpy[4]));
+		assert_eq!(select_wheel(&numpy, &python, "macos", "x86_64", false), None);
+
+		// A platform wheel is preferred over a pure one, which still works anywhere
+		let mixed = ["pkg-1.0-py3-none-any.whl", "pkg-1.0-cp312-cp312-manylinux_2_17_x86_64.whl"];
+		assert_eq!(select_wheel(&mixed, &python, "linux", "x86_64", false), Some(mixed[1]));
+		assert_eq!(select_wheel(&mixed, &python, "windows", "x86_64", false), Some(mixed[0]));
+
+		// Only Windows and macOS wheels
+		let desktop = ["pywin-1.0-cp312-cp312-win_amd64.whl", "pywin-1.0-cp312-cp312-macosx_10_9_universal2.whl"];
+		assert_eq!(select_wheel(&desktop, &python, "linux", "x86_64", false), None);
+		assert_eq!(select_wheel(&desktop, &python, "macos", "aarch64", false), Some(desktop[1]));
+	}
+
+	#[test]
+	fn manylinux_is_ranked_above_musllinux_on_glibc() {
+		assert_eq!(rank_platform_tag("manylinux_2_17_x86_64", "linux", "x86_64", false), Some(2));
+		assert_eq!(rank_platform_tag("musllinux_1_2_x86_64", "linux", "x86_64", false), None);
+		assert_eq!(rank_platform_tag("manylinux_2_17_x86_64", "linux", "x86_64", true), None);
+		assert_eq!(rank_platform_tag("manylinux_2_17_i686", "linux", "x86", false), Some(2));
+		assert_eq!(rank_platform_tag("manylinux_2_17_aarch64", "linux", "x86_64", false), None);
+		assert_eq!(rank_platform_tag("any", "linux", "x86_64", false), Some(0));
+	}
+
+	#[test]
+	fn short_release_versions_are_padded() {
+		assert_eq!(parse_release_version("3.10"), Some(Version::new(3, 10, 0)));
+		assert_eq!(parse_release_version("2"), Some(Version::new(2, 0, 0)));
+		assert_eq!(parse_release_version("2.32.3"), Some(Version::new(2, 32, 3)));
+		assert_eq!(parse_release_version("2.0rc1"), None);
+		assert_eq!(parse_release_version("1.0.0.post1"), None);
+		assert_eq!(parse_release_version("1..2"), None);
+	}
+}
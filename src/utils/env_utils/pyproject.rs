@@ -0,0 +1,643 @@
+use semver::{Comparator, Op, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use toml_edit::{Array, DocumentMut, Item, Table, TableLike, Value};
+
+use crate::utils::{error, evaluate_marker, guard, merge_table, normalize_package_name, AnyError, Config, PythonSpec, Script, MAIN_GROUP};
+
+/// The keys of `[tool.pen]`, the packages are in `[project]`.
+pub static PEN_TOOL_KEYS: [&str; 3] = ["python", "env-files", "scripts"];
+
+#[derive(Deserialize)]
+struct Pyproject {
+	#[serde(default)]
+	project: Project,
+	#[serde(default)]
+	tool: Tool,
+}
+
+#[derive(Deserialize, Default)]
+struct Project {
+	#[serde(default)]
+	dependencies: Vec<String>,
+	#[serde(default, rename = "optional-dependencies")]
+	optional_dependencies: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+struct Tool {
+	#[serde(default)]
+	pen: Option<PenTool>,
+}
+
+/// The `[tool.pen]` table, the settings of `pen.toml` that `[project]` has no place for.
+#[derive(Serialize, Deserialize)]
+struct PenTool {
+	python: PythonSpec,
+	#[serde(default, rename = "env-files", skip_serializing_if = "Vec::is_empty")]
+	env_files: Vec<PathBuf>,
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	scripts: BTreeMap<String, Script>,
+}
+
+/// A dependency of `[project]`, ex. `requests[socks]>=2.32,<3; python_version >= "3.10"`.
+pub struct ProjectDependency {
+	pub name: String,
+	/// The extras with their brackets, ex. `[socks]`, empty when there are none.
+	pub extras: String,
+	/// The version specifier translated to a pen version requirement, ex. `>=2.32.0, <3.0.0`.
+	pub version: String,
+	/// The environment marker, ex. `python_version >= "3.10"`.
+	pub marker: Option<String>,
+}
+
+impl ProjectDependency {
+	/// Whether the dependency applies to the Python of the project on this machine, ex. not `pywin32; sys_platform == "win32"` on Linux.
+	///
+	/// # Termination
+	/// - This function returns an error for markers pen can't evaluate, ex. `platform_release >= "5.0"`.
+	pub fn applies_to(&self, python: &PythonSpec) -> Result<bool, String> {
+		return match &self.marker {
+			Some(marker) => evaluate_marker(marker, python),
+			None => Ok(true),
+		};
+	}
+
+	/// Formats the dependency for `[project]`, with `version` translated back to a PEP 440 specifier.
+	fn to_pep508(&self) -> Result<String, String> {
+		let version = VersionReq::parse(&self.version).map_err(|e| e.to_string())?;
+		let mut dependency = format!("{}{}{}", self.name, self.extras, to_version_specifier(&version));
+		if let Some(marker) = &self.marker {
+			dependency = format!("{}; {}", dependency, marker);
+		}
+		return Ok(dependency);
+	}
+}
+
+/// Reads the config of a project from the `[project]` and `[tool.pen]` tables of its `pyproject.toml`.
+///
+/// # Arguments
+/// - `config_path`: The path of the `pyproject.toml`, already checked with `validate_pyproject`.
+///
+/// # Output
+/// - The config. `dependencies` become `[packages]` and each `optional-dependencies` list becomes a dependency group.
+///   Dependencies whose environment marker doesn't match the Python of the project on this machine are left out.
+///
+/// # Termination
+/// - This function returns an error if the file can't be read or parsed, or if an environment marker can't be evaluated.
+///
+/// # Limitations
+/// - Extras are kept in `pyproject.toml` but not used: the package is installed without them.
+pub fn read_pyproject_config(config_path: &PathBuf) -> Result<Config, AnyError> {
+	let contents = guard!(fs::read_to_string(config_path), "Couldn't read {}.", config_path.display());
+	let pyproject = guard!(toml::from_str::<Pyproject>(&contents), "Couldn't parse {}.", config_path.display());
+	let pen_tool = match pyproject.tool.pen {
+		Some(pen_tool) => pen_tool,
+		None => return error!("No [tool.pen] table in {}, run `pen init <python version>`.", config_path.display()),
+	};
+
+	let mut dependency_groups = BTreeMap::new();
+	for (group, dependencies) in &pyproject.project.optional_dependencies {
+		dependency_groups.insert(group.clone(), to_packages_table(dependencies, &pen_tool.python)?);
+	}
+	let packages = to_packages_table(&pyproject.project.dependencies, &pen_tool.python)?;
+	return Ok(Config {
+		python: pen_tool.python,
+		env_files: pen_tool.env_files,
+		packages,
+		dependency_groups,
+		scripts: pen_tool.scripts,
+	});
+}
+
+/// Writes the config of a project to its `pyproject.toml`, the packages to `[project]` and the other settings to `[tool.pen]`.
+///
+/// # Arguments
+/// - `config_path`: The path of the `pyproject.toml`.
+/// - `config`: The new config.
+///
+/// # Termination
+/// - This function returns an error if the file can't be parsed or written, or if a requirement has no PEP 440 equivalent.
+///
+/// # Guarantees
+/// - The dependencies whose requirement didn't change are kept as written, with their extras, markers and comments.
+///   So are the dependencies whose environment marker doesn't match, they aren't part of the config on this machine.
+///   The rest of the file is left as it was.
+pub fn write_pyproject_config(config_path: &PathBuf, config: &Config) -> Result<(), AnyError> {
+	let contents = guard!(fs::read_to_string(config_path), "Couldn't read {}.", config_path.display());
+	let mut document = guard!(contents.parse::<DocumentMut>(), "Couldn't parse {}.", config_path.display());
+
+	let project = get_or_insert_table(document.as_table_mut(), "project")?;
+	let dependencies = get_or_insert_array(project, "dependencies")?;
+	update_dependencies(dependencies, &config.packages, &config.python)?;
+
+	if config.dependency_groups.is_empty() {
+		project.remove("optional-dependencies");
+	} else {
+		let groups = get_or_insert_table(project, "optional-dependencies")?;
+		let removed_groups: Vec<String> = groups
+			.iter()
+			.map(|(group, _)| String::from(group))
+			.filter(|group| !config.dependency_groups.contains_key(group))
+			.collect();
+		for group in removed_groups {
+			groups.remove(&group);
+		}
+		for (group, packages) in &config.dependency_groups {
+			update_dependencies(get_or_insert_array(groups, group)?, packages, &config.python)?;
+		}
+	}
+
+	let pen_tool = PenTool {
+		python: config.python.clone(),
+		env_files: config.env_files.clone(),
+		scripts: config.scripts.clone(),
+	};
+	let toml = guard!(
+		toml::to_string_pretty(&pen_tool),
+		"Couldn't convert config to valid toml.\nPlease open an issue on Github."
+	);
+	let new_pen_tool = guard!(
+		toml.parse::<DocumentMut>(),
+		"Couldn't convert config to valid toml.\nPlease open an issue on Github."
+	);
+	let tool = get_or_insert_table(document.as_table_mut(), "tool")?;
+	let old_pen_tool = get_or_insert_table(tool, "pen")?;
+	merge_table(old_pen_tool, new_pen_tool.as_table(), Some(&PEN_TOOL_KEYS));
+
+	guard!(
+		fs::write(config_path, document.to_string()),
+		"Couldn't write to {}.",
+		config_path.display()
+	);
+	return Ok(());
+}
+
+/// Sets the Python of a project in the `[tool.pen]` table of its `pyproject.toml`, creating the table if needed.
+///
+/// # Termination
+/// - This function returns an error if the file can't be parsed or written, or if `python` doesn't satisfy `requires-python`.
+pub fn init_pyproject_config(config_path: &PathBuf, python: &PythonSpec) -> Result<(), AnyError> {
+	let contents = guard!(fs::read_to_string(config_path), "Couldn't read {}.", config_path.display());
+	let mut document = guard!(contents.parse::<DocumentMut>(), "Couldn't parse {}.", config_path.display());
+
+	let requires_python = document
+		.get("project")
+		.and_then(|project| project.get("requires-python"))
+		.and_then(|r| r.as_str());
+	if let Some(requires_python) = requires_python {
+		let version = match to_version_requirement(requires_python).map(|r| VersionReq::parse(&r)) {
+			Ok(Ok(version)) => version,
+			_ => return error!("Invalid requires-python \"{}\" in {}.", requires_python, config_path.display()),
+		};
+		if !version.matches(&python.version) {
+			return error!(
+				"Python {} doesn't satisfy requires-python \"{}\" of {}.",
+				python,
+				requires_python,
+				config_path.display()
+			);
+		}
+	}
+
+	let tool = get_or_insert_table(document.as_table_mut(), "tool")?;
+	let pen_tool = get_or_insert_table(tool, "pen")?;
+	if pen_tool.contains_key("python") {
+		return error!("{} already has a Python in [tool.pen].", config_path.display());
+	}
+	pen_tool.insert("python", toml_edit::value(python.to_string()));
+
+	guard!(
+		fs::write(config_path, document.to_string()),
+		"Couldn't write to {}.",
+		config_path.display()
+	);
+	return Ok(());
+}
+
+/// Reads the environment markers of the dependencies of some groups of a `pyproject.toml`.
+///
+/// # Arguments
+/// - `config_path`: The path of the `pyproject.toml`.
+/// - `groups`: The groups to read, `main` for `dependencies`.
+///
+/// # Output
+/// - The marker of each package, by normalized name. A package listed once without a marker in any of the groups has none.
+///   Dependencies whose marker doesn't match the Python of the project, and so aren't locked, are left out.
+pub fn read_dependency_markers(config_path: &PathBuf, python: &PythonSpec, groups: &[String]) -> Result<BTreeMap<String, String>, AnyError> {
+	let contents = guard!(fs::read_to_string(config_path), "Couldn't read {}.", config_path.display());
+	let pyproject = guard!(toml::from_str::<Pyproject>(&contents), "Couldn't parse {}.", config_path.display());
+
+	let mut dependencies = Vec::new();
+	if groups.iter().any(|group| group == MAIN_GROUP) {
+		dependencies.extend(pyproject.project.dependencies.iter());
+	}
+	for (group, group_dependencies) in &pyproject.project.optional_dependencies {
+		if groups.contains(group) {
+			dependencies.extend(group_dependencies.iter());
+		}
+	}
+
+	let mut markers: BTreeMap<String, Option<Vec<String>>> = BTreeMap::new();
+	for dependency in dependencies {
+		let dependency = match parse_project_dependency(dependency) {
+			Ok(dependency) => dependency,
+			Err(e) => return error!("Invalid dependency \"{}\": {}", dependency, e),
+		};
+		match dependency.applies_to(python) {
+			Ok(true) => {}
+			Ok(false) => continue,
+			Err(e) => return error!("Invalid dependency {}: {}", dependency.name, e),
+		}
+		let entry = markers.entry(normalize_package_name(&dependency.name)).or_insert(Some(Vec::new()));
+		match (entry.as_mut(), dependency.marker) {
+			(Some(entry_markers), Some(marker)) => entry_markers.push(marker),
+			_ => *entry = None,
+		}
+	}
+
+	return Ok(markers
+		.into_iter()
+		.filter_map(|(name, markers)| {
+			let markers = markers?;
+			match markers.len() {
+				1 => Some((name, markers[0].clone())),
+				_ => Some((name, markers.iter().map(|m| format!("({})", m)).collect::<Vec<String>>().join(" or "))),
+			}
+		})
+		.collect());
+}
+
+/// Parses a PEP 508 dependency of `[project]`, ex. `requests[socks]>=2.32,<3; python_version >= "3.10"`.
+///
+/// # Termination
+/// - This function returns an error explaining what can't be translated, ex. direct references (`name @ url`) or `!=`.
+pub fn parse_project_dependency(dependency: &str) -> Result<ProjectDependency, String> {
+	let (requirement, marker) = match dependency.split_once(';') {
+		Some((requirement, marker)) => (requirement.trim(), Some(String::from(marker.trim()))),
+		None => (dependency.trim(), None),
+	};
+	if requirement.contains('@') {
+		return Err(String::from("direct references (`name @ url`) are not supported"));
+	}
+
+	let name_end = requirement
+		.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+		.unwrap_or(requirement.len());
+	let name = &requirement[..name_end];
+	if name.is_empty() {
+		return Err(String::from("missing package name"));
+	}
+
+	let mut specifier = requirement[name_end..].trim();
+	let mut extras = String::new();
+	if specifier.starts_with('[') {
+		let end = match specifier.find(']') {
+			Some(end) => end,
+			None => return Err(String::from("unclosed `[` in the extras")),
+		};
+		extras = String::from(&specifier[..=end]);
+		specifier = specifier[end + 1..].trim();
+	}
+	let specifier = specifier.trim_start_matches('(').trim_end_matches(')');
+
+	return Ok(ProjectDependency {
+		name: String::from(name),
+		extras,
+		version: to_version_requirement(specifier)?,
+		marker,
+	});
+}
+
+/// Translates a PEP 440 version specifier to a pen version requirement, ex. `>=2.32,<3` to `>=2.32.0, <3.0.0`.
+///
+/// # Termination
+/// - This function returns an error for `!=`, and for versions that are not plain release numbers, ex. `2.0rc1` or `1.0.post1`.
+pub fn to_version_requirement(specifier: &str) -> Result<String, String> {
+	let mut comparators = Vec::new();
+	for clause in specifier.split(',').map(str::trim).filter(|clause| !clause.is_empty()) {
+		let operator_end = clause.find(|c: char| !"<>=!~".contains(c)).unwrap_or(clause.len());
+		let (operator, version) = (&clause[..operator_end], clause[operator_end..].trim());
+
+		let (release, is_wildcard) = match version.strip_suffix(".*") {
+			Some(release) => (release, true),
+			None => (version, false),
+		};
+		let parts: Vec<u64> = match release.split('.').map(|part| part.parse::<u64>()).collect() {
+			Ok(parts) => parts,
+			Err(_) => {
+				return Err(format!(
+					"unsupported version `{}` in `{}`, pen only supports versions like 1.2.3",
+					version, clause
+				))
+			}
+		};
+		if parts.len() > 3 || (is_wildcard && operator != "==") {
+			return Err(format!(
+				"unsupported version `{}` in `{}`, pen only supports versions like 1.2.3",
+				version, clause
+			));
+		}
+		// A missing part is 0, ex. `>1.4` is `>1.4.0`, except in wildcards where it matches any number
+		let padded = match is_wildcard {
+			true => String::from(release),
+			false => format!("{}{}", release, ".0".repeat(3 - parts.len())),
+		};
+
+		match operator {
+			">=" | "<=" | ">" | "<" => comparators.push(format!("{}{}", operator, padded)),
+			"==" | "===" => comparators.push(format!("={}", padded)),
+			"~=" if parts.len() >= 2 => {
+				let mut upper = parts[..parts.len() - 1].to_vec();
+				*upper.last_mut().unwrap() += 1;
+				let upper: Vec<String> = upper.iter().map(|part| part.to_string()).collect();
+				comparators.push(format!(">={}", padded));
+				comparators.push(format!("<{}", upper.join(".")));
+			}
+			"~=" => return Err(format!("`{}` needs at least two version numbers, ex. ~=1.4", clause)),
+			"!=" => return Err(format!("`{}` can't be translated, pen version requirements have no `!=`", clause)),
+			_ => return Err(format!("unknown operator `{}` in `{}`", operator, clause)),
+		}
+	}
+
+	if comparators.is_empty() {
+		return Ok(String::from("*"));
+	}
+	return Ok(comparators.join(", "));
+}
+
+/// Translates a pen version requirement to a PEP 440 version specifier, ex. `^2.32` to `>=2.32,<3`. Empty for `*`.
+pub fn to_version_specifier(version: &VersionReq) -> String {
+	let clauses: Vec<String> = version.comparators.iter().flat_map(to_version_clauses).collect();
+	return clauses.join(",");
+}
+
+fn to_version_clauses(comparator: &Comparator) -> Vec<String> {
+	let mut version = comparator.major.to_string();
+	for part in [comparator.minor, comparator.patch].into_iter().flatten() {
+		version = format!("{}.{}", version, part);
+	}
+	if !comparator.pre.is_empty() {
+		version = format!("{}{}", version, comparator.pre);
+	}
+
+	let major = comparator.major;
+	let minor = comparator.minor.unwrap_or(0);
+	let lower = format!(">={}", version);
+	let next_major = format!("{}", major + 1);
+	let next_minor = format!("{}.{}", major, minor + 1);
+	return match (comparator.op, comparator.minor, comparator.patch) {
+		(Op::Exact | Op::Wildcard, Some(_), Some(_)) => vec![format!("=={}", version)],
+		(Op::Exact | Op::Wildcard, _, _) => vec![format!("=={}.*", version)],
+		// Partial versions cover every version starting with them, ex. `>1.4` means `>=1.5.0`
+		(Op::Greater, _, Some(_)) => vec![format!(">{}", version)],
+		(Op::Greater, Some(_), None) => vec![format!(">={}", next_minor)],
+		(Op::Greater, None, _) => vec![format!(">={}", next_major)],
+		(Op::LessEq, _, Some(_)) => vec![format!("<={}", version)],
+		(Op::LessEq, Some(_), None) => vec![format!("<{}", next_minor)],
+		(Op::LessEq, None, _) => vec![format!("<{}", next_major)],
+		(Op::Less, _, _) => vec![format!("<{}", version)],
+		(Op::Tilde, Some(_), _) => vec![lower, format!("<{}", next_minor)],
+		(Op::Tilde, None, _) => vec![lower, format!("<{}", next_major)],
+		(Op::Caret, Some(0), Some(patch)) if major == 0 => vec![lower, format!("<0.0.{}", patch + 1)],
+		(Op::Caret, Some(_), _) if major == 0 => vec![lower, format!("<{}", next_minor)],
+		(Op::Caret, None, _) if major == 0 => vec![lower, String::from("<1")],
+		(Op::Caret, _, _) => vec![lower, format!("<{}", next_major)],
+		_ => vec![lower],
+	};
+}
+
+/// Turns a list of `[project]` dependencies into a table of packages and version requirements, like `[packages]` of `pen.toml`.
+fn to_packages_table(dependencies: &[String], python: &PythonSpec) -> Result<toml::Table, AnyError> {
+	let mut packages = toml::Table::new();
+	for dependency in dependencies {
+		let parsed = match parse_project_dependency(dependency) {
+			Ok(parsed) => parsed,
+			Err(e) => return error!("Invalid dependency \"{}\": {}", dependency, e),
+		};
+		match parsed.applies_to(python) {
+			Ok(true) => {}
+			// Ex. `pywin32; sys_platform == "win32"` on Linux
+			Ok(false) => continue,
+			Err(e) => return error!("Invalid dependency \"{}\": {}", dependency, e),
+		}
+		let dependency = parsed;
+		// A package listed several times, ex. with different markers, gets all the requirements
+		let version = match packages.get(&dependency.name).and_then(|version| version.as_str()) {
+			Some("*") | None => dependency.version,
+			Some(version) if dependency.version == "*" => String::from(version),
+			Some(version) => format!("{}, {}", version, dependency.version),
+		};
+		packages.insert(dependency.name, toml::Value::String(version));
+	}
+	return Ok(packages);
+}
+
+/// Updates a list of `[project]` dependencies to the packages of a group, rewriting only the dependencies whose requirement changed.
+/// The dependencies whose marker doesn't match `python` on this machine aren't in `packages`, they are left untouched.
+fn update_dependencies(dependencies: &mut Array, packages: &toml::Table, python: &PythonSpec) -> Result<(), AnyError> {
+	let wanted: BTreeMap<String, (&String, &str)> = packages
+		.iter()
+		.map(|(name, version)| (normalize_package_name(name), (name, version.as_str().unwrap_or("*"))))
+		.collect();
+
+	// The current requirement of each package, combined over the dependencies listing it
+	let mut current: BTreeMap<String, Vec<ProjectDependency>> = BTreeMap::new();
+	for value in dependencies.iter() {
+		let dependency = value.as_str().and_then(|dependency| parse_project_dependency(dependency).ok());
+		if let Some(dependency) = dependency.filter(|dependency| dependency.applies_to(python) == Ok(true)) {
+			current.entry(normalize_package_name(&dependency.name)).or_default().push(dependency);
+		}
+	}
+	let is_unchanged = |name: &String| -> bool {
+		let (Some((_, version)), Some(listed)) = (wanted.get(name), current.get(name)) else {
+			return false;
+		};
+		let listed: Vec<&str> = listed.iter().map(|d| d.version.as_str()).filter(|v| *v != "*").collect();
+		let listed = if listed.is_empty() { String::from("*") } else { listed.join(", ") };
+		return VersionReq::parse(&listed).ok() == VersionReq::parse(version).ok();
+	};
+
+	let mut index = 0;
+	let mut written: Vec<String> = Vec::new();
+	while index < dependencies.len() {
+		let dependency = match dependencies.get(index).and_then(|value| value.as_str()).map(parse_project_dependency) {
+			Some(Ok(dependency)) if dependency.applies_to(python) == Ok(true) => dependency,
+			_ => {
+				index += 1;
+				continue;
+			}
+		};
+		let name = normalize_package_name(&dependency.name);
+		if is_unchanged(&name) {
+			index += 1;
+			continue;
+		}
+		let version = match wanted.get(&name) {
+			Some((_, version)) if !written.contains(&name) => version,
+			// Removed from the group, or already written from an earlier dependency of the same package
+			_ => {
+				remove_dependency(dependencies, index);
+				continue;
+			}
+		};
+
+		let dependency = ProjectDependency {
+			version: String::from(*version),
+			..dependency
+		};
+		let formatted = match dependency.to_pep508() {
+			Ok(formatted) => formatted,
+			Err(e) => return error!("Invalid version requirement for {}: {}", dependency.name, e),
+		};
+		let decor = dependencies.get(index).map(|value| value.decor().clone()).unwrap_or_default();
+		let mut value = Value::from(formatted);
+		*value.decor_mut() = decor;
+		dependencies.replace_formatted(index, value);
+		written.push(name);
+		index += 1;
+	}
+
+	for (name, (package, version)) in &wanted {
+		if current.contains_key(name) {
+			continue;
+		}
+		let dependency = ProjectDependency {
+			name: (*package).clone(),
+			extras: String::new(),
+			version: String::from(*version),
+			marker: None,
+		};
+		let formatted = match dependency.to_pep508() {
+			Ok(formatted) => formatted,
+			Err(e) => return error!("Invalid version requirement for {}: {}", package, e),
+		};
+		// New dependencies are formatted like the last one, ex. on their own line in a multiline list
+		let mut value = Value::from(formatted);
+		match dependencies.iter().last() {
+			Some(last) => *value.decor_mut() = last.decor().clone(),
+			None => value.decor_mut().clear(),
+		}
+		dependencies.push_formatted(value);
+	}
+	return Ok(());
+}
+
+/// Removes a dependency from a list, with the comment at the end of its line.
+fn remove_dependency(dependencies: &mut Array, index: usize) {
+	let removed = dependencies.remove(index);
+	// In a multiline list, the comment at the end of a line is at the start of the prefix of the next dependency,
+	// or of the trailing whitespace of the list for the last one
+	let prefix = removed.decor().prefix().and_then(|prefix| prefix.as_str()).unwrap_or("");
+	let previous_comment = match prefix.split_once('\n') {
+		Some((previous_comment, _)) => previous_comment,
+		None => return,
+	};
+
+	let replace_first_line = |text: &str| match text.split_once('\n') {
+		Some((_, rest)) => format!("{}\n{}", previous_comment, rest),
+		None => String::from(text),
+	};
+	match dependencies.get_mut(index) {
+		Some(next) => {
+			let next_prefix = next.decor().prefix().and_then(|prefix| prefix.as_str()).unwrap_or("");
+			let next_prefix = replace_first_line(next_prefix);
+			next.decor_mut().set_prefix(next_prefix);
+		}
+		None => {
+			let trailing = replace_first_line(dependencies.trailing().as_str().unwrap_or(""));
+			dependencies.set_trailing(trailing);
+		}
+	}
+}
+
+fn get_or_insert_table<'a>(table: &'a mut dyn TableLike, key: &str) -> Result<&'a mut dyn TableLike, AnyError> {
+	if !table.contains_key(key) {
+		let mut new_table = Table::new();
+		new_table.set_implicit(true);
+		table.insert(key, Item::Table(new_table));
+	}
+	return match table.get_mut(key).and_then(|item| item.as_table_like_mut()) {
+		Some(table) => Ok(table),
+		None => error!("`{}` must be a table.", key),
+	};
+}
+
+fn get_or_insert_array<'a>(table: &'a mut dyn TableLike, key: &str) -> Result<&'a mut Array, AnyError> {
+	if !table.contains_key(key) {
+		table.insert(key, toml_edit::value(Array::new()));
+	}
+	return match table.get_mut(key).and_then(|item| item.as_array_mut()) {
+		Some(array) => Ok(array),
+		None => error!("`{}` must be a list of dependencies.", key),
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+
+	#[test]
+	fn version_requirements() {
+		assert_eq!(to_version_requirement(">=2.32,<3"), Ok(String::from(">=2.32.0, <3.0.0")));
+		assert_eq!(to_version_requirement("==1.4.*"), Ok(String::from("=1.4")));
+		assert_eq!(to_version_requirement("~=1.4.2"), Ok(String::from(">=1.4.2, <1.5")));
+		assert_eq!(to_version_requirement(""), Ok(String::from("*")));
+		assert!(to_version_requirement("~=1").is_err());
+		assert!(to_version_requirement("!=1.4").is_err());
+		assert!(to_version_requirement(">=2.0rc1").is_err());
+		assert!(to_version_requirement(">=1.2.3.4").is_err());
+	}
+
+	#[test]
+	fn dependencies_not_matching_their_marker_are_left_out() {
+		let project_path = env::temp_dir().join(format!("pen-test-pyproject-markers-{}", std::process::id()));
+		fs::create_dir_all(&project_path).unwrap();
+		let config_path = project_path.join("pyproject.toml");
+		let contents = [
+			"[project]",
+			"dependencies = [",
+			"\t\"requests>=2.32\",",
+			"\t\"pywin32>=306; sys_platform == 'win32'\",",
+			"\t\"tomli>=2; python_version < '3.11'\",",
+			"]",
+			"",
+			"[tool.pen]",
+			"python = \"3.12.4\"",
+			"",
+		]
+		.join("\n");
+		fs::write(&config_path, &contents).unwrap();
+
+		let mut config = read_pyproject_config(&config_path);
+		if let Ok(config) = &mut config {
+			config.packages.insert(String::from("rich"), toml::Value::String(String::from("^13.0")));
+		}
+		let written = match &config {
+			Ok(config) => write_pyproject_config(&config_path, config).is_ok(),
+			Err(_) => false,
+		};
+		let new_contents = fs::read_to_string(&config_path).unwrap_or_default();
+		let markers = read_dependency_markers(&config_path, &PythonSpec::parse("3.12.4").unwrap(), &[String::from(MAIN_GROUP)]);
+		fs::remove_dir_all(&project_path).unwrap();
+
+		let config = config.unwrap();
+		let names: Vec<&String> = config.packages.keys().collect();
+		assert_eq!(names, vec!["requests", "rich"]);
+		assert!(written);
+		assert!(new_contents.contains("\"pywin32>=306; sys_platform == 'win32'\""));
+		assert!(new_contents.contains("\"tomli>=2; python_version < '3.11'\""));
+		assert!(new_contents.contains("\"rich>=13.0,<14\""));
+		assert!(markers.unwrap().is_empty());
+	}
+
+	#[test]
+	fn markers_pen_cant_evaluate_are_errors() {
+		let python = PythonSpec::parse("3.12.4").unwrap();
+		let dependencies = vec![String::from("foo; platform_release >= '5.0'")];
+		assert!(to_packages_table(&dependencies, &python).is_err());
+	}
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use crate::constants::{REGISTRY_FILE, TMP_DIR};
+use crate::utils::{get_lock_path, guard, lock_exclusive, AnyError, PythonSpec};
+
+/// Every virtual environment created or updated by pen, keyed by the path of the environment.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Registry {
+	#[serde(default)]
+	pub environments: BTreeMap<PathBuf, RegisteredEnvironment>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RegisteredEnvironment {
+	pub python: PythonSpec,
+	/// The interpreter the environment links to.
+	pub python_executable: PathBuf,
+	/// The cached packages (in `PYTHON_PACKAGES_DIR`) the environment links to.
+	#[serde(default)]
+	pub packages: Vec<PathBuf>,
+	/// The dependency groups the environment was synced with, reused when `pen run` syncs it again.
+	#[serde(default)]
+	pub groups: SyncedGroups,
+	/// The names of the entry point scripts pen wrote in the `bin` directory of the environment.
+	#[serde(default)]
+	pub entry_points: Vec<String>,
+}
+
+/// The dependency groups asked to `pen sync`, see `select_groups`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SyncedGroups {
+	/// The groups installed in addition to `[packages]` and the `dev` group, its `--group` arguments.
+	pub groups: Vec<String>,
+	/// Whether the `dev` group was left out.
+	pub no_dev: bool,
+}
+
+/// Reads the registry of environments.
+///
+/// # Output
+/// - The registry. Environments that were deleted since they were registered are left out.
+///
+/// # Termination
+/// - This function returns an error if the registry exists but can't be read or parsed.
+pub fn read_registry() -> Result<Registry, AnyError> {
+	if !guard!(REGISTRY_FILE.try_exists(), "Unable to know if {} exists", REGISTRY_FILE.display()) {
+		return Ok(Registry::default());
+	}
+	let contents = guard!(fs::read_to_string(&*REGISTRY_FILE), "Couldn't read {}.", REGISTRY_FILE.display());
+	let mut registry = guard!(toml::from_str::<Registry>(&contents), "Couldn't parse {}.", REGISTRY_FILE.display());
+
+	registry.environments.retain(|env_path, _| env_path.join("pyvenv.cfg").exists());
+	return Ok(registry);
+}
+
+/// Records that the environment at `env_path` now uses `environment`, replacing what was registered before.
+///
+/// # Termination
+/// - This function returns an error if the registry can't be read or written.
+///
+/// # Guarantees
+/// - The registry file is replaced in one step, so it is never left half written.
+pub fn register_environment(env_path: &Path, environment: RegisteredEnvironment) -> Result<(), AnyError> {
+	let _registry_lock = lock_exclusive(&get_lock_path(&REGISTRY_FILE))?;
+	let mut registry = read_registry()?;
+	registry.environments.insert(env_path.to_path_buf(), environment);
+
+	let contents = guard!(
+		toml::to_string_pretty(&registry),
+		"Couldn't convert registry to valid toml.\nPlease open an issue on Github."
+	);
+	let temp_registry_path = TMP_DIR.join("temp_registry");
+	guard!(
+		fs::write(&temp_registry_path, contents),
+		"Couldn't write {}.",
+		temp_registry_path.display()
+	);
+	guard!(
+		fs::rename(&temp_registry_path, &*REGISTRY_FILE),
+		"Couldn't write {}.",
+		REGISTRY_FILE.display()
+	);
+	return Ok(());
+}
+
+/// Checks if any registered environment links to the cached package at `package_path`.
+pub fn is_package_used(registry: &Registry, package_path: &PathBuf) -> bool {
+	return registry
+		.environments
+		.values()
+		.any(|environment| environment.packages.contains(package_path));
+}
+
+/// Lists the registered environments that use the interpreter installed in `python_dir`.
+pub fn get_environments_using(registry: &Registry, python_dir: &PathBuf) -> Vec<PathBuf> {
+	return registry
+		.environments
+		.iter()
+		.filter(|(_, environment)| environment.python_executable.starts_with(python_dir))
+		.map(|(env_path, _)| env_path.clone())
+		.collect();
+}
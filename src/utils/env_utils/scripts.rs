@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	os::unix::process::ExitStatusExt,
+	path::{Path, PathBuf},
+	process,
+};
+
+use crate::utils::{error, get_activated_path, guard, AnyError};
+
+/// A script of the `[scripts]` table of `pen.toml`.
+///
+/// ```toml
+/// [scripts]
+/// test = "pytest -x"
+/// lint = ["ruff check .", "mypy src"]
+/// serve = { cmd = "uvicorn app:app --reload", env = { PORT = "8000" }, help = "Start the dev server" }
+/// ci = ["lint", "test"]
+/// ```
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Script {
+	Command(ScriptCommand),
+	Detailed {
+		cmd: ScriptCommand,
+		#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+		env: BTreeMap<String, String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		help: Option<String>,
+	},
+}
+
+/// A command line run by `sh`, or a list of steps run one after the other.
+/// A step that is the name of another script runs that script.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ScriptCommand {
+	Line(String),
+	Steps(Vec<String>),
+}
+
+impl Script {
+	fn steps(&self) -> Vec<&String> {
+		let cmd = match self {
+			Script::Command(cmd) => cmd,
+			Script::Detailed { cmd, .. } => cmd,
+		};
+		return match cmd {
+			ScriptCommand::Line(line) => vec![line],
+			ScriptCommand::Steps(steps) => steps.iter().collect(),
+		};
+	}
+
+	/// The text shown by `pen run --list`, the help of the script or its steps.
+	pub fn description(&self) -> String {
+		if let Script::Detailed { help: Some(help), .. } = self {
+			return help.clone();
+		}
+		return self.steps().iter().map(|s| s.as_str()).collect::<Vec<&str>>().join(" && ");
+	}
+}
+
+/// Runs a script of the project inside its virtual environment.
+///
+/// # Arguments
+/// - `scripts`: The `[scripts]` table of the project.
+/// - `name`: The script to run.
+/// - `args`: Extra arguments, appended to the command line of the last step only, or of the last step of the script it chains to.
+/// - `project_path`: The project root, where the steps run.
+/// - `env_path`: The virtual environment to run the steps in.
+/// - `env_vars`: Variables set for every step, ex. those of the dotenv files.
+///
+/// # Output
+/// - The exit status of the first failing step, or of the last step if all succeed.
+///
+/// # Termination
+/// - This function returns an error if the script doesn't exist, if it chains to itself, or if a step can't be started.
+///
+/// # Guarantees
+/// - The steps stop at the first one failing, like `&&` in a shell.
+/// - Variables of the `env` of a script also apply to the scripts it chains to, which can override them.
+pub fn run_script(
+	scripts: &BTreeMap<String, Script>,
+	name: &str,
+	args: &[&String],
+	project_path: &PathBuf,
+	env_path: &Path,
+	env_vars: &[(String, String)],
+) -> Result<process::ExitStatus, AnyError> {
+	let mut env: BTreeMap<String, String> = env_vars.iter().cloned().collect();
+	env.insert(String::from("VIRTUAL_ENV"), env_path.to_string_lossy().into_owned());
+	env.insert(String::from("PATH"), get_activated_path(env_path, env_vars));
+
+	let mut chain = Vec::new();
+	return run_script_steps(scripts, name, args, project_path, &env, &mut chain);
+}
+
+fn run_script_steps(
+	scripts: &BTreeMap<String, Script>,
+	name: &str,
+	args: &[&String],
+	project_path: &PathBuf,
+	env: &BTreeMap<String, String>,
+	chain: &mut Vec<String>,
+) -> Result<process::ExitStatus, AnyError> {
+	let script = match scripts.get(name) {
+		Some(script) => script,
+		None => return error!("No script named {} in the [scripts] table", name),
+	};
+	if chain.iter().any(|called| called == name) {
+		return error!("Script {} calls itself through {} -> {}", name, chain.join(" -> "), name);
+	}
+	chain.push(String::from(name));
+
+	let mut env = env.clone();
+	if let Script::Detailed { env: script_env, .. } = script {
+		env.extend(script_env.clone());
+	}
+
+	let mut status = process::ExitStatus::from_raw(0);
+	let steps = script.steps();
+	for (i, step) in steps.iter().enumerate() {
+		// `pen run lint src/` checks src/ with the last tool, the steps before it run as written
+		let step_args = match i == steps.len() - 1 {
+			true => args,
+			false => &[],
+		};
+		status = match scripts.contains_key(step.as_str()) {
+			true => run_script_steps(scripts, step, step_args, project_path, &env, chain)?,
+			false => run_command_line(step, step_args, project_path, &env)?,
+		};
+		if !status.success() {
+			break;
+		}
+	}
+
+	chain.pop();
+	return Ok(status);
+}
+
+/// Runs a command line with `sh`, with the extra arguments appended as `"$@"` so that they are not split again.
+fn run_command_line(line: &str, args: &[&String], project_path: &PathBuf, env: &BTreeMap<String, String>) -> Result<process::ExitStatus, AnyError> {
+	let command_line = match args.is_empty() {
+		true => String::from(line),
+		false => format!("{} \"$@\"", strip_trailing_comment(line)),
+	};
+	let child = process::Command::new("sh")
+		.arg("-c")
+		.arg(command_line)
+		.arg("sh")
+		.args(args)
+		.current_dir(project_path)
+		.envs(env)
+		.env_remove("PYTHONHOME")
+		.spawn();
+	let mut child = guard!(child, "Failed to run {}", line);
+
+	// Like a shell, pen doesn't stop on Ctrl+C but lets the step handle it and reports how it ended
+	unsafe {
+		libc::signal(libc::SIGINT, libc::SIG_IGN);
+		libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+	}
+	let status = child.wait();
+	unsafe {
+		libc::signal(libc::SIGINT, libc::SIG_DFL);
+		libc::signal(libc::SIGQUIT, libc::SIG_DFL);
+	}
+	return Ok(guard!(status, "Failed to wait for {}", line));
+}
+
+/// Removes the comment ending a command line, ex. `pytest -x  # fast` becomes `pytest -x`, so that arguments appended to it aren't commented out.
+///
+/// # Limitations
+/// - Only quotes and backslashes are understood, a `#` inside `$(...)` or a here-document is taken as a comment.
+fn strip_trailing_comment(line: &str) -> &str {
+	let mut quote = None;
+	let mut escaped = false;
+	let mut previous = None;
+	for (i, c) in line.char_indices() {
+		match (quote, c) {
+			_ if escaped => escaped = false,
+			(Some('\''), '\'') | (Some('"'), '"') => quote = None,
+			(Some('"'), '\\') | (None, '\\') => escaped = true,
+			(None, '\'') | (None, '"') => quote = Some(c),
+			// A word starting with # starts a comment, up to the end of its line
+			(None, '#') if previous.is_none_or(|p: char| p.is_whitespace() || ";&|()".contains(p)) && !line[i..].contains('\n') => {
+				return line[..i].trim_end();
+			}
+			_ => {}
+		}
+		previous = Some(c);
+	}
+	return line;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{env, fs};
+
+	#[test]
+	fn trailing_comments_are_stripped() {
+		assert_eq!(strip_trailing_comment("pytest -x  # fast"), "pytest -x");
+		assert_eq!(strip_trailing_comment("pytest -x"), "pytest -x");
+		assert_eq!(strip_trailing_comment("echo '# not a comment'"), "echo '# not a comment'");
+		assert_eq!(strip_trailing_comment("echo \"a # b\" \\# c"), "echo \"a # b\" \\# c");
+		assert_eq!(strip_trailing_comment("echo a#b"), "echo a#b");
+		assert_eq!(strip_trailing_comment("make;# build"), "make;");
+		// A comment on an earlier line ends with it
+		assert_eq!(strip_trailing_comment("# setup\nmake test"), "# setup\nmake test");
+		assert_eq!(strip_trailing_comment("cd src\nmake test # all"), "cd src\nmake test");
+	}
+
+	#[test]
+	fn arguments_go_to_the_last_step() {
+		let project_path = env::temp_dir().join(format!("pen-test-scripts-{}", std::process::id()));
+		fs::create_dir_all(&project_path).unwrap();
+		let scripts: BTreeMap<String, Script> = toml::from_str(
+			r#"
+			first = "echo first >> out.txt # the first step"
+			last = ["echo setup >> out.txt", "echo last >> out.txt # with the arguments"]
+			all = ["first", "last"]
+			"#,
+		)
+		.unwrap();
+
+		let arg = String::from("arg");
+		let status = run_script(&scripts, "all", &[&arg], &project_path, &project_path, &Vec::new());
+		let output = fs::read_to_string(project_path.join("out.txt"));
+		fs::remove_dir_all(&project_path).unwrap();
+
+		assert!(status.unwrap().success());
+		assert_eq!(output.unwrap(), "first\nsetup\nlast arg\n");
+	}
+}
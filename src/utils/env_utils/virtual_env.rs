@@ -1,94 +1,609 @@
-use crate::utils::{self, download_package, error, find_matching_package_version, guard, py_install_algo_v1, AnyError, Config, Package};
-use semver::{Version, VersionReq};
-use std::{fs, os::unix, path::PathBuf};
-
-pub fn create_or_update_virtual_env(config: Config, destination_path: &PathBuf) -> Result<(), AnyError> {
-	let py_dir = utils::get_python_path(&config.python);
-	let py_version_short = format!("{}.{}", config.python.major, config.python.minor);
-
-	guard!(fs::create_dir_all(destination_path.join("bin")), "Couldn't create folder");
-
-	let temp = format!(
-		// todo this pyenv.cfg file, when writen, has some spacing beforeeach line of the paragraph, needs fixing
-		r#" # Created using pen
-		home = {0}/bin
-		include-system-site-packages = false
-		version = {1}
-		executable = {0}/bin/python
-		command = {0}/bin/python -m venv {2}
-		"#,
-		py_dir.to_string_lossy(),
+use crate::constants::{ENV_DIR_NAME, LOCKFILE_NAME};
+use crate::utils::{
+	self, error, get_config_path, guard, merge_tree, read_entry_points, read_record, register_environment, write_activate_scripts, AnyError, Config,
+	LinkMode, Package, PythonSpec, RegisteredEnvironment, SyncedGroups,
+};
+use std::{
+	collections::HashSet,
+	fs, io,
+	os::unix::{self, fs::PermissionsExt},
+	path::{Component, Path, PathBuf},
+};
+
+/// Creates the virtual environment described by `config` at `destination_path`, replacing the one already there.
+///
+/// # Arguments
+/// - `config`: The project config.
+/// - `packages`: The locked packages to install, see `get_group_packages`.
+/// - `destination_path`: The path of the environment.
+/// - `link_mode`: How packages are put into the environment, see `LinkMode`.
+/// - `groups`: The dependency groups `packages` were selected from, recorded in the registry.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error naming the step that failed if the environment can't be built.
+///
+/// # Guarantees
+/// - The new environment is built in a staging directory next to `destination_path` and swapped in only once complete.
+///   If building or swapping it in fails, the environment previously at `destination_path` is left exactly as it was.
+pub fn create_or_update_virtual_env(
+	config: Config,
+	packages: Vec<Package>,
+	destination_path: &PathBuf,
+	link_mode: LinkMode,
+	groups: SyncedGroups,
+) -> Result<(), AnyError> {
+	let staging_path = get_sibling_path(destination_path, "pen-staging");
+
+	step("prepare the staging directory", clear_dir(&staging_path))?;
+
+	let environment = match build_virtual_env(config, packages, destination_path, &staging_path, link_mode) {
+		Ok(environment) => RegisteredEnvironment { groups, ..environment },
+		Err(e) => {
+			let _ = fs::remove_dir_all(&staging_path);
+			return Err(e);
+		}
+	};
+
+	swap_dirs(&staging_path, destination_path)?;
+	if let Err(e) = register_environment(destination_path, environment) {
+		return error!(
+			"Failed to register the environment: {}. The new environment is in place, but until it is synced again `pen cache prune` and `pen delete` don't know what it uses.",
+			e
+		);
+	}
+	return Ok(());
+}
+
+/// Converts an environment made by another tool (ex. `python -m venv` and pip) into one built by pen, keeping what pen doesn't install.
+///
+/// # Arguments
+/// - `config`, `packages`, `link_mode`: What to build, see `create_or_update_virtual_env`.
+/// - `env_path`: The path of the environment to convert.
+/// - `replaced_paths`: The paths, relative to `env_path`, of the files that the packages of pen replace. They are not carried over.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if the new environment can't be built, or if the rest of the previous one can't be moved into it.
+///
+/// # Guarantees
+/// - Everything else of the previous environment (other distributions, scripts in `bin`, `.pth` files, ...) is moved into the new one,
+///   unless pen put something at the same path. Links into the package cache are never written through.
+/// - If building fails, the previous environment is put back as it was. If moving its files fails, what is left of it is kept
+///   next to `env_path`, ex. `.venv.pen-adopted`.
+pub fn adopt_virtual_env(
+	config: Config,
+	packages: Vec<Package>,
+	env_path: &PathBuf,
+	link_mode: LinkMode,
+	replaced_paths: &HashSet<PathBuf>,
+) -> Result<(), AnyError> {
+	let previous_path = get_sibling_path(env_path, "pen-adopted");
+	if fs::symlink_metadata(&previous_path).is_ok() {
+		return error!("{} already exists, move it away first.", previous_path.display());
+	}
+	guard!(fs::rename(env_path, &previous_path), "Couldn't move {} aside", env_path.display());
+
+	if let Err(e) = create_or_update_virtual_env(config, packages, env_path, link_mode, SyncedGroups::default()) {
+		guard!(
+			fs::rename(&previous_path, env_path),
+			"Couldn't restore {} from {}, please move it back manually",
+			env_path.display(),
+			previous_path.display()
+		);
+		return Err(e);
+	}
+
+	if let Err(e) = carry_over(&previous_path, env_path, &PathBuf::new(), replaced_paths) {
+		return error!(
+			"Failed to move the files of the previous environment: {}. What is left of it is in {}.",
+			e,
+			previous_path.display()
+		);
+	}
+	// Only the files replaced by pen are left
+	if let Err(e) = fs::remove_dir_all(&previous_path) {
+		eprintln!("Couldn't remove the previous environment at {}: {}", previous_path.display(), e);
+	}
+	return Ok(());
+}
+
+/// Moves the entries of `source_path` missing from `destination_path` into it, merging directories present in both.
+fn carry_over(source_path: &PathBuf, destination_path: &Path, relative_path: &Path, replaced_paths: &HashSet<PathBuf>) -> Result<(), AnyError> {
+	let entries = guard!(fs::read_dir(source_path), "Failed to read {}", source_path.display());
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		let entry_relative_path = relative_path.join(entry.file_name());
+		if replaced_paths.contains(&entry_relative_path) {
+			continue;
+		}
+
+		let destination = destination_path.join(entry.file_name());
+		let is_dir = guard!(entry.file_type(), "Couldn't read the type of {}", entry.path().display()).is_dir();
+		match fs::symlink_metadata(&destination) {
+			Err(_) => guard!(
+				fs::rename(entry.path(), &destination),
+				"Couldn't move {} to {}",
+				entry.path().display(),
+				destination.display()
+			),
+			// Symlinks are skipped by `is_dir`, so nothing is written into the package cache
+			Ok(metadata) if metadata.is_dir() && is_dir => carry_over(&entry.path(), &destination, &entry_relative_path, replaced_paths)?,
+			Ok(_) => {} // What pen put there wins
+		}
+	}
+	return Ok(());
+}
+
+/// Checks if the virtual environment of a project was built from its current config and lockfile.
+///
+/// # Arguments
+/// - `project_path`: The project root.
+///
+/// # Output
+/// - `false` if the environment doesn't exist, if its interpreter is gone, or if the config or the lockfile were modified
+///   after it was built.
+///
+/// # Termination
+/// - This function returns an error if the config can't be read.
+pub fn is_virtual_env_up_to_date(project_path: &Path) -> Result<bool, AnyError> {
+	let env_path = project_path.join(ENV_DIR_NAME);
+	let config_path = get_config_path(project_path);
+	let lockfile_path = project_path.join(LOCKFILE_NAME);
+
+	let config_metadata = guard!(fs::metadata(&config_path), "Couldn't read {}", config_path.display());
+	let env_metadata = match fs::metadata(env_path.join("pyvenv.cfg")) {
+		Ok(env_metadata) => env_metadata,
+		Err(_) => return Ok(false),
+	};
+
+	// Follows the symlink, so a deleted interpreter counts as out of date
+	if !env_path.join("bin/python").exists() {
+		return Ok(false);
+	}
+
+	let config_modified = guard!(
+		config_metadata.modified(),
+		"Couldn't read the modification time of {}",
+		config_path.display()
+	);
+	let env_modified = guard!(env_metadata.modified(), "Couldn't read the modification time of {}", env_path.display());
+	if let Ok(lockfile_modified) = fs::metadata(&lockfile_path).and_then(|metadata| metadata.modified()) {
+		if lockfile_modified > env_modified {
+			return Ok(false);
+		}
+	}
+	return Ok(env_modified >= config_modified);
+}
+
+/// Builds the environment in `staging_path`, with every path inside it pointing to where it will be once swapped in.
+fn build_virtual_env(
+	config: Config,
+	packages: Vec<Package>,
+	destination_path: &Path,
+	staging_path: &Path,
+	link_mode: LinkMode,
+) -> Result<RegisteredEnvironment, AnyError> {
+	let mut link_mode = link_mode;
+	let py_version_short = config.python.short_version();
+
+	step("create the bin directory", create_dir(&staging_path.join("bin")))?;
+
+	// Bin
+	let py_executable = step(
+		"link the Python interpreter",
+		link_python(&config.python, staging_path.join("bin"), &py_version_short),
+	)?;
+	let py_bin_dir = py_executable.parent().expect("Interpreter executables are always in a directory");
+
+	let pyvenv_text = format!(
+		"# Created using pen\n\
+			home = {0}\n\
+			include-system-site-packages = false\n\
+			version = {1}\n\
+			executable = {2}\n\
+			command = {2} -m venv {3}\n\
+		",
+		py_bin_dir.to_string_lossy(),
 		config.python,
+		py_executable.to_string_lossy(),
 		destination_path.to_string_lossy()
 	);
 
-	guard!(fs::write(destination_path.join("pyvenv.cfg"), temp), "Couldn't write pyenv.cfg");
+	step("write pyvenv.cfg", write_file(&staging_path.join("pyvenv.cfg"), pyvenv_text))?;
 
-	// Bin
-	link_python(&config.python, destination_path.join("bin"), &py_version_short);
+	// The prompt is the name of the project, like `python -m venv` does with the name of the environment
+	let prompt = match destination_path.parent().and_then(|p| p.file_name()) {
+		Some(name) => name.to_string_lossy().into_owned(),
+		None => String::from(ENV_DIR_NAME),
+	};
+	step(
+		"write the activate scripts",
+		write_activate_scripts(staging_path, destination_path, &prompt),
+	)?;
 
 	// Lib
-	let site_packages_path = destination_path.join(format!("lib/python{}/site-packages", py_version_short));
-	let _ = fs::remove_dir_all(&site_packages_path);
-
-	guard!(fs::create_dir_all(&site_packages_path), "Couldn't create folder");
-
-	// TODO: split this in another function
-	for (name, version) in config.packages {
-		let version = version
-			.try_into::<String>()
-			.ok()
-			.map(|v| match VersionReq::parse(&v) {
-				Ok(version) => version,
-				Err(e) => todo!("Couldn't read version of {} in config", name),
-			})
-			.unwrap();
-
-		// TODO: use lockfile to cache version
-		let package = find_matching_package_version(&name, &version)?;
-		link_package(&package, &site_packages_path, &config.python);
+	let site_packages_path = staging_path.join("lib").join(config.python.lib_dir_name()).join("site-packages");
+	step("create the site-packages directory", create_dir(&site_packages_path))?;
+
+	let mut package_paths = Vec::new();
+	let mut entry_points = Vec::new();
+	let requested_link_mode = link_mode;
+
+	for package in packages {
+		link_mode = step(
+			&format!("link {} {}", package.name, package.version),
+			link_package(&package, &site_packages_path, &config.python, link_mode),
+		)?;
+		let package_path = utils::get_package_path(&package);
+		entry_points.extend(step(
+			&format!("write the entry point scripts of {} {}", package.name, package.version),
+			write_entry_points(&package_path, &staging_path.join("bin"), &destination_path.join("bin/python")),
+		)?);
+		package_paths.push(package_path);
+	}
+
+	if link_mode != requested_link_mode {
+		eprintln!("Packages were linked with {} instead of {}.", link_mode, requested_link_mode);
+	}
+
+	return Ok(RegisteredEnvironment {
+		python: config.python,
+		python_executable: py_executable,
+		packages: package_paths,
+		groups: SyncedGroups::default(),
+		entry_points,
+	});
+}
+
+/// Adds the step of the environment update that failed to an error.
+fn step<T>(name: &str, result: Result<T, AnyError>) -> Result<T, AnyError> {
+	return result.map_err(|e| format!("Failed to {}: {}. The previous environment was left untouched.", name, e).into());
+}
+
+/// Replaces `destination_path` with `staging_path`.
+///
+/// # Termination
+/// - This function returns an error if the new environment can't be moved in. The previous one is then put back,
+///   or the error says where it was left.
+///
+/// # Limitations
+/// - The swap is a single step only where the system can exchange two paths (`renameat2` on Linux, `renamex_np` on macOS)
+///   and the filesystem supports it. Otherwise the previous environment is moved aside first, so `destination_path`
+///   briefly doesn't exist.
+fn swap_dirs(staging_path: &PathBuf, destination_path: &PathBuf) -> Result<(), AnyError> {
+	let had_previous = fs::symlink_metadata(destination_path).is_ok();
+	if had_previous && exchange_paths(staging_path, destination_path).is_ok() {
+		// The previous environment is now at the staging path
+		if let Err(e) = fs::remove_dir_all(staging_path) {
+			eprintln!("Couldn't remove the previous environment at {}: {}", staging_path.display(), e);
+		}
+		return Ok(());
+	}
+
+	let backup_path = get_sibling_path(destination_path, "pen-backup");
+	step("prepare the backup directory", clear_dir(&backup_path))?;
+	if had_previous {
+		step(
+			&format!("move {} aside", destination_path.display()),
+			fs::rename(destination_path, &backup_path).map_err(|e| e.into()),
+		)?;
+	}
+
+	if let Err(e) = fs::rename(staging_path, destination_path) {
+		if had_previous {
+			if let Err(restore_error) = fs::rename(&backup_path, destination_path) {
+				return error!(
+					"Couldn't move the new environment to {} ({}), nor restore the previous one ({}). It is in {}, please move it back manually.",
+					destination_path.display(),
+					e,
+					restore_error,
+					backup_path.display()
+				);
+			}
+		}
+		return step(&format!("move the new environment to {}", destination_path.display()), Err(e.into()));
+	}
+
+	if had_previous {
+		// The new environment is in place, failing to clean up the old one is not worth failing the update
+		if let Err(e) = fs::remove_dir_all(&backup_path) {
+			eprintln!("Couldn't remove the previous environment at {}: {}", backup_path.display(), e);
+		}
+	}
+	return Ok(());
+}
+
+#[cfg(target_os = "linux")]
+fn exchange_paths(first: &Path, second: &Path) -> io::Result<()> {
+	use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+	let first = CString::new(first.as_os_str().as_bytes())?;
+	let second = CString::new(second.as_os_str().as_bytes())?;
+	let result = unsafe { libc::renameat2(libc::AT_FDCWD, first.as_ptr(), libc::AT_FDCWD, second.as_ptr(), libc::RENAME_EXCHANGE) };
+	if result != 0 {
+		return Err(io::Error::last_os_error());
 	}
 	return Ok(());
 }
 
-pub fn link_python(version: &Version, destination_path: PathBuf, py_version_short: &String) -> Result<(), AnyError> {
-	let python_path = utils::get_python_path(&version);
+#[cfg(target_os = "macos")]
+fn exchange_paths(first: &Path, second: &Path) -> io::Result<()> {
+	use std::{ffi::CString, os::unix::ffi::OsStrExt};
 
-	let python_path_exists = guard!(fs::exists(&python_path), "Couldn't see if package is installed");
-	if !python_path_exists {
-		py_install_algo_v1(&version)?;
+	let first = CString::new(first.as_os_str().as_bytes())?;
+	let second = CString::new(second.as_os_str().as_bytes())?;
+	let result = unsafe { libc::renamex_np(first.as_ptr(), second.as_ptr(), libc::RENAME_SWAP) };
+	if result != 0 {
+		return Err(io::Error::last_os_error());
 	}
+	return Ok(());
+}
 
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn exchange_paths(_first: &Path, _second: &Path) -> io::Result<()> {
+	return Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		"exchanging paths is not supported on this platform",
+	));
+}
+
+/// Gets a path next to `path` (so on the same filesystem, where renames are atomic), ex. `.venv.pen-staging`.
+fn get_sibling_path(path: &Path, suffix: &str) -> PathBuf {
+	let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+	return path.with_file_name(format!("{}.{}", file_name, suffix));
+}
+
+fn clear_dir(dir_path: &PathBuf) -> Result<(), AnyError> {
+	if fs::symlink_metadata(dir_path).is_ok() {
+		guard!(fs::remove_dir_all(dir_path), "Couldn't remove {}", dir_path.display());
+	}
+	return Ok(());
+}
+
+fn create_dir(dir_path: &PathBuf) -> Result<(), AnyError> {
+	guard!(fs::create_dir_all(dir_path), "Couldn't create folder {}", dir_path.display());
+	return Ok(());
+}
+
+fn write_file(file_path: &PathBuf, contents: String) -> Result<(), AnyError> {
+	guard!(fs::write(file_path, contents), "Couldn't write {}", file_path.display());
+	return Ok(());
+}
+
+/// Links the interpreter for `python` into the `bin` directory of a virtual environment.
+///
+/// # Output
+/// - The path of the interpreter executable that was linked.
+///
+/// # Termination
+/// - This function returns an error if no interpreter can be found or installed for `python`.
+pub fn link_python(python: &PythonSpec, destination_path: PathBuf, py_version_short: &String) -> Result<PathBuf, AnyError> {
+	let py_executable = utils::get_python_executable(python)?;
+
+	// Relative links so that the environment can be built in one place and moved to another
+	symlink(py_executable.clone(), destination_path.join("python"), Some(true))?;
+	symlink(PathBuf::from("python"), destination_path.join("python3"), Some(false))?;
 	symlink(
-		python_path.join("bin/python3"), // this is a little cursed since it is dependent on python3 so idk what to do
-		destination_path.join("python"),
-		Some(true),
-	);
-	symlink(destination_path.join("python"), destination_path.join("python3"), Some(false));
-	symlink(
-		destination_path.join("python"),
+		PathBuf::from("python"),
 		destination_path.join(format!("python{}", py_version_short)),
 		Some(false),
-	);
-	return Ok(());
+	)?;
+	return Ok(py_executable);
 }
 
-pub fn link_package(package: &Package, site_packages_path: &PathBuf, py_version: &Version) -> Result<(), AnyError> {
-	let package_path = utils::get_package_path(&package);
+/// Links a package of the cache into `site_packages_path`, downloading it first if needed.
+///
+/// # Output
+/// - The link mode that worked, which can be a fallback of `link_mode`.
+pub fn link_package(package: &Package, site_packages_path: &Path, python: &PythonSpec, link_mode: LinkMode) -> Result<LinkMode, AnyError> {
+	let package_path = utils::get_package_path(package);
 
-	let package_path_exists = guard!(fs::exists(&package_path), "Couldn't see if package is installed");
-	if !package_path_exists {
-		download_package(&package, py_version);
-	}
+	utils::download_dep_if_missing(package, python)?;
 
+	let mut link_mode = link_mode;
 	let entries = guard!(fs::read_dir(&package_path), "Failed to read {}", package_path.display());
 	for directory_entry_result in entries {
 		let directory_entry = guard!(directory_entry_result, "Failed to read directory entry");
-		symlink(directory_entry.path(), site_packages_path.join(directory_entry.file_name()), None);
+		link_mode = merge_tree(&directory_entry.path(), &site_packages_path.join(directory_entry.file_name()), link_mode)?;
+	}
+	return Ok(link_mode);
+}
+
+/// Removes the files of a package from `site_packages_path`, as listed in the `RECORD` of its `.dist-info`.
+///
+/// # Arguments
+/// - `package`: The package to remove.
+/// - `site_packages_path`: The site-packages of the environment.
+/// - `other_package_paths`: The cached packages (see `get_package_path`) still linked in the environment.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if the package isn't in the cache or if a file can't be removed.
+///
+/// # Guarantees
+/// - Files and directories linked by other packages (ex. other members of a namespace package) are left untouched,
+///   and directories left empty are removed. A hardlinked, cloned or copied file is only removed if no other package lists it in its `RECORD`.
+/// - The package cache is never modified, links into it are removed rather than followed.
+pub fn unlink_package(package: &Package, site_packages_path: &PathBuf, other_package_paths: &[PathBuf]) -> Result<(), AnyError> {
+	return unlink_package_files(&utils::get_package_path(package), site_packages_path, other_package_paths);
+}
+
+fn unlink_package_files(package_path: &PathBuf, site_packages_path: &PathBuf, other_package_paths: &[PathBuf]) -> Result<(), AnyError> {
+	let record = read_package_record(package_path)?;
+
+	// Packages missing from the cache can't tell which files are theirs, and pen sync relinks them anyway
+	let mut owned_by_others = HashSet::new();
+	for other_package_path in other_package_paths.iter().filter(|path| *path != package_path && path.exists()) {
+		owned_by_others.extend(read_package_record(other_package_path)?);
+	}
+
+	let mut parent_dirs: Vec<PathBuf> = Vec::new();
+
+	for record_path in record {
+		// Files installed outside of site-packages (scripts, data) are not part of what pen links
+		if record_path.components().any(|c| !matches!(c, Component::Normal(_))) {
+			continue;
+		}
+
+		let mut current_path = site_packages_path.clone();
+		for component in record_path.components() {
+			current_path.push(component);
+			let metadata = match fs::symlink_metadata(&current_path) {
+				Ok(metadata) => metadata,
+				Err(_) => break, // Already removed, ex. through a symlink to a whole directory
+			};
+
+			if metadata.is_symlink() {
+				let target = guard!(fs::read_link(&current_path), "Couldn't read link {}", current_path.display());
+				if target.starts_with(package_path) {
+					guard!(fs::remove_file(&current_path), "Couldn't remove {}", current_path.display());
+					parent_dirs.push(current_path.parent().expect("Paths in site-packages have a parent").to_path_buf());
+				}
+				break;
+			}
+			if !metadata.is_dir() {
+				// Hardlinked, cloned or copied file, which another package can install too
+				if owned_by_others.contains(&record_path) {
+					break;
+				}
+				guard!(fs::remove_file(&current_path), "Couldn't remove {}", current_path.display());
+				parent_dirs.push(current_path.parent().expect("Paths in site-packages have a parent").to_path_buf());
+				break;
+			}
+		}
+	}
+
+	// Entries not listed in RECORD, ex. __pycache__ directories created when building the cache
+	let entries = guard!(fs::read_dir(site_packages_path), "Failed to read {}", site_packages_path.display());
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		if let Ok(target) = fs::read_link(entry.path()) {
+			if target.starts_with(package_path) {
+				guard!(fs::remove_file(entry.path()), "Couldn't remove {}", entry.path().display());
+			}
+		}
+	}
+
+	// Deepest directories first, so that a parent only gets checked once its children are gone
+	parent_dirs.sort_unstable_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+	parent_dirs.dedup();
+	for dir in parent_dirs {
+		let mut dir = dir;
+		while dir.starts_with(site_packages_path) && dir != *site_packages_path {
+			// Fails when the directory isn't empty, which is where we stop
+			if fs::remove_dir(&dir).is_err() {
+				break;
+			}
+			dir.pop();
+		}
 	}
 	return Ok(());
 }
 
+/// Writes the entry point scripts of a package of the cache (its `console_scripts` and `gui_scripts`) into the `bin` directory of an environment,
+/// like pip does when it installs a wheel.
+///
+/// # Arguments
+/// - `package_path`: The package in the cache, see `get_package_path`.
+/// - `bin_path`: Where the scripts are written.
+/// - `python_path`: The interpreter of the environment once it is in place, the shebang of the scripts.
+///
+/// # Output
+/// - The names of the scripts written. A script named like the interpreter or like a file already in `bin_path` isn't written.
+///
+/// # Termination
+/// - This function returns an error if the package has no `.dist-info` or if a script can't be written.
+fn write_entry_points(package_path: &PathBuf, bin_path: &Path, python_path: &Path) -> Result<Vec<String>, AnyError> {
+	let mut written = Vec::new();
+	for entry_point in read_entry_points(&get_dist_info_path(package_path)?) {
+		let script_path = bin_path.join(&entry_point.name);
+		if entry_point.name.starts_with("python") || entry_point.name.contains('/') || fs::symlink_metadata(&script_path).is_ok() {
+			continue;
+		}
+
+		// `Cli.main` is called as `Cli.main()` after importing `Cli`
+		let imported = entry_point.function.split('.').next().unwrap_or_default();
+		let script = [
+			format!("#!{}", python_path.display()),
+			String::from("# -*- coding: utf-8 -*-"),
+			String::from("import re"),
+			String::from("import sys"),
+			format!("from {} import {}", entry_point.module, imported),
+			String::from("if __name__ == \"__main__\":"),
+			String::from("    sys.argv[0] = re.sub(r\"(-script\\.pyw|\\.exe)?$\", \"\", sys.argv[0])"),
+			format!("    sys.exit({}())", entry_point.function),
+			String::new(),
+		]
+		.join("\n");
+		guard!(fs::write(&script_path, script), "Couldn't write {}", script_path.display());
+		guard!(
+			fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755)),
+			"Couldn't make {} executable",
+			script_path.display()
+		);
+		written.push(entry_point.name);
+	}
+	return Ok(written);
+}
+
+/// Removes the entry point scripts of a package (its `console_scripts` and `gui_scripts`) from the `bin` directory of an environment.
+///
+/// # Arguments
+/// - `package`: The package whose scripts are removed.
+/// - `bin_path`: The `bin` directory of the environment.
+/// - `written_scripts`: The scripts pen wrote in `bin_path`, see `RegisteredEnvironment::entry_points`.
+///
+/// # Output
+/// - The names of the scripts removed.
+///
+/// # Termination
+/// - This function returns an error if the package isn't in the cache or if a script can't be removed.
+///
+/// # Guarantees
+/// - Only scripts in `written_scripts` are removed, never one written by another tool under the same name, nor the interpreter.
+pub fn unlink_entry_points(package: &Package, bin_path: &Path, written_scripts: &[String]) -> Result<Vec<String>, AnyError> {
+	let package_path = utils::get_package_path(package);
+	let dist_info_path = get_dist_info_path(&package_path)?;
+	let mut removed = Vec::new();
+	for script_name in read_entry_points(&dist_info_path).into_iter().map(|entry_point| entry_point.name) {
+		if !written_scripts.contains(&script_name) {
+			continue;
+		}
+
+		let script_path = bin_path.join(&script_name);
+		if fs::symlink_metadata(&script_path).is_ok() {
+			guard!(fs::remove_file(&script_path), "Couldn't remove {}", script_path.display());
+		}
+		removed.push(script_name);
+	}
+	return Ok(removed);
+}
+
+/// Finds the `.dist-info` directory of a package in the cache.
+fn get_dist_info_path(package_path: &PathBuf) -> Result<PathBuf, AnyError> {
+	let entries = guard!(fs::read_dir(package_path), "Failed to read {}", package_path.display());
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		if entry.file_name().to_string_lossy().ends_with(".dist-info") {
+			return Ok(entry.path());
+		}
+	}
+	return error!("{} has no .dist-info directory.", package_path.display());
+}
+
+/// Reads the paths listed in the `RECORD` of a package in the cache.
+fn read_package_record(package_path: &PathBuf) -> Result<Vec<PathBuf>, AnyError> {
+	let record = read_record(&get_dist_info_path(package_path)?.join("RECORD"))?;
+	return Ok(record.into_iter().map(|entry| entry.path).collect());
+}
+
 fn symlink(original: PathBuf, link: PathBuf, remove_existing: Option<bool>) -> Result<(), AnyError> {
 	match fs::read_link(&link) {
 		Ok(_) => match remove_existing {
@@ -111,3 +626,90 @@ fn symlink(original: PathBuf, link: PathBuf, remove_existing: Option<bool>) -> R
 	);
 	return Ok(());
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+
+	/// Writes a package to `cache_path` with the given files, listed in its `RECORD`, and copies them into `site_packages_path`.
+	fn write_package(cache_path: &Path, site_packages_path: &PathBuf, name: &str, files: &[&str]) -> PathBuf {
+		let package_path = cache_path.join(format!("{}_1.0.0", name));
+		let dist_info = format!("{}-1.0.0.dist-info", name);
+		let mut record = String::new();
+		for file in files.iter().copied().chain([format!("{}/RECORD", dist_info).as_str()]) {
+			record.push_str(&format!("{},,\n", file));
+		}
+		for file in files {
+			for root in [&package_path, site_packages_path] {
+				fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+				fs::write(root.join(file), file).unwrap();
+			}
+		}
+		fs::create_dir_all(package_path.join(&dist_info)).unwrap();
+		fs::write(package_path.join(&dist_info).join("RECORD"), record).unwrap();
+		return package_path;
+	}
+
+	#[test]
+	fn unlink_keeps_files_listed_by_other_packages() {
+		let root = env::temp_dir().join(format!("pen-test-unlink-{}", std::process::id()));
+		let cache_path = root.join("cache");
+		let site_packages_path = root.join("site-packages");
+		fs::create_dir_all(&site_packages_path).unwrap();
+
+		// Two members of the `shared` namespace package, which both install its __init__.py
+		let first = write_package(&cache_path, &site_packages_path, "first", &["shared/__init__.py", "shared/first.py"]);
+		let second = write_package(&cache_path, &site_packages_path, "second", &["shared/__init__.py", "shared/second.py"]);
+
+		let result = unlink_package_files(&first, &site_packages_path, &[first.clone(), second.clone()]);
+		let shared_init_exists = site_packages_path.join("shared/__init__.py").exists();
+		let first_exists = site_packages_path.join("shared/first.py").exists();
+		let second_exists = site_packages_path.join("shared/second.py").exists();
+
+		// Once the second one is gone too, nothing is left
+		let second_result = unlink_package_files(&second, &site_packages_path, &[]);
+		let shared_exists = site_packages_path.join("shared").exists();
+		fs::remove_dir_all(&root).unwrap();
+
+		assert!(result.is_ok());
+		assert!(shared_init_exists);
+		assert!(!first_exists);
+		assert!(second_exists);
+		assert!(second_result.is_ok());
+		assert!(!shared_exists);
+	}
+
+	#[test]
+	fn entry_point_scripts_are_written() {
+		let root = env::temp_dir().join(format!("pen-test-entry-points-{}", std::process::id()));
+		let bin_path = root.join("bin");
+		fs::create_dir_all(&bin_path).unwrap();
+		fs::write(bin_path.join("activate"), "").unwrap();
+		let package_path = write_package(&root.join("cache"), &root.join("site-packages"), "tool", &["tool/__init__.py"]);
+		let entry_points = [
+			"[console_scripts]",
+			"tool = tool.cli:main",
+			"tool-admin = tool.cli:Admin.run [admin]",
+			"python = tool:hijack",
+			"activate = tool:hijack",
+			"",
+			"[tool.plugins]",
+			"plugin = tool:plugin",
+		];
+		fs::write(package_path.join("tool-1.0.0.dist-info/entry_points.txt"), entry_points.join("\n")).unwrap();
+
+		let written = write_entry_points(&package_path, &bin_path, &PathBuf::from("/project/.venv/bin/python"));
+		let script = fs::read_to_string(bin_path.join("tool-admin")).unwrap_or_default();
+		let mode = fs::metadata(bin_path.join("tool")).map(|metadata| metadata.permissions().mode() & 0o777);
+		let activate = fs::read_to_string(bin_path.join("activate")).unwrap_or_default();
+		fs::remove_dir_all(&root).unwrap();
+
+		assert_eq!(written.unwrap(), vec!["tool", "tool-admin"]);
+		assert!(script.starts_with("#!/project/.venv/bin/python\n"));
+		assert!(script.contains("from tool.cli import Admin\n"));
+		assert!(script.contains("sys.exit(Admin.run())"));
+		assert_eq!(mode.ok(), Some(0o755));
+		assert!(activate.is_empty());
+	}
+}
@@ -0,0 +1,33 @@
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+
+use crate::constants::PEN_CONFIG_FILE;
+use crate::utils::{guard, AnyError, LinkMode};
+
+/// Reads the user wide pen configuration file.
+///
+/// # Output
+/// - The parsed `GlobalConfig`. Missing keys take their default value, so an empty file is valid.
+///
+/// # Termination
+/// - This function returns an error if the file can't be read or isn't valid toml.
+pub fn read_global_config() -> Result<GlobalConfig, AnyError> {
+	let contents = guard!(fs::read_to_string(&*PEN_CONFIG_FILE), "Couldn't read {}.", PEN_CONFIG_FILE.display());
+	let config = guard!(toml::from_str::<GlobalConfig>(&contents), "Couldn't parse {}.", PEN_CONFIG_FILE.display());
+	return Ok(config);
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct GlobalConfig {
+	/// Path to a file in the `sha256sum` format listing the checksums of trusted Python tarballs.
+	pub python_checksums: Option<PathBuf>,
+	/// Path to a minisign public key used to verify the detached signature of Python tarballs.
+	pub python_public_key: Option<PathBuf>,
+	/// Base URL of the `<tarball file name>.minisig` signatures, ex. a mirror, since the Python releases don't publish minisign signatures.
+	pub python_signatures_url: Option<String>,
+	/// When true, only interpreters installed by pen are used, even if the system already has the requested version.
+	pub ignore_system_pythons: bool,
+	/// How packages are put into environments when `pen sync` isn't given `--link-mode`.
+	pub link_mode: Option<LinkMode>,
+}
@@ -0,0 +1,88 @@
+use std::{
+	fs::{File, OpenOptions, TryLockError},
+	path::{Path, PathBuf},
+};
+
+use crate::constants::{CACHE_LOCK_FILE, LOCKS_DIR, PEN_DIR};
+use crate::utils::{guard, AnyError};
+
+/// An advisory lock on a file, released when dropped.
+pub struct FileLock {
+	_file: File,
+}
+
+/// Takes an exclusive lock on `lock_path`, waiting for other processes to release it if needed.
+///
+/// # Termination
+/// - This function returns an error if the lock file can't be opened or locked.
+pub fn lock_exclusive(lock_path: &PathBuf) -> Result<FileLock, AnyError> {
+	return lock(lock_path, true);
+}
+
+/// Takes an exclusive lock on `lock_path` only if no other process holds it.
+///
+/// # Output
+/// - `None` if another process holds the lock.
+pub fn try_lock_exclusive(lock_path: &PathBuf) -> Result<Option<FileLock>, AnyError> {
+	let file = open_lock_file(lock_path)?;
+	match file.try_lock() {
+		Ok(()) => return Ok(Some(FileLock { _file: file })),
+		Err(TryLockError::WouldBlock) => return Ok(None),
+		Err(TryLockError::Error(e)) => {
+			eprintln!("[{}:{}] - Couldn't lock {}", file!(), line!(), lock_path.display());
+			return Err(e.into());
+		}
+	}
+}
+
+/// Locks the whole cache (interpreters and packages) until the lock is dropped.
+///
+/// # Arguments
+/// - `exclusive`: Commands that delete from the cache need an exclusive lock, every other command takes a shared one
+///   so that nothing gets deleted while it is in use.
+///
+/// # Limitations
+/// - The lock isn't inherited by the commands pen starts. Commands that wait on a user command (ex. `pen run` with a script)
+///   drop it before starting it, so that a long-running command doesn't block `pen cache prune` or `pen delete`.
+pub fn lock_cache(exclusive: bool) -> Result<FileLock, AnyError> {
+	return lock(&CACHE_LOCK_FILE, exclusive);
+}
+
+/// Gets the path of the lock file guarding the installation of a directory of the cache, ex. a package or an interpreter.
+pub fn get_lock_path(path: &Path) -> PathBuf {
+	let relative_path = path.strip_prefix(&*PEN_DIR).unwrap_or(path);
+	let lock_name = relative_path.to_string_lossy().replace('/', "-");
+	return LOCKS_DIR.join(format!("{}.lock", lock_name));
+}
+
+fn lock(lock_path: &PathBuf, exclusive: bool) -> Result<FileLock, AnyError> {
+	let file = open_lock_file(lock_path)?;
+
+	let try_result = match exclusive {
+		true => file.try_lock(),
+		false => file.try_lock_shared(),
+	};
+	match try_result {
+		Ok(()) => return Ok(FileLock { _file: file }),
+		Err(TryLockError::WouldBlock) => eprintln!("Waiting for another pen process to finish..."),
+		Err(TryLockError::Error(e)) => {
+			eprintln!("[{}:{}] - Couldn't lock {}", file!(), line!(), lock_path.display());
+			return Err(e.into());
+		}
+	}
+
+	match exclusive {
+		true => guard!(file.lock(), "Couldn't lock {}", lock_path.display()),
+		false => guard!(file.lock_shared(), "Couldn't lock {}", lock_path.display()),
+	}
+	return Ok(FileLock { _file: file });
+}
+
+fn open_lock_file(lock_path: &PathBuf) -> Result<File, AnyError> {
+	let file = guard!(
+		OpenOptions::new().create(true).truncate(false).write(true).open(lock_path),
+		"Couldn't open lock file {}",
+		lock_path.display()
+	);
+	return Ok(file);
+}
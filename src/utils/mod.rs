@@ -1,11 +1,16 @@
 mod env_utils;
 mod error;
+mod global_config;
+mod lock;
 mod path;
 mod py_utils;
+#[allow(clippy::module_inception)]
 mod utils;
 
 pub use env_utils::*;
 pub use error::*;
+pub use global_config::*;
+pub use lock::*;
 pub use path::*;
 pub use py_utils::*;
 pub use utils::*;
@@ -1,27 +1,27 @@
-use crate::constants::{PYTHON_PACKAGES_DIR, PYTHON_VERSIONS_DIR, TMP_DIR};
-use crate::utils::{error, guard, AnyError, Package};
-use semver::Version;
+use crate::constants::{CONFIG_FILE_NAME, PYPROJECT_FILE_NAME, PYTHON_PACKAGES_DIR, PYTHON_VERSIONS_DIR, TEMP_ROOT_DIR, TMP_DIR};
+use crate::utils::{error, get_lock_path, guard, lock_exclusive, try_lock_exclusive, AnyError, FileLock, Package, PythonImplementation, PythonSpec};
 use std::{env, fs, path::PathBuf};
 
-/// Constructs the path to the directory for a specified Python version without validating the format of the version string.
+/// Constructs the path to the directory for a specified Python interpreter.
 ///
 /// # Arguments
-/// - `py_version`: A string slice representing the Python version, which has been
-///   validated to conform to the expected format (major.minor.patch).
+/// - `python`: The interpreter. CPython versions are stored as `major.minor.patch`,
+///   the other implementations as `implementation-major.minor` (ex. `pypy-3.10`).
 ///
 /// # Output
-/// - A `PathBuf` pointing to the directory associated with the specified Python version.
+/// - A `PathBuf` pointing to the directory associated with the specified Python interpreter.
 ///
 /// # Termination
 /// - This function does not terminate.
 ///
-/// # Guarantees
-/// - The returned path will be correctly formed if `py_version` is well formatted.
-///
 /// # Limitations
 /// - The function does not validate the contents of the constructed path or its existence.
-pub fn get_python_path(version: &Version) -> PathBuf {
-	PYTHON_VERSIONS_DIR.join(format!("{}.{}.{}", version.major, version.minor, version.patch))
+pub fn get_python_path(python: &PythonSpec) -> PathBuf {
+	let version = &python.version;
+	match python.implementation {
+		PythonImplementation::CPython => PYTHON_VERSIONS_DIR.join(format!("{}.{}.{}", version.major, version.minor, version.patch)),
+		_ => PYTHON_VERSIONS_DIR.join(format!("{}-{}", python.implementation.name(), python.short_version())),
+	}
 }
 
 // todo docstring
@@ -32,19 +32,28 @@ pub fn get_package_path(package: &Package) -> PathBuf {
 	))
 }
 
-// todo docstring
+/// Finds the root of the project the current directory is in.
+///
+/// # Output
+/// - The closest directory, starting from the current one, with a `pen.toml` or a `pyproject.toml`.
+///
+/// # Termination
+/// - This function returns an error if no directory above has one.
 pub fn get_project_root() -> Result<PathBuf, AnyError> {
 	let mut dir = guard!(env::current_dir(), "Failed to get current working directory.");
 
 	loop {
-		match guard!(fs::exists(dir.join("pen.toml")), "Failed to check if pen.toml exists.") {
-			true => return Ok(dir),
-			false => {
-				if !dir.pop() {
-					return error!("Couldn't find a pen.toml filein the current directory or any above.");
-				}
+		for file_name in [CONFIG_FILE_NAME, PYPROJECT_FILE_NAME] {
+			if guard!(fs::exists(dir.join(file_name)), "Failed to check if {} exists.", file_name) {
+				return Ok(dir);
 			}
 		}
+		if !dir.pop() {
+			return error!(
+				"Couldn't find a {} or a {} in the current directory or any above.",
+				CONFIG_FILE_NAME, PYPROJECT_FILE_NAME
+			);
+		}
 	}
 }
 
@@ -74,10 +83,10 @@ pub fn try_deleting_dir_to_temp(dir_path: &PathBuf, temp_dir: &PathBuf) -> Resul
 		}
 	}
 	if guard!(temp_dir.try_exists(), "Unable to know if {} exists", temp_dir.display()) {
-		guard!(fs::remove_dir_all(&temp_dir), "todo")
+		guard!(fs::remove_dir_all(temp_dir), "todo")
 	}
 
-	fs::rename(&dir_path, &temp_dir)?;
+	fs::rename(dir_path, temp_dir)?;
 
 	if guard!(dir_path.try_exists(), "todo") {
 		return error!("Directory still exists");
@@ -86,7 +95,28 @@ pub fn try_deleting_dir_to_temp(dir_path: &PathBuf, temp_dir: &PathBuf) -> Resul
 	return Ok(());
 }
 
-/// Clears and recreates the temporary directory.
+/// Creates the temporary directory of this process and locks it for the lifetime of the process.
+///
+/// # Output
+/// - The lock, which must be kept alive for as long as `TMP_DIR` is used.
+///
+/// # Termination
+/// - If locking, removal or creation operations fail, the function prints an error message and returns an error.
+///
+/// # Guarantees
+/// - If this function returns, `TMP_DIR` exists, is empty and won't be cleared by other pen processes.
+pub fn create_process_temp() -> Result<FileLock, AnyError> {
+	let temp_lock = lock_exclusive(&get_lock_path(&TMP_DIR))?;
+
+	// A directory left by a dead process that had the same pid
+	if guard!(TMP_DIR.try_exists(), "Unable to know if {} exists", TMP_DIR.display()) {
+		guard!(fs::remove_dir_all(&*TMP_DIR), "Failed to clear directory {}", TMP_DIR.display());
+	}
+	guard!(fs::create_dir_all(&*TMP_DIR), "Failed to create directory {}", TMP_DIR.display());
+	return Ok(temp_lock);
+}
+
+/// Clears the temporary directories left by pen processes that are not running anymore.
 ///
 /// # Input
 /// - None.
@@ -95,16 +125,38 @@ pub fn try_deleting_dir_to_temp(dir_path: &PathBuf, temp_dir: &PathBuf) -> Resul
 /// - None.
 ///
 /// # Termination
-/// - If either removal or creation operations fail, the function prints an error message and terminates the process.
+/// - If a removal operation fails, the function prints an error message and returns an error.
+///
+/// # Guarantees
+/// - The temporary directories of running pen processes are left untouched, since they hold their lock.
 pub fn clear_temp() -> Result<(), AnyError> {
-	let mut dir_entries = guard!((&*TMP_DIR).read_dir(), "Failed to check contents of directory {}", (*TMP_DIR).display());
-	let temp_is_empty = dir_entries.next().is_none();
+	let dir_entries = guard!(
+		TEMP_ROOT_DIR.read_dir(),
+		"Failed to check contents of directory {}",
+		TEMP_ROOT_DIR.display()
+	);
 
-	if temp_is_empty {
-		return Ok(());
-	}
+	for dir_entry in dir_entries {
+		let dir_entry = guard!(dir_entry, "Failed to read directory entry");
+		let entry_path = dir_entry.path();
+		if entry_path == *TMP_DIR {
+			continue;
+		}
 
-	guard!(fs::remove_dir_all(&*TMP_DIR), "Failed to clear directory {}", (*TMP_DIR).display());
-	guard!(fs::create_dir(&*TMP_DIR), "Failed to create directory {}", (*TMP_DIR).display());
+		let lock_path = get_lock_path(&entry_path);
+		let lock = match try_lock_exclusive(&lock_path)? {
+			Some(lock) => lock,
+			None => continue, // Still in use
+		};
+
+		let entry_type = guard!(dir_entry.file_type(), "Failed to read metadata");
+		if entry_type.is_dir() {
+			guard!(fs::remove_dir_all(&entry_path), "Failed to clear directory {}", entry_path.display());
+		} else {
+			guard!(fs::remove_file(&entry_path), "Failed to clear file {}", entry_path.display());
+		}
+		guard!(fs::remove_file(&lock_path), "Failed to remove lock file {}", lock_path.display());
+		drop(lock);
+	}
 	return Ok(());
 }
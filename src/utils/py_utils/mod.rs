@@ -1,2 +1,12 @@
+mod py_discover;
+mod py_install;
 mod py_install_algo_v1;
+mod py_install_prebuilt;
+mod py_spec;
+mod py_verify_tarball;
+pub use py_discover::*;
+pub use py_install::py_install;
 pub use py_install_algo_v1::py_install_algo_v1;
+pub use py_install_prebuilt::py_install_prebuilt;
+pub use py_spec::*;
+pub use py_verify_tarball::verify_python_tarball;
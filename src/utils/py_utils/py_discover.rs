@@ -0,0 +1,202 @@
+use semver::Version;
+use std::{env, fmt, fs, path::PathBuf, process};
+
+use crate::constants::{HOME_DIR, PYTHON_VERSIONS_DIR};
+use crate::utils::{self, error, guard, py_install, read_global_config, AnyError, PythonSpec};
+
+// Prints the implementation, the exact version and the ABI of the interpreter running it, one per line.
+static PROBE_SCRIPT: &str = "import sys, sysconfig; print(sys.implementation.name); print('.'.join(map(str, sys.version_info[:3]))); print(sysconfig.get_config_var('SOABI') or sys.implementation.cache_tag)";
+
+pub struct DiscoveredPython {
+	pub executable: PathBuf,
+	pub implementation: String,
+	pub version: Version,
+	pub abi: String,
+	pub source: PythonSource,
+}
+
+/// Where an interpreter was found.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PythonSource {
+	Pen,
+	System,
+	Pyenv,
+	Uv,
+	Asdf,
+	Path,
+}
+
+impl fmt::Display for PythonSource {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let marker = match self {
+			PythonSource::Pen => "pen",
+			PythonSource::System => "system",
+			PythonSource::Pyenv => "pyenv",
+			PythonSource::Uv => "uv",
+			PythonSource::Asdf => "asdf",
+			PythonSource::Path => "PATH",
+		};
+		return write!(f, "{}", marker);
+	}
+}
+
+/// Finds the Python interpreters installed outside of pen.
+///
+/// # Output
+/// - Every interpreter found on `PATH`, in the common system prefixes and in the directories of other version managers,
+///   each probed for its exact version and ABI. Interpreters reachable through several paths are only listed once.
+///
+/// # Termination
+/// - This function returns an error if a directory that exists can't be read.
+///
+/// # Limitations
+/// - Executables that fail to run the probe are silently ignored.
+pub fn discover_system_pythons() -> Result<Vec<DiscoveredPython>, AnyError> {
+	let mut search_dirs = vec![
+		(PathBuf::from("/usr/bin"), PythonSource::System),
+		(PathBuf::from("/usr/local/bin"), PythonSource::System),
+		(PathBuf::from("/opt/homebrew/bin"), PythonSource::System),
+	];
+	for (manager_dir, source) in [
+		(HOME_DIR.join(".pyenv/versions"), PythonSource::Pyenv),
+		(HOME_DIR.join(".local/share/uv/python"), PythonSource::Uv),
+		(HOME_DIR.join(".asdf/installs/python"), PythonSource::Asdf),
+	] {
+		if !guard!(manager_dir.try_exists(), "Unable to know if {} exists", manager_dir.display()) {
+			continue;
+		}
+		let entries = guard!(fs::read_dir(&manager_dir), "Failed to read {}", manager_dir.display());
+		for entry in entries {
+			let entry = guard!(entry, "Failed to read directory entry");
+			search_dirs.push((entry.path().join("bin"), source));
+		}
+	}
+	if let Some(path_var) = env::var_os("PATH") {
+		for dir in env::split_paths(&path_var) {
+			// Shims are scripts that forward to another interpreter, which is found through its real directory instead.
+			if !dir.ends_with("shims") {
+				search_dirs.push((dir, PythonSource::Path));
+			}
+		}
+	}
+
+	let mut seen_executables: Vec<PathBuf> = Vec::new();
+	let mut pythons = Vec::new();
+
+	for (dir, source) in search_dirs {
+		let entries = match fs::read_dir(&dir) {
+			Ok(entries) => entries,
+			Err(_) => continue, // Missing directories are expected, not every machine has every prefix
+		};
+		for entry in entries {
+			let entry = guard!(entry, "Failed to read directory entry");
+			if !is_python_executable_name(&entry.file_name().to_string_lossy()) {
+				continue;
+			}
+			let executable = entry.path();
+			let canonical = match fs::canonicalize(&executable) {
+				Ok(path) => path,
+				Err(_) => continue,
+			};
+			if canonical.starts_with(&*PYTHON_VERSIONS_DIR) || seen_executables.contains(&canonical) {
+				continue;
+			}
+			seen_executables.push(canonical);
+
+			if let Some(python) = probe_python(&executable, source) {
+				pythons.push(python);
+			}
+		}
+	}
+	return Ok(pythons);
+}
+
+/// Runs an interpreter to get its implementation, version and ABI.
+///
+/// # Output
+/// - `None` if the executable can't be run or doesn't print what is expected.
+pub fn probe_python(executable: &PathBuf, source: PythonSource) -> Option<DiscoveredPython> {
+	let output = process::Command::new(executable)
+		.stdin(process::Stdio::null())
+		.stderr(process::Stdio::null())
+		.arg("-c")
+		.arg(PROBE_SCRIPT)
+		.output()
+		.ok()?;
+
+	if !output.status.success() {
+		return None;
+	}
+
+	let stdout = String::from_utf8(output.stdout).ok()?;
+	let mut lines = stdout.lines();
+	let implementation = lines.next()?.to_string();
+	let version = Version::parse(lines.next()?).ok()?;
+	let abi = lines.next()?.to_string();
+
+	return Some(DiscoveredPython {
+		executable: executable.clone(),
+		implementation,
+		version,
+		abi,
+		source,
+	});
+}
+
+/// Gets the interpreter executable to use for a Python interpreter.
+///
+/// # Output
+/// - The path of the executable. Pen's own installs are preferred, then matching interpreters found on the system
+///   (unless `ignore_system_pythons` is set), and the interpreter is only installed when neither exists.
+///
+/// # Termination
+/// - This function returns an error if the version has to be installed and the installation fails.
+///
+/// # Guarantees
+/// - If this function returns, the returned executable exists.
+pub fn get_python_executable(python: &PythonSpec) -> Result<PathBuf, AnyError> {
+	let pen_executable = utils::get_python_path(python).join(python.implementation.executable());
+	if guard!(pen_executable.try_exists(), "Unable to know if {} exists", pen_executable.display()) {
+		return Ok(pen_executable);
+	}
+
+	if !read_global_config()?.ignore_system_pythons {
+		let discovered = discover_system_pythons()?
+			.into_iter()
+			.find(|discovered| python.matches(&discovered.implementation, &discovered.version));
+
+		if let Some(discovered) = discovered {
+			eprintln!(
+				"Using Python {} found at {} ({}).",
+				python,
+				discovered.executable.display(),
+				discovered.source
+			);
+			return Ok(discovered.executable);
+		}
+	}
+
+	py_install(python)?;
+	// An interrupted install can leave the version directory without its executable
+	if !pen_executable.exists() {
+		return error!(
+			"{} was installed but {} doesn't exist, delete it with `pen delete` and try again.",
+			python,
+			pen_executable.display()
+		);
+	}
+	return Ok(pen_executable);
+}
+
+fn is_python_executable_name(file_name: &str) -> bool {
+	if file_name == "graalpy" {
+		return true;
+	}
+	match file_name.strip_prefix("python3").or_else(|| file_name.strip_prefix("pypy3")) {
+		Some("") => true,
+		Some(minor) => minor
+			.strip_prefix('.')
+			.is_some_and(|minor| !minor.is_empty() && minor.chars().all(|c| c.is_ascii_digit())),
+		None => false,
+	}
+}
@@ -0,0 +1,24 @@
+use crate::utils::{self, get_lock_path, lock_exclusive, py_install_algo_v1, py_install_prebuilt, AnyError, PythonImplementation, PythonSpec};
+
+/// Installs a Python interpreter with the install algorithm of its implementation.
+///
+/// # Arguments
+/// - `python`: The interpreter to install.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if the installation fails.
+///
+/// # Guarantees
+/// - If this function returns `Ok(())`, the interpreter is installed in the directory given by `get_python_path`.
+/// - Concurrent pen processes install a given interpreter only once, the others wait for it and reuse it.
+pub fn py_install(python: &PythonSpec) -> Result<(), AnyError> {
+	let _python_lock = lock_exclusive(&get_lock_path(&utils::get_python_path(python)))?;
+
+	match python.implementation {
+		PythonImplementation::CPython => return py_install_algo_v1(python),
+		PythonImplementation::PyPy | PythonImplementation::GraalPy => return py_install_prebuilt(python),
+	}
+}
@@ -1,19 +1,20 @@
 use semver::Version;
 
 use crate::constants::TMP_DIR;
-use crate::utils::{self, error, guard, AnyError};
+use crate::utils::{self, error, guard, AnyError, PythonSpec};
 use std::{fs, path::PathBuf, process};
 
 // todo put a file in the py_utils dir with the job of selecting the file with the function with the appropriate python install algo version. I changed the name of this function but it was a mistake, remove the v1
-pub fn py_install_algo_v1(version: &Version) -> Result<(), AnyError> {
-	let version_dir = utils::get_python_path(&version);
+pub fn py_install_algo_v1(python: &PythonSpec) -> Result<(), AnyError> {
+	let version = &python.version;
+	let version_dir = utils::get_python_path(python);
 
 	if guard!(version_dir.try_exists(), "todo") {
-		println!("{} is already installed", version_dir.display());
+		eprintln!("{} is already installed", version_dir.display());
 		return Ok(());
 	}
 
-	println!("Installing Python version: {}", &version);
+	eprintln!("Installing Python version: {}", &version);
 
 	let temp_tarball_path = TMP_DIR.join("temp_tarball.tgz"); // todo remove hardcoded value
 	let python_tarball_url = format!(
@@ -21,13 +22,20 @@ pub fn py_install_algo_v1(version: &Version) -> Result<(), AnyError> {
 		&version, &version
 	);
 
-	println!("Downloading Python installation files.");
-	utils::download_file(&python_tarball_url, &temp_tarball_path);
+	eprintln!("Downloading Python installation files.");
+	guard!(
+		utils::download_file(&python_tarball_url, &temp_tarball_path),
+		"Failed to download Python version {}",
+		version
+	);
+
+	eprintln!("Verifying Python installation files.");
+	utils::verify_python_tarball(python, &python_tarball_url, &temp_tarball_path)?;
 
-	println!("Building Python from source.");
-	unpack_and_install_python_version_v1(&version, &version_dir, &temp_tarball_path);
+	eprintln!("Building Python from source.");
+	unpack_and_install_python_version_v1(version, &temp_tarball_path)?;
 
-	println!("Verifying Python install.");
+	eprintln!("Verifying Python install.");
 	let temp_python_version_dir = TMP_DIR.join("temp_python_version_download");
 	let temp_python_bin = temp_python_version_dir.join("bin/python3"); // todo remove hardcoded value
 	let python_process = process::Command::new(temp_python_bin)
@@ -43,21 +51,26 @@ pub fn py_install_algo_v1(version: &Version) -> Result<(), AnyError> {
 		return error!("Failed to verify if Python version is installed.");
 	}
 
-	println!("Moving files...");
+	eprintln!("Moving files...");
 
 	if let Err(e1) = fs::rename(&temp_python_version_dir, &version_dir) {
 		if let Err(e2) = utils::try_deleting_dir(&version_dir) {
-			eprintln!("todo catastrophic message with both e1 and e2");
-		} else {
-			return error!("Failed to move Python version {}", version);
+			return error!(
+				"Failed to move Python version {} ({}) and to clean up {} ({}), please remove it manually",
+				version,
+				e1,
+				version_dir.display(),
+				e2
+			);
 		}
+		return error!("Failed to move Python version {}: {}", version, e1);
 	}
 
-	println!("Python version {} installed successfully.", &version);
+	eprintln!("Python version {} installed successfully.", &version);
 	return Ok(());
 }
 
-fn unpack_and_install_python_version_v1(py_version: &Version, py_version_dir: &PathBuf, temp_tarball_path: &PathBuf) -> Result<(), AnyError> {
+fn unpack_and_install_python_version_v1(py_version: &Version, temp_tarball_path: &PathBuf) -> Result<(), AnyError> {
 	// Preparing directories
 	let temp_extract_path_dir = TMP_DIR.join("temp_python_extract");
 	let temp_python_version_dir = TMP_DIR.join("temp_python_version_download");
@@ -73,14 +86,14 @@ fn unpack_and_install_python_version_v1(py_version: &Version, py_version_dir: &P
 	);
 	guard!(fs::create_dir(&temp_extract_path_dir), "Failed to create temp extract directory");
 
-	println!("Extracting tarball...");
+	eprintln!("Extracting tarball...");
 
 	let tar_process = process::Command::new("tar")
 		.stdin(process::Stdio::null())
 		.stdout(process::Stdio::null())
 		.stderr(process::Stdio::null())
 		.arg("-xzf")
-		.arg(&temp_tarball_path)
+		.arg(temp_tarball_path)
 		.arg("-C")
 		.arg(&temp_extract_path_dir)
 		.status();
@@ -90,7 +103,7 @@ fn unpack_and_install_python_version_v1(py_version: &Version, py_version_dir: &P
 		return error!("Failed to extract Python version {}", py_version);
 	}
 
-	println!("Configuring Python...");
+	eprintln!("Configuring Python...");
 
 	let source_name = format!("Python-{}", py_version);
 	let source_dir = temp_extract_path_dir.join(PathBuf::from(source_name));
@@ -108,7 +121,7 @@ fn unpack_and_install_python_version_v1(py_version: &Version, py_version_dir: &P
 		return error!("Failed to configure Python version {}", py_version);
 	}
 
-	println!("Compiling (this might take a few minutes)...");
+	eprintln!("Compiling (this might take a few minutes)...");
 
 	let make_process1 = process::Command::new("make")
 		.stdin(process::Stdio::null())
@@ -122,7 +135,7 @@ fn unpack_and_install_python_version_v1(py_version: &Version, py_version_dir: &P
 		return error!("Failed to make Python version {}", py_version);
 	}
 
-	println!("Finishing Build...");
+	eprintln!("Finishing Build...");
 
 	let make_process2 = process::Command::new("make")
 		.stdin(process::Stdio::null())
@@ -0,0 +1,163 @@
+use serde::Deserialize;
+use std::{
+	env::consts::{ARCH, OS},
+	fs, process,
+};
+
+use crate::constants::{GRAALPY_RELEASES, TMP_DIR};
+use crate::utils::{self, error, guard, AnyError, PythonImplementation, PythonSpec};
+
+/// Installs an interpreter distributed as a prebuilt archive (PyPy and GraalPy).
+///
+/// # Arguments
+/// - `python`: The interpreter to install.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if no archive exists for this platform, if the archive fails verification,
+///   or if the extracted interpreter doesn't run.
+///
+/// # Guarantees
+/// - If this function returns `Ok(())`, the interpreter is installed in the directory given by `get_python_path`.
+pub fn py_install_prebuilt(python: &PythonSpec) -> Result<(), AnyError> {
+	let python_dir = utils::get_python_path(python);
+
+	if guard!(python_dir.try_exists(), "Unable to know if {} exists", python_dir.display()) {
+		eprintln!("{} is already installed", python_dir.display());
+		return Ok(());
+	}
+
+	eprintln!("Installing Python: {}", python);
+
+	let archive_url = match python.implementation {
+		PythonImplementation::PyPy => find_pypy_download_url(python)?,
+		PythonImplementation::GraalPy => find_graalpy_download_url(python)?,
+		PythonImplementation::CPython => return error!("CPython is built from source, not installed from a prebuilt archive."),
+	};
+	let temp_archive_path = TMP_DIR.join("temp_prebuilt_archive");
+
+	eprintln!("Downloading Python installation files.");
+	guard!(
+		utils::download_file(&archive_url, &temp_archive_path),
+		"Failed to download {}",
+		archive_url
+	);
+
+	eprintln!("Verifying Python installation files.");
+	utils::verify_python_tarball(python, &archive_url, &temp_archive_path)?;
+
+	eprintln!("Extracting archive...");
+	let temp_extract_path_dir = TMP_DIR.join("temp_python_extract");
+	guard!(
+		utils::try_deleting_dir(&temp_extract_path_dir),
+		"Failed to delete {}",
+		temp_extract_path_dir.display()
+	);
+	guard!(fs::create_dir(&temp_extract_path_dir), "Failed to create temp extract directory");
+
+	let tar_process = process::Command::new("tar")
+		.stdin(process::Stdio::null())
+		.stdout(process::Stdio::null())
+		.stderr(process::Stdio::null())
+		.arg("-xf")
+		.arg(&temp_archive_path)
+		.arg("-C")
+		.arg(&temp_extract_path_dir)
+		.status();
+
+	let tar_status = guard!(tar_process, "Failed to extract {}", python);
+	if !tar_status.success() {
+		return error!("Failed to extract {}", python);
+	}
+
+	// The archives contain a single directory named after the release, ex. pypy3.10-v7.3.17-linux64
+	let mut extracted_entries = guard!(fs::read_dir(&temp_extract_path_dir), "Failed to read {}", temp_extract_path_dir.display());
+	let extracted_dir = match extracted_entries.next() {
+		Some(entry) => guard!(entry, "Failed to read directory entry").path(),
+		None => return error!("The archive of {} is empty.", python),
+	};
+
+	eprintln!("Verifying Python install.");
+	let python_process = process::Command::new(extracted_dir.join(python.implementation.executable()))
+		.stdin(process::Stdio::null())
+		.stdout(process::Stdio::null())
+		.stderr(process::Stdio::null())
+		.arg("--version")
+		.status();
+
+	let python_status = guard!(python_process, "Failed to verify if {} is installed.", python);
+	if !python_status.success() {
+		return error!("Failed to verify if {} is installed.", python);
+	}
+
+	eprintln!("Moving files...");
+	guard!(fs::rename(&extracted_dir, &python_dir), "Failed to move {}", python);
+
+	eprintln!("{} installed successfully.", python);
+	return Ok(());
+}
+
+/// Finds the archive of the newest stable PyPy release implementing the requested Python version for this platform.
+fn find_pypy_download_url(python: &PythonSpec) -> Result<String, AnyError> {
+	let request = minreq::get("https://downloads.python.org/pypy/versions.json").with_header("Accept", "application/json");
+	let response = guard!(request.send(), "Couldn't request the PyPy release index.");
+	if response.status_code != 200 {
+		return error!("PyPy release index request failed with status: {}.", response.status_code);
+	}
+	let releases = guard!(
+		response.json::<Vec<ApiPypyRelease>>(),
+		"Received an invalid response from the PyPy release index."
+	);
+
+	let platform = match OS {
+		"macos" => "darwin",
+		_ => OS,
+	};
+	let arch = match ARCH {
+		"x86_64" => "x64",
+		"aarch64" if OS == "macos" => "arm64",
+		_ => ARCH,
+	};
+	let python_prefix = format!("{}.", python.short_version());
+
+	// The index lists the newest releases first
+	for release in releases.iter().filter(|r| r.stable && r.python_version.starts_with(&python_prefix)) {
+		if let Some(file) = release.files.iter().find(|f| f.platform == platform && f.arch == arch) {
+			return Ok(file.download_url.clone());
+		}
+	}
+	return error!("No PyPy release for Python {} is available for {} {}.", python.short_version(), OS, ARCH);
+}
+
+/// Builds the URL of the GraalPy archive implementing the requested Python version for this platform.
+fn find_graalpy_download_url(python: &PythonSpec) -> Result<String, AnyError> {
+	let graalpy_version = match GRAALPY_RELEASES.iter().find(|(py_version, _)| *py_version == python.short_version()) {
+		Some((_, graalpy_version)) => graalpy_version,
+		None => return error!("No GraalPy release is known to implement Python {}.", python.short_version()),
+	};
+	let arch = match ARCH {
+		"x86_64" => "amd64",
+		_ => ARCH,
+	};
+	return Ok(format!(
+		"https://github.com/oracle/graalpython/releases/download/graal-{0}/graalpy-{0}-{1}-{2}.tar.gz",
+		graalpy_version, OS, arch
+	));
+}
+
+// See: https://doc.pypy.org/en/latest/release-process.html#versions-json
+#[derive(Deserialize, Debug)]
+struct ApiPypyRelease {
+	python_version: String,
+	stable: bool,
+	files: Vec<ApiPypyFile>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiPypyFile {
+	arch: String,
+	platform: String,
+	download_url: String,
+}
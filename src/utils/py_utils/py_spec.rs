@@ -0,0 +1,212 @@
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A Python interpreter as written in `pen.toml`, ex. `3.12.4` for CPython or `pypy@3.10` for PyPy.
+///
+/// The version is always the version of the Python language. For CPython it is the exact release,
+/// for the other implementations only the major and minor parts are meaningful.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct PythonSpec {
+	pub implementation: PythonImplementation,
+	pub version: Version,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PythonImplementation {
+	CPython,
+	PyPy,
+	GraalPy,
+}
+
+impl PythonImplementation {
+	/// The name reported by `sys.implementation.name`.
+	pub fn name(&self) -> &'static str {
+		match self {
+			PythonImplementation::CPython => "cpython",
+			PythonImplementation::PyPy => "pypy",
+			PythonImplementation::GraalPy => "graalpy",
+		}
+	}
+
+	/// The path of the interpreter executable, relative to the root of an install.
+	pub fn executable(&self) -> &'static str {
+		match self {
+			PythonImplementation::CPython => "bin/python3",
+			PythonImplementation::PyPy => "bin/pypy3",
+			PythonImplementation::GraalPy => "bin/graalpy",
+		}
+	}
+
+	fn from_name(name: &str) -> Option<PythonImplementation> {
+		match name.to_ascii_lowercase().as_str() {
+			"cpython" => Some(PythonImplementation::CPython),
+			"pypy" => Some(PythonImplementation::PyPy),
+			"graalpy" => Some(PythonImplementation::GraalPy),
+			_ => None,
+		}
+	}
+}
+
+impl PythonSpec {
+	/// Parses a Python given by the user, ex. `3.12.4`, `cpython@3.12.4`, `pypy@3.10` or `graalpy@3.11`.
+	pub fn parse(python: &str) -> Result<PythonSpec, String> {
+		let (implementation, version) = match python.split_once('@') {
+			Some((name, version)) => match PythonImplementation::from_name(name) {
+				Some(implementation) => (implementation, version),
+				None => return Err(format!("Unknown Python implementation {}, expected cpython, pypy or graalpy", name)),
+			},
+			None => (PythonImplementation::CPython, python),
+		};
+
+		let version = match implementation {
+			PythonImplementation::CPython => Version::parse(version),
+			_ if version.matches('.').count() == 1 => Version::parse(&format!("{}.0", version)),
+			_ => Version::parse(version),
+		};
+
+		return match version {
+			Ok(version) => Ok(PythonSpec { implementation, version }),
+			Err(e) => Err(format!("Version parsing failed for Python {}: {}", python, e)),
+		};
+	}
+
+	/// The `major.minor` part of the version, ex. `3.10`.
+	pub fn short_version(&self) -> String {
+		return format!("{}.{}", self.version.major, self.version.minor);
+	}
+
+	/// Checks if an interpreter of the given implementation and version satisfies this spec.
+	pub fn matches(&self, implementation: &str, version: &Version) -> bool {
+		if implementation != self.implementation.name() {
+			return false;
+		}
+		return match self.implementation {
+			PythonImplementation::CPython => &self.version == version,
+			_ => self.version.major == version.major && self.version.minor == version.minor,
+		};
+	}
+
+	/// The name of the directory holding this interpreter's packages in `lib/` of a virtual environment.
+	pub fn lib_dir_name(&self) -> String {
+		return match self.implementation {
+			PythonImplementation::PyPy => format!("pypy{}", self.short_version()),
+			_ => format!("python{}", self.short_version()),
+		};
+	}
+
+	/// The implementation part of the tags used in wheel file names, ex. `cp` or `pp`.
+	pub fn wheel_python_tag_prefix(&self) -> &'static str {
+		match self.implementation {
+			PythonImplementation::CPython => "cp",
+			PythonImplementation::PyPy => "pp",
+			PythonImplementation::GraalPy => "graalpy",
+		}
+	}
+
+	/// The python tag used in wheel file names, ex. `cp312` or `pp310`.
+	pub fn wheel_python_tag(&self) -> String {
+		return format!("{}{}{}", self.wheel_python_tag_prefix(), self.version.major, self.version.minor);
+	}
+
+	/// Checks if a wheel built for a python tag and an ABI tag can be imported by this interpreter, ex. `cp312` and `cp312`,
+	/// `cp38` and `abi3`, `py3` and `none`, or `pp310` and `pypy310_pp73`.
+	///
+	/// # Limitations
+	/// - Only the PyPy 7.3 ABI (`pp73`), used by every PyPy release since 2020, is supported.
+	/// - Debug and free-threaded CPython ABIs (ex. `cp313t`) are never supported.
+	pub fn supports_wheel_tags(&self, python_tag: &str, abi_tag: &str) -> bool {
+		let (major, minor) = (self.version.major, self.version.minor);
+		let version_tag = format!("{}{}", major, minor);
+
+		// Pure Python wheels for this version or an older one of the same major version, ex. `py3`, `py38` or `cp312-none`
+		if abi_tag == "none" {
+			if python_tag == self.wheel_python_tag() {
+				return true;
+			}
+			return match python_tag.strip_prefix("py").map(|version| version.split_at(version.len().min(1))) {
+				Some((tag_major, "")) => tag_major == major.to_string(),
+				Some((tag_major, tag_minor)) => tag_major == major.to_string() && tag_minor.parse::<u64>().is_ok_and(|tag_minor| tag_minor <= minor),
+				None => false,
+			};
+		}
+
+		return match self.implementation {
+			// The stable ABI works with any CPython at least as new as the one it was built for
+			PythonImplementation::CPython if abi_tag == "abi3" => match python_tag
+				.strip_prefix("cp")
+				.map(|version| version.split_at(version.len().min(1)))
+			{
+				Some((tag_major, tag_minor)) => tag_major == major.to_string() && tag_minor.parse::<u64>().is_ok_and(|tag_minor| tag_minor <= minor),
+				None => false,
+			},
+			PythonImplementation::CPython => python_tag == self.wheel_python_tag() && abi_tag == format!("cp{}", version_tag),
+			// PyPy doesn't implement the CPython stable ABI, its ABI tags look like `pypy310_pp73`
+			PythonImplementation::PyPy => python_tag == self.wheel_python_tag() && abi_tag == format!("pypy{}_pp73", version_tag),
+			// ex. `graalpy311_graalpy242_311_native`
+			PythonImplementation::GraalPy => python_tag == self.wheel_python_tag() && abi_tag.starts_with(&format!("graalpy{}_", version_tag)),
+		};
+	}
+}
+
+impl fmt::Display for PythonSpec {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		return match self.implementation {
+			PythonImplementation::CPython => write!(f, "{}", self.version),
+			_ => write!(f, "{}@{}", self.implementation.name(), self.short_version()),
+		};
+	}
+}
+
+impl TryFrom<String> for PythonSpec {
+	type Error = String;
+
+	fn try_from(python: String) -> Result<Self, Self::Error> {
+		return PythonSpec::parse(&python);
+	}
+}
+
+impl From<PythonSpec> for String {
+	fn from(python: PythonSpec) -> Self {
+		return python.to_string();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cpython_wheel_tags() {
+		let python = PythonSpec::parse("3.12.4").unwrap();
+		assert!(python.supports_wheel_tags("cp312", "cp312"));
+		assert!(python.supports_wheel_tags("cp38", "abi3"));
+		assert!(python.supports_wheel_tags("py3", "none"));
+		assert!(python.supports_wheel_tags("py311", "none"));
+		assert!(python.supports_wheel_tags("cp312", "none"));
+		assert!(!python.supports_wheel_tags("cp313", "abi3"));
+		assert!(!python.supports_wheel_tags("cp311", "cp311"));
+		assert!(!python.supports_wheel_tags("cp313", "cp313t"));
+		assert!(!python.supports_wheel_tags("py2", "none"));
+		assert!(!python.supports_wheel_tags("pp310", "pypy310_pp73"));
+	}
+
+	#[test]
+	fn pypy_wheel_tags() {
+		let python = PythonSpec::parse("pypy@3.10").unwrap();
+		assert!(python.supports_wheel_tags("pp310", "pypy310_pp73"));
+		assert!(python.supports_wheel_tags("py3", "none"));
+		assert!(!python.supports_wheel_tags("cp38", "abi3"));
+		assert!(!python.supports_wheel_tags("cp310", "cp310"));
+		assert!(!python.supports_wheel_tags("pp39", "pypy39_pp73"));
+		assert!(!python.supports_wheel_tags("pp310", "pypy310_pp72"));
+	}
+
+	#[test]
+	fn graalpy_wheel_tags() {
+		let python = PythonSpec::parse("graalpy@3.11").unwrap();
+		assert!(python.supports_wheel_tags("graalpy311", "graalpy311_graalpy242_311_native"));
+		assert!(!python.supports_wheel_tags("cp311", "abi3"));
+	}
+}
@@ -0,0 +1,294 @@
+use minisign_verify::{PublicKey, Signature};
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{fs, path::PathBuf};
+
+use crate::utils::{error, guard, read_global_config, AnyError, PythonImplementation, PythonSpec};
+
+/// Verifies a downloaded Python tarball before anything in it gets built or run.
+///
+/// # Arguments
+/// - `python`: The interpreter the tarball is supposed to contain.
+/// - `tarball_url`: The URL the tarball was downloaded from, used to find its checksum.
+/// - `tarball_path`: The path of the downloaded tarball.
+///
+/// # Output
+/// - None.
+///
+/// # Termination
+/// - This function returns an error if no checksum can be found for the version, if the checksum doesn't match
+///   or if a public key is configured and the signature is missing or invalid.
+///
+/// # Guarantees
+/// - If this function returns `Ok(())`, the tarball matches a known checksum (and signature, when a key is configured).
+///
+/// # Limitations
+/// - python.org and the PyPy and GraalPy releases don't publish minisign signatures, only Sigstore or GPG ones. Signatures are
+///   downloaded from `python_signatures_url`, ex. a mirror or a directory served by the user, as `<tarball file name>.minisig`.
+pub fn verify_python_tarball(python: &PythonSpec, tarball_url: &str, tarball_path: &PathBuf) -> Result<(), AnyError> {
+	let global_config = read_global_config()?;
+	let tarball = guard!(fs::read(tarball_path), "Couldn't read {}.", tarball_path.display());
+	let file_name = tarball_url.rsplit('/').next().unwrap_or(tarball_url);
+
+	let expected_checksum = match (&global_config.python_checksums, python.implementation) {
+		(Some(manifest_path), _) => find_pinned_checksum(manifest_path, file_name)?,
+		(None, PythonImplementation::CPython) => find_index_checksum(&python.version, file_name)?,
+		(None, PythonImplementation::GraalPy) => find_github_checksum(tarball_url, file_name)?,
+		(None, PythonImplementation::PyPy) => find_pypy_checksum(file_name)?,
+	};
+	verify_checksum(&tarball, &expected_checksum, file_name)?;
+
+	if let Some(public_key_path) = &global_config.python_public_key {
+		let signatures_url = match &global_config.python_signatures_url {
+			Some(signatures_url) => signatures_url,
+			None => {
+				return error!(
+					"python_public_key is set but python_signatures_url isn't. The Python releases don't publish minisign signatures, set it to where yours are."
+				)
+			}
+		};
+		let signature_url = format!("{}/{}.minisig", signatures_url.trim_end_matches('/'), file_name);
+		let response = guard!(minreq::get(&signature_url).send(), "Couldn't download {}.", signature_url);
+		if response.status_code != 200 {
+			return error!("Signature download request failed with status: {}.", response.status_code);
+		}
+		let signature_text = guard!(response.as_str(), "Signature at {} isn't valid text.", signature_url);
+		verify_signature(&tarball, public_key_path, signature_text, file_name)?;
+	}
+
+	return Ok(());
+}
+
+/// Checks that the sha256 of a tarball is `expected_checksum`, a hex digest.
+fn verify_checksum(tarball: &[u8], expected_checksum: &str, file_name: &str) -> Result<(), AnyError> {
+	let actual_checksum = format!("{:x}", Sha256::digest(tarball));
+	if !actual_checksum.eq_ignore_ascii_case(expected_checksum) {
+		return error!(
+			"Checksum mismatch for {}: expected {}, got {}. Refusing to build.",
+			file_name, expected_checksum, actual_checksum
+		);
+	}
+	return Ok(());
+}
+
+/// Checks the minisign signature of a tarball against the public key at `public_key_path`.
+fn verify_signature(tarball: &[u8], public_key_path: &PathBuf, signature_text: &str, file_name: &str) -> Result<(), AnyError> {
+	let public_key = guard!(
+		PublicKey::from_file(public_key_path),
+		"Couldn't read public key {}.",
+		public_key_path.display()
+	);
+	let signature = guard!(Signature::decode(signature_text), "Couldn't decode signature of {}.", file_name);
+	guard!(
+		public_key.verify(tarball, &signature, false),
+		"Signature verification failed for {}. Refusing to build.",
+		file_name
+	);
+	return Ok(());
+}
+
+/// Looks up the checksum of `file_name` in a manifest in the `sha256sum` format (`<hex digest>  <file name>`).
+fn find_pinned_checksum(manifest_path: &PathBuf, file_name: &str) -> Result<String, AnyError> {
+	let manifest = guard!(fs::read_to_string(manifest_path), "Couldn't read {}.", manifest_path.display());
+	match find_manifest_checksum(&manifest, file_name) {
+		Some(checksum) => return Ok(checksum),
+		None => return error!("No checksum pinned for {} in {}.", file_name, manifest_path.display()),
+	}
+}
+
+/// Finds the line of `file_name` in text in the `sha256sum` format, ignoring every other line.
+fn find_manifest_checksum(manifest: &str, file_name: &str) -> Option<String> {
+	for line in manifest.lines() {
+		let mut parts = line.split_whitespace();
+		if let (Some(checksum), Some(name)) = (parts.next(), parts.next()) {
+			let is_sha256 = checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit());
+			if is_sha256 && name.trim_start_matches('*') == file_name {
+				return Some(checksum.to_string());
+			}
+		}
+	}
+	return None;
+}
+
+/// Looks up the checksum of `file_name` in the sha256 sums published on pypy.org.
+fn find_pypy_checksum(file_name: &str) -> Result<String, AnyError> {
+	let response = guard!(
+		minreq::get("https://www.pypy.org/checksums.html").send(),
+		"Couldn't request the PyPy checksums."
+	);
+	if response.status_code != 200 {
+		return error!("PyPy checksums request failed with status: {}.", response.status_code);
+	}
+	let page = guard!(response.as_str(), "Received an invalid response from pypy.org.");
+
+	// The sums are listed in the `sha256sum` format, one per line, inside the HTML page
+	match find_manifest_checksum(page, file_name) {
+		Some(checksum) => return Ok(checksum),
+		None => return error!("pypy.org has no sha256 checksum for {}. Pin one in python_checksums.", file_name),
+	}
+}
+
+/// Looks up the checksum of `file_name` in the python.org release index.
+fn find_index_checksum(version: &Version, file_name: &str) -> Result<String, AnyError> {
+	let release_url = format!("https://www.python.org/api/v2/downloads/release/?name=Python%20{}", version);
+	let response = guard!(minreq::get(&release_url).send(), "Couldn't request the python.org release index.");
+	if response.status_code != 200 {
+		return error!("Release index request failed with status: {}.", response.status_code);
+	}
+	let releases = guard!(response.json::<Vec<ApiRelease>>(), "Received an invalid response from python.org.");
+
+	let release_id = match releases
+		.first()
+		.and_then(|release| release.resource_uri.trim_end_matches('/').rsplit('/').next())
+	{
+		Some(id) => id.to_string(),
+		None => return error!("Python {} isn't in the python.org release index.", version),
+	};
+
+	let files_url = format!("https://www.python.org/api/v2/downloads/release_file/?release={}", release_id);
+	let response = guard!(minreq::get(&files_url).send(), "Couldn't request the python.org release index.");
+	if response.status_code != 200 {
+		return error!("Release index request failed with status: {}.", response.status_code);
+	}
+	let files = guard!(response.json::<Vec<ApiReleaseFile>>(), "Received an invalid response from python.org.");
+
+	match files
+		.iter()
+		.find(|file| file.url.ends_with(file_name))
+		.and_then(|file| file.sha256_sum.clone())
+	{
+		Some(checksum) if !checksum.is_empty() => return Ok(checksum),
+		_ => {
+			return error!(
+				"The python.org release index has no sha256 checksum for {}. Pin one in python_checksums.",
+				file_name
+			)
+		}
+	}
+}
+
+/// Looks up the checksum of `file_name` in the GitHub release it was downloaded from.
+fn find_github_checksum(tarball_url: &str, file_name: &str) -> Result<String, AnyError> {
+	// Release downloads look like https://github.com/{owner}/{repo}/releases/download/{tag}/{file_name}
+	let parts = tarball_url.trim_start_matches("https://github.com/").split('/').collect::<Vec<&str>>();
+	if parts.len() != 6 || parts[2] != "releases" || parts[3] != "download" {
+		return error!("{} isn't a GitHub release download.", tarball_url);
+	}
+
+	let release_url = format!("https://api.github.com/repos/{}/{}/releases/tags/{}", parts[0], parts[1], parts[4]);
+	let request = minreq::get(&release_url)
+		.with_header("Accept", "application/json")
+		.with_header("User-Agent", "pen");
+	let response = guard!(request.send(), "Couldn't request the GitHub release index.");
+	if response.status_code != 200 {
+		return error!("GitHub release request failed with status: {}.", response.status_code);
+	}
+	let release = guard!(response.json::<ApiGithubRelease>(), "Received an invalid response from GitHub.");
+
+	match release
+		.assets
+		.iter()
+		.find(|asset| asset.name == file_name)
+		.and_then(|asset| asset.digest.as_ref())
+		.and_then(|digest| digest.strip_prefix("sha256:"))
+	{
+		Some(checksum) => return Ok(checksum.to_string()),
+		None => {
+			return error!(
+				"The GitHub release has no sha256 checksum for {}. Pin one in python_checksums.",
+				file_name
+			)
+		}
+	}
+}
+
+// See: https://www.python.org/api/v2/downloads/release/
+#[derive(Deserialize, Debug)]
+struct ApiRelease {
+	resource_uri: String,
+}
+
+// See: https://www.python.org/api/v2/downloads/release_file/
+#[derive(Deserialize, Debug)]
+struct ApiReleaseFile {
+	url: String,
+	sha256_sum: Option<String>,
+}
+
+// See: https://docs.github.com/en/rest/releases/releases#get-a-release-by-tag-name
+#[derive(Deserialize, Debug)]
+struct ApiGithubRelease {
+	assets: Vec<ApiGithubAsset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiGithubAsset {
+	name: String,
+	digest: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+
+	static TARBALL: &[u8] = b"fake Python-3.12.4.tgz contents\n";
+	static TARBALL_SHA256: &str = "6f1c4cb261220a98b24f962658e413cad38205d8371f3aa6ae230d775ae46534";
+	static PUBLIC_KEY: &str = "untrusted comment: minisign public key 0807060504030201\nRWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4\n";
+	static SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQBAgMEBQYHCKIxRnCsYKBq3a/Ejql3RZa3VdTchv9pNZ4k/7ZZ4jJITFJfNvtIk+zB38ppPbD5rlyHR8BfG9zZVzUKOrMvbAo=
+trusted comment: timestamp:1700000000\tfile:Python-3.12.4.tgz\tprehashed
+aqyvxgqyy1TbsUXlynwR/bF4DAQiYrt59FOWLGBi6EnpMSWH+N/yeR6qd0bYLjr8Vi1WHzOB4Ek/sIreJypKAQ==
+";
+
+	fn write_public_key(name: &str) -> PathBuf {
+		let key_path = env::temp_dir().join(format!("pen-test-{}-{}.pub", name, std::process::id()));
+		fs::write(&key_path, PUBLIC_KEY).unwrap();
+		return key_path;
+	}
+
+	#[test]
+	fn checksum_matches() {
+		assert!(verify_checksum(TARBALL, TARBALL_SHA256, "Python-3.12.4.tgz").is_ok());
+		assert!(verify_checksum(TARBALL, &TARBALL_SHA256.to_uppercase(), "Python-3.12.4.tgz").is_ok());
+	}
+
+	#[test]
+	fn checksum_mismatch_is_rejected() {
+		let tampered = b"fake Python-3.12.4.tgz contents, tampered\n";
+		let e = verify_checksum(tampered, TARBALL_SHA256, "Python-3.12.4.tgz").unwrap_err();
+		assert!(e.to_string().contains("Checksum mismatch for Python-3.12.4.tgz"));
+	}
+
+	#[test]
+	fn manifest_checksum_is_found() {
+		let manifest = format!(
+			"<pre>pypy3.10-v7.3.17 sha256:\n\n{0}  pypy3.10-v7.3.17-aarch64.tar.bz2\n{1} *pypy3.10-v7.3.17-linux64.tar.bz2\n</pre>",
+			"a".repeat(64),
+			TARBALL_SHA256
+		);
+		assert_eq!(
+			find_manifest_checksum(&manifest, "pypy3.10-v7.3.17-linux64.tar.bz2").as_deref(),
+			Some(TARBALL_SHA256)
+		);
+		assert_eq!(find_manifest_checksum(&manifest, "pypy3.10-v7.3.17-macos_arm64.tar.bz2"), None);
+		assert_eq!(find_manifest_checksum("<p>not-a-checksum pypy.tar.bz2</p>", "pypy.tar.bz2"), None);
+	}
+
+	#[test]
+	fn signature_verifies() {
+		let key_path = write_public_key("verify");
+		let result = verify_signature(TARBALL, &key_path, SIGNATURE, "Python-3.12.4.tgz");
+		fs::remove_file(&key_path).unwrap();
+		assert!(result.is_ok());
+	}
+
+	#[test]
+	fn signature_of_another_file_is_rejected() {
+		let key_path = write_public_key("reject");
+		let result = verify_signature(b"another tarball", &key_path, SIGNATURE, "Python-3.12.4.tgz");
+		fs::remove_file(&key_path).unwrap();
+		assert!(result.is_err());
+	}
+}
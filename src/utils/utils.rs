@@ -1,7 +1,5 @@
-use crate::constants::{HOME_DIR, PEN_CONFIG_FILE, PEN_DIR, PYTHON_PACKAGES_DIR, PYTHON_VERSIONS_DIR, TMP_DIR};
-use crate::utils::{error, guard, AnyError, Config, Package};
-use semver::Version;
-use serde_json::Value;
+use crate::constants::{HOME_DIR, LOCKS_DIR, PEN_CONFIG_FILE, PEN_DIR, PYTHON_PACKAGES_DIR, PYTHON_VERSIONS_DIR, TEMP_ROOT_DIR};
+use crate::utils::{self, download_package, error, get_lock_path, guard, lock_exclusive, AnyError, Package, PythonSpec};
 use std::{
 	fs,
 	io::{self, Write},
@@ -10,18 +8,9 @@ use std::{
 };
 
 // todo docstring
-pub fn user_string_to_version(version: &String) -> Result<Version, AnyError> {
-	let version = guard!(Version::parse(version), "Version parsing failed for version {version}");
-	return Ok(version);
-	// match version {
-	// 	Some(version) => {
-	// 		let version = guard!(Version::parse(version), "Version parsing failed for version {version}");
-	// 		return Ok(version);
-	// 	}
-	// 	None => {
-	// 		todo!("pick the most recent version either from the web or the system wide installation")
-	// 	}
-	// }
+pub fn user_string_to_python(python: &str) -> Result<PythonSpec, AnyError> {
+	let python = guard!(PythonSpec::parse(python), "Python parsing failed for {python}");
+	return Ok(python);
 }
 
 // /// Asserts that a given version string adheres to the "major.minor.patch" format.
@@ -95,11 +84,10 @@ pub fn confirm_action(prompt: &str) -> Result<bool, AnyError> {
 /// - The function guarantees the downloaded file exists.
 ///
 /// # Limitations
-/// - The function does not validate the contents of the downloaded file.
+/// - The function does not validate the contents of the downloaded file. Python tarballs are checked with `verify_python_tarball`.
 pub fn download_file(file_url: &str, file_path: &PathBuf) -> Result<(), AnyError> {
-	match guard!(fs::exists(file_path), "todo") {
-		true => guard!(fs::remove_file(file_path), "todo"),
-		false => (),
+	if guard!(fs::exists(file_path), "todo") {
+		guard!(fs::remove_file(file_path), "todo");
 	}
 
 	let response = guard!(minreq::get(file_url).send(), "todo");
@@ -111,46 +99,6 @@ pub fn download_file(file_url: &str, file_path: &PathBuf) -> Result<(), AnyError
 	return Ok(());
 }
 
-/// Takes the major and minor version and returns the full version using https://endoflife.date/api/python.json
-///
-/// # Arguments
-/// - `major_minor_version` : a string representing the "x.y" part of the release
-///
-/// # Output
-/// - Will output the full version from the provided major & minor
-///
-/// #Termination
-/// - An error should be thrown if the file for checking is not found, or if the version is not found.
-///
-/// # Limitations
-/// - The function assumes that all data passed to it is in the correct format
-pub fn get_full_python_version(major_minor_version: &str) -> Result<Option<String>, AnyError> {
-	let request = minreq::get("https://endoflife.date/api/python.json").with_header("Accept", "application/json");
-	let response = guard!(request.send(), "todo");
-	if response.status_code != 200 {
-		return error!("todo");
-	}
-
-	let json = guard!(response.json::<Value>(), "Failed to parse response into json.");
-
-	if let Some(json) = json.as_array() {
-		for item in json {
-			match item.as_object() {
-				Some(items) => {
-					for (key, value) in items {
-						if (key == "cycle") && (value == major_minor_version) {
-							// returns the latest
-							return Ok(Some(item["latest"].to_string()));
-						}
-					}
-				}
-				None => return error!("todo"),
-			}
-		}
-	}
-	return Ok(None);
-}
-
 /// Checks if the specified dependencies are installed by running their `--help` command.
 ///
 /// # Arguments
@@ -213,7 +161,14 @@ pub fn assert_global_paths() -> Result<(), AnyError> {
 	}
 
 	// No need to check for PEN_BIN since it is only used when uninstalling, where it is checked for existence.
-	let dirs_to_check = vec![(&*PEN_DIR), (&*PYTHON_PACKAGES_DIR), (&*TMP_DIR), (&*PYTHON_VERSIONS_DIR)];
+	// TMP_DIR is created by `create_process_temp`, once it is locked
+	let dirs_to_check = vec![
+		(&*PEN_DIR),
+		(&*PYTHON_PACKAGES_DIR),
+		(&*TEMP_ROOT_DIR),
+		(&*LOCKS_DIR),
+		(&*PYTHON_VERSIONS_DIR),
+	];
 
 	for path in dirs_to_check {
 		guard!(create_dir_if_missing(path, false), "todo");
@@ -247,14 +202,57 @@ pub fn create_dir_if_missing(dir_path: &PathBuf, overwrite: bool) -> Result<(),
 	return Ok(());
 }
 
-pub fn get_recursive_dependencies(config: &Config) -> Vec<Package> {
-	todo!()
+/// Computes the size of a directory and everything in it.
+///
+/// # Output
+/// - The size in bytes. Symlinks count for their own size, what they point to isn't followed.
+///
+/// # Termination
+/// - This function returns an error if an entry can't be read.
+pub fn get_dir_size(dir_path: &PathBuf) -> Result<u64, AnyError> {
+	let metadata = guard!(fs::symlink_metadata(dir_path), "Failed to read metadata of {}", dir_path.display());
+	if !metadata.is_dir() {
+		return Ok(metadata.len());
+	}
+
+	let mut size = 0;
+	let entries = guard!(fs::read_dir(dir_path), "Failed to read {}", dir_path.display());
+	for entry in entries {
+		let entry = guard!(entry, "Failed to read directory entry");
+		size += get_dir_size(&entry.path())?;
+	}
+	return Ok(size);
 }
 
-pub fn download_dep_if_missing(dependency: &Package) -> () {
-	todo!()
+/// Formats a size in bytes for humans, ex. `12.3 MiB`.
+pub fn format_size(bytes: u64) -> String {
+	let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+	let mut size = bytes as f64;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < units.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	match unit {
+		0 => return format!("{} {}", bytes, units[0]),
+		_ => return format!("{:.1} {}", size, units[unit]),
+	}
 }
 
-pub fn get_info_of_package(package: &Package) {
-	// since the json returned from the url is very complicated, it is expected to add new information to return when needed since we do not want to specify a json structure.
+/// Downloads a package into the cache unless it is already there.
+///
+/// # Termination
+/// - This function returns an error if the package can't be locked or downloaded.
+///
+/// # Guarantees
+/// - Concurrent pen processes download a given package only once, the others wait for it and reuse it.
+pub fn download_dep_if_missing(dependency: &Package, python: &PythonSpec) -> Result<(), AnyError> {
+	let package_path = utils::get_package_path(dependency);
+	let _package_lock = lock_exclusive(&get_lock_path(&package_path))?;
+
+	let package_path_exists = guard!(fs::exists(&package_path), "Couldn't see if package is installed");
+	if !package_path_exists {
+		download_package(dependency, python)?;
+	}
+	return Ok(());
 }